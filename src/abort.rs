@@ -0,0 +1,67 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+
+use futures_util::future::{AbortHandle, AbortRegistration};
+
+/// Abort handles of the requests currently in flight.
+#[derive(Default)]
+pub(crate) struct InFlight {
+    next_id: Cell<u64>,
+    handles: RefCell<HashMap<u64, AbortHandle>>,
+    closed: Cell<bool>,
+}
+
+impl InFlight {
+    /// Track a new request until the returned guard is dropped.
+    pub fn register(self: &Rc<Self>) -> (AbortRegistration, InFlightGuard) {
+        let (handle, regis) = AbortHandle::new_pair();
+
+        let guard = self.track(handle);
+
+        (regis, guard)
+    }
+
+    /// Track this handle until the returned guard is dropped, aborting it at once if closed.
+    pub fn track(self: &Rc<Self>, handle: AbortHandle) -> InFlightGuard {
+        let id = self.next_id.get();
+        self.next_id.set(id.wrapping_add(1));
+
+        if self.closed.get() {
+            handle.abort();
+        } else {
+            self.handles.borrow_mut().insert(id, handle);
+        }
+
+        InFlightGuard {
+            in_flight: self.clone(),
+            id,
+        }
+    }
+
+    pub fn abort_all(&self) {
+        for (_, handle) in self.handles.borrow_mut().drain() {
+            handle.abort();
+        }
+    }
+
+    /// Abort all and every request tracked from now on.
+    pub fn close(&self) {
+        self.closed.set(true);
+
+        self.abort_all();
+    }
+}
+
+pub(crate) struct InFlightGuard {
+    in_flight: Rc<InFlight>,
+    id: u64,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.in_flight.handles.borrow_mut().remove(&self.id);
+    }
+}
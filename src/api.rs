@@ -0,0 +1,143 @@
+use bytes::Bytes;
+
+use cid::Cid;
+
+use futures_util::{future::LocalBoxFuture, FutureExt};
+
+#[cfg(feature = "pubsub")]
+use futures_util::{stream::LocalBoxStream, StreamExt};
+
+use crate::{
+    path::IpfsPath,
+    peer_id::PeerId,
+    responses::{KeyInfo, NamePublishResponse, PinList, PinType},
+    IpfsService, Result,
+};
+
+#[cfg(feature = "pubsub")]
+use crate::{pubsub::Subscription, responses::PubSubMsg};
+
+/// Stream of messages of a subscription.
+#[cfg(feature = "pubsub")]
+pub type MsgStream = LocalBoxStream<'static, Result<PubSubMsg>>;
+
+/// Operations of an IPFS node.
+///
+/// Depend on `dyn IpfsApi` or a generic to swap the HTTP client for `MockIpfs` in tests, see the `testing` feature.
+/// DAG nodes are exchanged as JSON values to keep the trait object safe.
+pub trait IpfsApi {
+    fn add(&self, bytes: Bytes) -> LocalBoxFuture<'_, Result<Cid>>;
+
+    fn cat(&self, path: IpfsPath) -> LocalBoxFuture<'_, Result<Bytes>>;
+
+    fn block_get(&self, cid: Cid) -> LocalBoxFuture<'_, Result<Bytes>>;
+
+    fn dag_put(&self, node: serde_json::Value) -> LocalBoxFuture<'_, Result<Cid>>;
+
+    fn dag_get(&self, path: IpfsPath) -> LocalBoxFuture<'_, Result<serde_json::Value>>;
+
+    fn pin_add(&self, cid: Cid, recursive: bool) -> LocalBoxFuture<'_, Result<()>>;
+
+    fn pin_rm(&self, cid: Cid, recursive: bool) -> LocalBoxFuture<'_, Result<()>>;
+
+    fn pin_ls(&self, pin_type: Option<PinType>) -> LocalBoxFuture<'_, Result<PinList>>;
+
+    fn key_list(&self) -> LocalBoxFuture<'_, Result<Vec<KeyInfo>>>;
+
+    fn name_publish(
+        &self,
+        cid: Cid,
+        key: String,
+    ) -> LocalBoxFuture<'_, Result<NamePublishResponse>>;
+
+    fn name_resolve(&self, ipns: Cid) -> LocalBoxFuture<'_, Result<IpfsPath>>;
+
+    fn peer_id(&self) -> LocalBoxFuture<'_, Result<PeerId>>;
+
+    #[cfg(feature = "pubsub")]
+    fn pubsub_pub(&self, topic: Vec<u8>, data: Vec<u8>) -> LocalBoxFuture<'_, Result<()>>;
+
+    #[cfg(feature = "pubsub")]
+    fn pubsub_sub(&self, topic: Vec<u8>) -> LocalBoxFuture<'_, Result<(MsgStream, Subscription)>>;
+}
+
+impl IpfsApi for IpfsService {
+    #[cfg(target_arch = "wasm32")]
+    fn add(&self, bytes: Bytes) -> LocalBoxFuture<'_, Result<Cid>> {
+        IpfsService::add(self, bytes).boxed_local()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn add(&self, bytes: Bytes) -> LocalBoxFuture<'_, Result<Cid>> {
+        let stream = futures_util::stream::once(async { Ok::<_, std::io::Error>(bytes) });
+
+        IpfsService::add(self, stream).boxed_local()
+    }
+
+    fn cat(&self, path: IpfsPath) -> LocalBoxFuture<'_, Result<Bytes>> {
+        IpfsService::cat(self, path).boxed_local()
+    }
+
+    fn block_get(&self, cid: Cid) -> LocalBoxFuture<'_, Result<Bytes>> {
+        IpfsService::block_get(self, cid).boxed_local()
+    }
+
+    fn dag_put(&self, node: serde_json::Value) -> LocalBoxFuture<'_, Result<Cid>> {
+        async move { IpfsService::dag_put(self, &node).await }.boxed_local()
+    }
+
+    fn dag_get(&self, path: IpfsPath) -> LocalBoxFuture<'_, Result<serde_json::Value>> {
+        IpfsService::dag_get(self, path).boxed_local()
+    }
+
+    fn pin_add(&self, cid: Cid, recursive: bool) -> LocalBoxFuture<'_, Result<()>> {
+        IpfsService::pin_add(self, cid, recursive)
+            .map(|res| res.map(|_| ()))
+            .boxed_local()
+    }
+
+    fn pin_rm(&self, cid: Cid, recursive: bool) -> LocalBoxFuture<'_, Result<()>> {
+        IpfsService::pin_rm(self, cid, recursive)
+            .map(|res| res.map(|_| ()))
+            .boxed_local()
+    }
+
+    fn pin_ls(&self, pin_type: Option<PinType>) -> LocalBoxFuture<'_, Result<PinList>> {
+        IpfsService::pin_ls(self, pin_type).boxed_local()
+    }
+
+    fn key_list(&self) -> LocalBoxFuture<'_, Result<Vec<KeyInfo>>> {
+        IpfsService::key_list(self).boxed_local()
+    }
+
+    fn name_publish(
+        &self,
+        cid: Cid,
+        key: String,
+    ) -> LocalBoxFuture<'_, Result<NamePublishResponse>> {
+        IpfsService::name_publish(self, cid, key).boxed_local()
+    }
+
+    fn name_resolve(&self, ipns: Cid) -> LocalBoxFuture<'_, Result<IpfsPath>> {
+        IpfsService::name_resolve(self, ipns).boxed_local()
+    }
+
+    fn peer_id(&self) -> LocalBoxFuture<'_, Result<PeerId>> {
+        IpfsService::peer_id(self).boxed_local()
+    }
+
+    #[cfg(feature = "pubsub")]
+    fn pubsub_pub(&self, topic: Vec<u8>, data: Vec<u8>) -> LocalBoxFuture<'_, Result<()>> {
+        IpfsService::pubsub_pub(self, topic, data).boxed_local()
+    }
+
+    #[cfg(feature = "pubsub")]
+    fn pubsub_sub(&self, topic: Vec<u8>) -> LocalBoxFuture<'_, Result<(MsgStream, Subscription)>> {
+        async move {
+            let (stream, handle) = IpfsService::pubsub_sub(self, topic).await?;
+
+            Ok((stream.boxed_local(), handle))
+        }
+        .boxed_local()
+    }
+}
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// Exponential backoff between reconnection attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Backoff {
+    /// Delay before the first attempt.
+    #[serde(with = "config::duration")]
+    pub initial: Duration,
+
+    /// Upper bound on any single delay.
+    #[serde(with = "config::duration")]
+    pub max: Duration,
+
+    /// Multiplier applied after each failed attempt.
+    pub factor: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(500),
+            max: Duration::from_secs(30),
+            factor: 2,
+        }
+    }
+}
+
+impl Backoff {
+    /// Delay to wait before attempt number `attempt`, starting at 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1);
+        let multiplier = self.factor.saturating_pow(exponent);
+
+        self.initial.saturating_mul(multiplier).min(self.max)
+    }
+}
@@ -0,0 +1,49 @@
+use std::str::FromStr;
+
+use cid::{multibase::Base, Cid};
+
+use crate::peer_id::PeerId;
+
+/// Multibase of the IPNS names returned by the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IpnsBase {
+    #[default]
+    Base32,
+
+    /// `k51...` names, the default of recent Kubo versions.
+    Base36,
+
+    Base58Btc,
+}
+
+impl IpnsBase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Base32 => "base32",
+            Self::Base36 => "base36",
+            Self::Base58Btc => "base58btc",
+        }
+    }
+
+    /// Encode a CID in this base, e.g. to display an IPNS name.
+    pub fn encode(&self, cid: &Cid) -> String {
+        let base = match self {
+            Self::Base32 => Base::Base32Lower,
+            Self::Base36 => Base::Base36Lower,
+            Self::Base58Btc => Base::Base58Btc,
+        };
+
+        // Only CIDv0 cannot be encoded in another base than base58btc
+        cid.to_string_of_base(base)
+            .unwrap_or_else(|_| cid.to_string())
+    }
+
+    /// The IPNS name in this base, given in any base or as a legacy peer ID, e.g. `12D3KooW...`.
+    ///
+    /// Compare names by their `PeerId` instead, parsed the same way.
+    pub fn convert(&self, name: &str) -> Result<String, cid::Error> {
+        let peer_id = PeerId::from_str(name)?;
+
+        Ok(peer_id.to_ipns_name(*self))
+    }
+}
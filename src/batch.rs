@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+use cid::Cid;
+
+use futures_util::{stream, StreamExt};
+
+use reqwest::multipart::{Form, Part};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{IpfsService, Result};
+
+/// Outcome of a batch of pin operations.
+#[derive(Debug, Default)]
+pub struct PinBatchReport {
+    pub succeeded: Vec<Cid>,
+
+    pub failed: Vec<(Cid, Box<dyn std::error::Error>)>,
+}
+
+impl PinBatchReport {
+    fn record<T>(&mut self, cid: Cid, res: Result<T>) {
+        match res {
+            Ok(_) => self.succeeded.push(cid),
+            Err(e) => self.failed.push((cid, e)),
+        }
+    }
+}
+
+impl IpfsService {
+    /// Store the dag nodes, at most `concurrency` at once.
+    ///
+    /// Results are in the order of the nodes, a failure does not stop the others.
+    pub async fn dag_put_many<'a, I, T>(&self, nodes: I, concurrency: usize) -> Vec<Result<Cid>>
+    where
+        I: IntoIterator<Item = &'a T>,
+        T: ?Sized + Serialize + 'a,
+    {
+        stream::iter(nodes)
+            .map(|node| self.dag_put(node))
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Deserialize the dag nodes, at most `concurrency` fetched at once.
+    pub async fn dag_get_many<I, T>(&self, cids: I, concurrency: usize) -> HashMap<Cid, Result<T>>
+    where
+        I: IntoIterator<Item = Cid>,
+        T: DeserializeOwned,
+    {
+        stream::iter(cids)
+            .map(|cid| async move { (cid, self.dag_get(cid).await) })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Download the content of the CIDs, at most `concurrency` at once.
+    pub async fn cat_many<I>(&self, cids: I, concurrency: usize) -> HashMap<Cid, Result<Bytes>>
+    where
+        I: IntoIterator<Item = Cid>,
+    {
+        stream::iter(cids)
+            .map(|cid| async move { (cid, self.cat(cid).await) })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Pin the CIDs, at most `concurrency` requests at once.
+    pub async fn pin_add_many<I>(
+        &self,
+        cids: I,
+        recursive: bool,
+        concurrency: usize,
+    ) -> PinBatchReport
+    where
+        I: IntoIterator<Item = Cid>,
+    {
+        let mut results = stream::iter(cids)
+            .map(|cid| async move { (cid, self.pin_add(cid, recursive).await) })
+            .buffer_unordered(concurrency.max(1));
+
+        let mut report = PinBatchReport::default();
+
+        while let Some((cid, res)) = results.next().await {
+            report.record(cid, res);
+        }
+
+        report
+    }
+
+    /// Unpin the CIDs, at most `concurrency` requests at once.
+    pub async fn pin_rm_many<I>(
+        &self,
+        cids: I,
+        recursive: bool,
+        concurrency: usize,
+    ) -> PinBatchReport
+    where
+        I: IntoIterator<Item = Cid>,
+    {
+        let mut results = stream::iter(cids)
+            .map(|cid| async move { (cid, self.pin_rm(cid, recursive).await) })
+            .buffer_unordered(concurrency.max(1));
+
+        let mut report = PinBatchReport::default();
+
+        while let Some((cid, res)) = results.next().await {
+            report.record(cid, res);
+        }
+
+        report
+    }
+
+    /// Add the files in as few requests as possible, each up to `max_batch_size` bytes unless a single file is larger.
+    ///
+    /// Returns their CIDs in order. Batches are sent one after the other, stopping at the first failure.
+    pub async fn add_many<I>(&self, files: I, max_batch_size: usize) -> Result<Vec<Cid>>
+    where
+        I: IntoIterator<Item = Bytes>,
+    {
+        let mut cids = Vec::new();
+        let mut batch = Vec::new();
+        let mut batch_size = 0;
+
+        for bytes in files {
+            if !batch.is_empty() && batch_size + bytes.len() > max_batch_size {
+                cids.extend(self.add_batch(std::mem::take(&mut batch)).await?);
+                batch_size = 0;
+            }
+
+            batch_size += bytes.len();
+            batch.push(bytes);
+        }
+
+        if !batch.is_empty() {
+            cids.extend(self.add_batch(batch).await?);
+        }
+
+        Ok(cids)
+    }
+
+    /// Add the files in one request, each named by its index to find its CID in the response.
+    async fn add_batch(&self, files: Vec<Bytes>) -> Result<Vec<Cid>> {
+        let count = files.len();
+
+        let mut form = Form::new();
+
+        for (i, bytes) in files.into_iter().enumerate() {
+            let part = Part::stream(bytes)
+                .file_name(i.to_string())
+                .mime_str("application/octet-stream")?;
+
+            form = form.part("file", part);
+        }
+
+        let output = self.add_form(form, false).await?;
+
+        (0..count)
+            .map(|i| {
+                let name = i.to_string();
+
+                match output.entries.iter().find(|entry| entry.name == name) {
+                    Some(entry) => Ok(entry.cid),
+                    None => Err(format!("Add response has no entry {}", name).into()),
+                }
+            })
+            .collect()
+    }
+}
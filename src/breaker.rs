@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use crate::clock;
+
+/// When a pool node stops receiving requests and for how long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures before the node is taken out of rotation.
+    pub failure_threshold: u32,
+
+    /// Time before a single probe request is let through again.
+    pub cool_down: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cool_down: Duration::from_secs(30),
+        }
+    }
+}
+
+/// State of a node circuit breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests flow normally.
+    Closed { failures: u32 },
+
+    /// Requests are rejected until the cool down ends.
+    Open { until: Duration },
+
+    /// A probe request is in flight, its outcome closes or reopens the circuit.
+    HalfOpen { since: Duration },
+}
+
+impl Default for CircuitState {
+    fn default() -> Self {
+        Self::Closed { failures: 0 }
+    }
+}
+
+impl CircuitState {
+    /// Returns true if a request may be sent to the node.
+    pub(crate) fn allow(&mut self, config: &CircuitBreakerConfig) -> bool {
+        let now = clock::now();
+
+        match *self {
+            Self::Closed { .. } => true,
+            Self::Open { until } if now >= until => {
+                *self = Self::HalfOpen { since: now };
+                true
+            }
+            Self::Open { .. } => false,
+            // The probe may have been dropped before completion
+            Self::HalfOpen { since }
+                if since
+                    .checked_add(config.cool_down)
+                    .is_some_and(|end| now >= end) =>
+            {
+                *self = Self::HalfOpen { since: now };
+                true
+            }
+            Self::HalfOpen { .. } => false,
+        }
+    }
+
+    pub(crate) fn on_success(&mut self) {
+        *self = Self::Closed { failures: 0 };
+    }
+
+    pub(crate) fn on_failure(&mut self, config: &CircuitBreakerConfig) {
+        let open = Self::Open {
+            until: clock::now()
+                .checked_add(config.cool_down)
+                .unwrap_or(Duration::MAX),
+        };
+
+        *self = match *self {
+            Self::Closed { failures } if failures + 1 < config.failure_threshold => Self::Closed {
+                failures: failures + 1,
+            },
+            _ => open,
+        };
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::{BTreeMap, HashMap};
+
+use bytes::Bytes;
+
+use crate::path::IpfsPath;
+
+/// Size limits of the response cache, the least recently used entries are evicted first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    pub max_entries: usize,
+    pub max_bytes: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 1024,
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Identify a response, only cached for immutable paths.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    pub path: IpfsPath,
+    /// Endpoint or output codec of the response.
+    pub format: &'static str,
+}
+
+/// Least recently used cache of response bodies.
+pub(crate) struct Cache {
+    config: CacheConfig,
+    entries: HashMap<CacheKey, (Bytes, u64)>,
+    /// Keys by last use.
+    order: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    bytes: usize,
+}
+
+impl Cache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            entries: HashMap::default(),
+            order: BTreeMap::default(),
+            tick: 0,
+            bytes: 0,
+        }
+    }
+
+    pub fn get(&mut self, key: &CacheKey) -> Option<Bytes> {
+        self.tick += 1;
+
+        let (bytes, last_use) = self.entries.get_mut(key)?;
+
+        if let Some(key) = self.order.remove(last_use) {
+            self.order.insert(self.tick, key);
+        }
+
+        *last_use = self.tick;
+
+        Some(bytes.clone())
+    }
+
+    pub fn insert(&mut self, key: CacheKey, bytes: Bytes) {
+        if bytes.len() > self.config.max_bytes || self.config.max_entries == 0 {
+            return;
+        }
+
+        self.tick += 1;
+
+        self.bytes += bytes.len();
+
+        if let Some((old, last_use)) = self.entries.insert(key.clone(), (bytes, self.tick)) {
+            self.bytes -= old.len();
+            self.order.remove(&last_use);
+        }
+
+        self.order.insert(self.tick, key);
+
+        while self.entries.len() > self.config.max_entries || self.bytes > self.config.max_bytes {
+            let oldest = match self.order.pop_first() {
+                Some((_, key)) => key,
+                None => break,
+            };
+
+            if let Some((bytes, _)) = self.entries.remove(&oldest) {
+                self.bytes -= bytes.len();
+            }
+        }
+    }
+}
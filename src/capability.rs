@@ -0,0 +1,166 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use serde::Deserialize;
+
+use crate::{clock, responses::IPFSError, IpfsService, Result};
+
+/// Time before probing again a node whose version could not be probed.
+const PROBE_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Request formats of a range of node versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompatMode {
+    /// go-ipfs before 0.11 and implementations without multibase pubsub.
+    ///
+    /// Topics and published data are sent as text, received messages are plain base64.
+    Legacy,
+
+    /// go-ipfs 0.11 and later, pubsub topics are multibase encoded.
+    #[default]
+    Current,
+}
+
+impl CompatMode {
+    /// Mode of a node version, e.g. `0.18.1`. `Current` if the version cannot be parsed.
+    pub fn from_version(version: &str) -> Self {
+        let mut numbers = version
+            .split(['.', '-'])
+            .map(|number| number.parse::<u64>().ok());
+
+        match (numbers.next().flatten(), numbers.next().flatten()) {
+            (Some(0), Some(minor)) if minor < 11 => Self::Legacy,
+            _ => Self::Current,
+        }
+    }
+}
+
+/// Version and API commands of a node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    pub version: String,
+
+    /// Endpoints relative to the API root, e.g. `pubsub/sub`.
+    pub commands: BTreeSet<String>,
+}
+
+impl Capabilities {
+    pub fn supports(&self, command: &str) -> bool {
+        self.commands.contains(command)
+    }
+
+    pub fn compat_mode(&self) -> CompatMode {
+        CompatMode::from_version(&self.version)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VersionResponse {
+    #[serde(rename = "Version")]
+    pub version: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommandsResponse {
+    #[serde(rename = "Name")]
+    pub name: String,
+
+    #[serde(rename = "Subcommands", default)]
+    pub subcommands: Vec<CommandsResponse>,
+}
+
+impl CommandsResponse {
+    /// Add the path of every subcommand, the root being the `ipfs` command itself.
+    fn flatten(&self, prefix: &str, commands: &mut BTreeSet<String>) {
+        for command in self.subcommands.iter() {
+            let path = match prefix {
+                "" => command.name.clone(),
+                _ => format!("{}/{}", prefix, command.name),
+            };
+
+            command.flatten(&path, commands);
+
+            commands.insert(path);
+        }
+    }
+}
+
+impl IpfsService {
+    /// Return a service using the request formats of this mode instead of detecting them.
+    ///
+    /// `None` detects the mode from the node version on first use.
+    pub fn with_compat_mode(&self, mode: Option<CompatMode>) -> Self {
+        Self {
+            compat: mode,
+            ..self.clone()
+        }
+    }
+
+    /// Version of the node.
+    pub async fn version(&self) -> Result<String> {
+        let url = self.base_url.join("version")?;
+
+        let request = self.client.post(url);
+
+        let bytes = self.send_idempotent(request).await?;
+
+        if let Ok(res) = serde_json::from_slice::<VersionResponse>(&bytes) {
+            self.detected_compat
+                .set(Some(CompatMode::from_version(&res.version)));
+
+            return Ok(res.version);
+        }
+
+        let error = serde_json::from_slice::<IPFSError>(&bytes)?;
+
+        Err(error.into())
+    }
+
+    /// Probe the version and commands of the node.
+    pub async fn capabilities(&self) -> Result<Capabilities> {
+        let version = self.version().await?;
+
+        let url = self.base_url.join("commands")?;
+
+        let request = self.client.post(url);
+
+        let bytes = self.send_idempotent(request).await?;
+
+        if let Ok(res) = serde_json::from_slice::<CommandsResponse>(&bytes) {
+            let mut commands = BTreeSet::new();
+
+            res.flatten("", &mut commands);
+
+            return Ok(Capabilities { version, commands });
+        }
+
+        let error = serde_json::from_slice::<IPFSError>(&bytes)?;
+
+        Err(error.into())
+    }
+
+    /// The forced mode, else the one of the node version, probed once and shared with clones.
+    ///
+    /// `Current` if the node cannot be probed, the request that follows will likely fail anyway.
+    /// After a failure, the node is not probed again for `PROBE_BACKOFF`.
+    #[cfg_attr(not(any(feature = "dht", feature = "pubsub")), allow(dead_code))]
+    pub(crate) async fn compat_mode(&self) -> CompatMode {
+        if let Some(mode) = self.compat.or_else(|| self.detected_compat.get()) {
+            return mode;
+        }
+
+        if let Some(failure) = self.compat_probe_failure.get() {
+            if clock::now() < failure + PROBE_BACKOFF {
+                return CompatMode::Current;
+            }
+        }
+
+        match self.version().await {
+            Ok(version) => CompatMode::from_version(&version),
+            Err(_) => {
+                self.compat_probe_failure.set(Some(clock::now()));
+
+                CompatMode::Current
+            }
+        }
+    }
+}
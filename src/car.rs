@@ -0,0 +1,482 @@
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+
+use cid::Cid;
+
+use crate::Result;
+
+/// First bytes of a CARv2 file, a CARv1 header announcing version 2.
+const CARV2_PRAGMA: [u8; 11] = [
+    0x0A, 0xA1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6F, 0x6E, 0x02,
+];
+
+/// Length of the CARv2 header following the pragma.
+const CARV2_HEADER_LEN: usize = 40;
+
+/// Multicodec of the index format written and read, CIDs sorted by digest.
+const INDEX_SORTED: usize = 0x0400;
+
+/// CARv1 or CARv2 file.
+///
+/// The index of a CARv2 file, if any, is used to find blocks without reading the others.
+#[derive(Debug, Clone)]
+pub struct CarFile {
+    roots: Vec<Cid>,
+
+    /// Sections of the CARv1 payload, after its header.
+    sections: Bytes,
+
+    /// Offset of the sections in the CARv1 payload.
+    sections_offset: usize,
+
+    /// Offsets of the sections in the CARv1 payload by digest.
+    index: Option<BTreeMap<Vec<u8>, Vec<usize>>>,
+
+    /// Whole CARv1 payload.
+    payload: Bytes,
+}
+
+impl CarFile {
+    pub fn parse(bytes: Bytes) -> Result<Self> {
+        if bytes.starts_with(&CARV2_PRAGMA) {
+            return Self::parse_v2(bytes);
+        }
+
+        Self::parse_v1(bytes, None)
+    }
+
+    fn parse_v1(payload: Bytes, index: Option<BTreeMap<Vec<u8>, Vec<usize>>>) -> Result<Self> {
+        let mut sections = payload.clone();
+
+        let header_len = read_varint(&mut sections)?;
+
+        if header_len > sections.len() {
+            return Err("Truncated CAR header".into());
+        }
+
+        let header = sections.split_to(header_len);
+
+        let (roots, version) = read_header(&header)?;
+
+        if version != 1 {
+            return Err(format!("Unsupported CAR version {}", version).into());
+        }
+
+        Ok(Self {
+            roots,
+            sections_offset: payload.len() - sections.len(),
+            sections,
+            index,
+            payload,
+        })
+    }
+
+    fn parse_v2(bytes: Bytes) -> Result<Self> {
+        let header = match bytes.get(CARV2_PRAGMA.len()..CARV2_PRAGMA.len() + CARV2_HEADER_LEN) {
+            Some(header) => header,
+            None => return Err("Truncated CARv2 header".into()),
+        };
+
+        let u64_at = |at: usize| {
+            let mut le = [0; 8];
+            le.copy_from_slice(&header[at..at + 8]);
+
+            u64::from_le_bytes(le) as usize
+        };
+
+        // The first 16 bytes are characteristics
+        let data_offset = u64_at(16);
+        let data_size = u64_at(24);
+        let index_offset = u64_at(32);
+
+        let payload = match bytes.get(data_offset..data_offset.saturating_add(data_size)) {
+            Some(_) => bytes.slice(data_offset..data_offset + data_size),
+            None => return Err("Truncated CARv2 data".into()),
+        };
+
+        let index = match index_offset {
+            0 => None,
+            offset if offset < bytes.len() => Some(read_index(bytes.slice(offset..))?),
+            _ => return Err("Truncated CARv2 index".into()),
+        };
+
+        Self::parse_v1(payload, index)
+    }
+
+    pub fn roots(&self) -> &[Cid] {
+        &self.roots
+    }
+
+    /// Blocks in file order.
+    pub fn blocks(&self) -> CarBlocks {
+        CarBlocks {
+            sections: self.sections.clone(),
+        }
+    }
+
+    /// Data of the block with this CID, looked up in the index if any.
+    pub fn get(&self, cid: &Cid) -> Result<Option<Bytes>> {
+        let offsets = match &self.index {
+            Some(index) => index.get(cid.hash().digest()),
+            None => {
+                for block in self.blocks() {
+                    let (block_cid, data) = block?;
+
+                    if block_cid == *cid {
+                        return Ok(Some(data));
+                    }
+                }
+
+                return Ok(None);
+            }
+        };
+
+        // Different CIDs may share a digest
+        for offset in offsets.into_iter().flatten() {
+            if *offset < self.sections_offset || *offset > self.payload.len() {
+                return Err("CARv2 index offset out of bounds".into());
+            }
+
+            let mut blocks = CarBlocks {
+                sections: self.payload.slice(*offset..),
+            };
+
+            if let Some(block) = blocks.next() {
+                let (block_cid, data) = block?;
+
+                if block_cid == *cid {
+                    return Ok(Some(data));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Iterator over the blocks of a CAR file.
+pub struct CarBlocks {
+    sections: Bytes,
+}
+
+impl Iterator for CarBlocks {
+    type Item = Result<(Cid, Bytes)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.sections.is_empty() {
+            return None;
+        }
+
+        let res = read_section(&mut self.sections);
+
+        if res.is_err() {
+            self.sections = Bytes::new();
+        }
+
+        Some(res)
+    }
+}
+
+fn read_section(bytes: &mut Bytes) -> Result<(Cid, Bytes)> {
+    let section_len = read_varint(bytes)?;
+
+    if section_len > bytes.len() {
+        return Err("Truncated CAR section".into());
+    }
+
+    let section = bytes.split_to(section_len);
+
+    let mut reader = &section[..];
+    let cid = Cid::read_bytes(&mut reader)?;
+
+    let data = section.slice(section_len - reader.len()..);
+
+    Ok((cid, data))
+}
+
+/// Blocks of a CAR file in file order, the header is skipped.
+pub(crate) fn read_car(bytes: Bytes) -> Result<Vec<(Cid, Bytes)>> {
+    CarFile::parse(bytes)?.blocks().collect()
+}
+
+/// CARv1 file of the blocks in this order.
+pub fn write_car(roots: &[Cid], blocks: &[(Cid, Bytes)]) -> Bytes {
+    write_payload(roots, blocks).0.into()
+}
+
+/// CARv2 file of the blocks in this order, indexed by digest.
+pub fn write_car_v2(roots: &[Cid], blocks: &[(Cid, Bytes)]) -> Bytes {
+    let (payload, offsets) = write_payload(roots, blocks);
+
+    let data_offset = CARV2_PRAGMA.len() + CARV2_HEADER_LEN;
+    let index_offset = data_offset + payload.len();
+
+    let mut car = Vec::with_capacity(index_offset);
+
+    car.extend_from_slice(&CARV2_PRAGMA);
+    car.extend_from_slice(&[0; 16]);
+    car.extend_from_slice(&(data_offset as u64).to_le_bytes());
+    car.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    car.extend_from_slice(&(index_offset as u64).to_le_bytes());
+    car.extend_from_slice(&payload);
+
+    write_index(&mut car, blocks, &offsets);
+
+    car.into()
+}
+
+/// CARv1 payload and the offset of each section in it.
+fn write_payload(roots: &[Cid], blocks: &[(Cid, Bytes)]) -> (Vec<u8>, Vec<usize>) {
+    let header = car_header(roots);
+
+    let mut car = Vec::new();
+
+    write_varint(&mut car, header.len());
+    car.extend_from_slice(&header);
+
+    let mut offsets = Vec::with_capacity(blocks.len());
+
+    for (cid, data) in blocks {
+        offsets.push(car.len());
+
+        let cid = cid.to_bytes();
+
+        write_varint(&mut car, cid.len() + data.len());
+        car.extend_from_slice(&cid);
+        car.extend_from_slice(data);
+    }
+
+    (car, offsets)
+}
+
+/// Sorted index, one bucket per digest length, each entry the digest and the section offset.
+fn write_index(out: &mut Vec<u8>, blocks: &[(Cid, Bytes)], offsets: &[usize]) {
+    let mut buckets: BTreeMap<usize, Vec<(&[u8], usize)>> = BTreeMap::new();
+
+    for ((cid, _), offset) in blocks.iter().zip(offsets) {
+        let digest = cid.hash().digest();
+
+        buckets
+            .entry(digest.len())
+            .or_default()
+            .push((digest, *offset));
+    }
+
+    write_varint(out, INDEX_SORTED);
+    out.extend_from_slice(&(buckets.len() as u32).to_le_bytes());
+
+    for (digest_len, mut entries) in buckets {
+        entries.sort();
+
+        let width = digest_len + 8;
+
+        out.extend_from_slice(&(width as u32).to_le_bytes());
+        out.extend_from_slice(&((entries.len() * width) as u64).to_le_bytes());
+
+        for (digest, offset) in entries {
+            out.extend_from_slice(digest);
+            out.extend_from_slice(&(offset as u64).to_le_bytes());
+        }
+    }
+}
+
+fn read_index(mut bytes: Bytes) -> Result<BTreeMap<Vec<u8>, Vec<usize>>> {
+    let codec = read_varint(&mut bytes)?;
+
+    if codec != INDEX_SORTED {
+        return Err(format!("Unsupported CARv2 index {:#x}", codec).into());
+    }
+
+    let bucket_count = read_le(&mut bytes, 4)?;
+
+    let mut index: BTreeMap<Vec<u8>, Vec<usize>> = BTreeMap::new();
+
+    for _ in 0..bucket_count {
+        let width = read_le(&mut bytes, 4)?;
+        let len = read_le(&mut bytes, 8)?;
+
+        if width <= 8 || len % width != 0 {
+            return Err("Invalid CARv2 index bucket".into());
+        }
+
+        for _ in 0..len / width {
+            let digest = take(&mut bytes, width - 8)?;
+            let offset = read_le(&mut bytes, 8)?;
+
+            index.entry(digest.to_vec()).or_default().push(offset);
+        }
+    }
+
+    Ok(index)
+}
+
+fn take(bytes: &mut Bytes, len: usize) -> Result<Bytes> {
+    if len > bytes.len() {
+        return Err("Truncated CAR".into());
+    }
+
+    Ok(bytes.split_to(len))
+}
+
+/// Little endian unsigned integer of `len` bytes, at most 8.
+fn read_le(bytes: &mut Bytes, len: usize) -> Result<usize> {
+    let mut buf = [0; 8];
+    buf[..len].copy_from_slice(&take(bytes, len)?);
+
+    Ok(u64::from_le_bytes(buf) as usize)
+}
+
+/// DAG-CBOR encoded `{"roots": [..], "version": 1}`.
+fn car_header(roots: &[Cid]) -> Vec<u8> {
+    let mut header = vec![0xA2];
+
+    header.push(0x65);
+    header.extend_from_slice(b"roots");
+
+    cbor_head(&mut header, 4, roots.len());
+
+    for root in roots {
+        // Tag 42, links are byte strings prefixed with the identity multibase
+        header.extend_from_slice(&[0xD8, 0x2A]);
+
+        let cid = root.to_bytes();
+
+        cbor_head(&mut header, 2, cid.len() + 1);
+        header.push(0x00);
+        header.extend_from_slice(&cid);
+    }
+
+    header.push(0x67);
+    header.extend_from_slice(b"version");
+    header.push(0x01);
+
+    header
+}
+
+/// Roots and version of a CAR header.
+fn read_header(header: &[u8]) -> Result<(Vec<Cid>, u64)> {
+    let mut reader = header;
+
+    let mut roots = Vec::new();
+    let mut version = None;
+
+    let entries = match read_cbor_head(&mut reader)? {
+        (5, entries) => entries,
+        _ => return Err("CAR header is not a map".into()),
+    };
+
+    for _ in 0..entries {
+        let key = match read_cbor_head(&mut reader)? {
+            (3, len) => take_slice(&mut reader, len as usize)?,
+            _ => return Err("CAR header key is not a string".into()),
+        };
+
+        match key {
+            b"roots" => {
+                let count = match read_cbor_head(&mut reader)? {
+                    (4, count) => count,
+                    _ => return Err("CAR roots are not an array".into()),
+                };
+
+                for _ in 0..count {
+                    let link = match (read_cbor_head(&mut reader)?, read_cbor_head(&mut reader)?) {
+                        ((6, 42), (2, len)) => take_slice(&mut reader, len as usize)?,
+                        _ => return Err("CAR root is not a link".into()),
+                    };
+
+                    match link.split_first() {
+                        Some((0x00, cid)) => roots.push(Cid::try_from(cid)?),
+                        _ => return Err("CAR root is not a link".into()),
+                    }
+                }
+            }
+            b"version" => match read_cbor_head(&mut reader)? {
+                (0, value) => version = Some(value),
+                _ => return Err("CAR version is not an integer".into()),
+            },
+            _ => return Err("Unsupported CAR header".into()),
+        }
+    }
+
+    match version {
+        Some(version) => Ok((roots, version)),
+        None => Err("CAR header has no version".into()),
+    }
+}
+
+fn take_slice<'a>(reader: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if len > reader.len() {
+        return Err("Truncated CAR header".into());
+    }
+
+    let (head, tail) = reader.split_at(len);
+    *reader = tail;
+
+    Ok(head)
+}
+
+/// CBOR major type and length, in the shortest form.
+fn cbor_head(out: &mut Vec<u8>, major: u8, len: usize) {
+    let major = major << 5;
+
+    match len {
+        0..=23 => out.push(major | len as u8),
+        24..=0xFF => out.extend_from_slice(&[major | 24, len as u8]),
+        0x100..=0xFFFF => {
+            out.push(major | 25);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 26);
+            out.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+}
+
+/// CBOR major type and argument.
+fn read_cbor_head(reader: &mut &[u8]) -> Result<(u8, u64)> {
+    let initial = take_slice(reader, 1)?[0];
+
+    let major = initial >> 5;
+
+    let value = match initial & 0x1F {
+        info @ 0..=23 => info as u64,
+        info @ 24..=27 => {
+            let len = 1 << (info - 24);
+
+            take_slice(reader, len)?
+                .iter()
+                .fold(0, |value, byte| value << 8 | *byte as u64)
+        }
+        _ => return Err("Unsupported CBOR in CAR header".into()),
+    };
+
+    Ok((major, value))
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+
+    out.push(value as u8);
+}
+
+/// Unsigned LEB128 as used by multiformats, at most 9 bytes.
+fn read_varint(bytes: &mut Bytes) -> Result<usize> {
+    let mut value = 0u64;
+
+    for (i, byte) in bytes.iter().enumerate().take(9) {
+        value |= ((byte & 0x7F) as u64) << (i * 7);
+
+        if byte & 0x80 == 0 {
+            let _ = bytes.split_to(i + 1);
+
+            return Ok(usize::try_from(value)?);
+        }
+    }
+
+    Err("Invalid CAR varint".into())
+}
@@ -0,0 +1,110 @@
+use std::{fmt, str::FromStr};
+
+use crate::Result;
+
+/// Largest chunk the node accepts, in bytes.
+const MAX_CHUNK_SIZE: u32 = 1024 * 1024;
+
+/// Smallest minimum of rabin chunks the node accepts, in bytes.
+const MIN_RABIN_SIZE: u32 = 16;
+
+/// Default average of rabin chunks, in bytes.
+const DEFAULT_RABIN_AVG: u32 = 256 * 1024;
+
+/// How added data is split in blocks.
+///
+/// Rabin and buzhash cut where the data matches a pattern,
+/// an edit then only changes the chunks around it and the others are deduplicated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chunker {
+    /// Chunks of this many bytes, 256 KiB by default.
+    Size(u32),
+
+    /// Chunks of these sizes in bytes, see `Chunker::rabin`.
+    Rabin {
+        min: u32,
+        avg: u32,
+        max: u32,
+    },
+
+    Buzhash,
+}
+
+impl Default for Chunker {
+    fn default() -> Self {
+        Self::Size(256 * 1024)
+    }
+}
+
+impl Chunker {
+    /// Rabin chunks of `avg` bytes on average, between a third and 1.5 times as large.
+    pub fn rabin(avg: u32) -> Self {
+        Self::Rabin {
+            min: avg / 3,
+            avg,
+            max: avg + avg / 2,
+        }
+    }
+
+    /// Check the sizes are accepted by the node.
+    pub fn validate(&self) -> Result<()> {
+        match *self {
+            Self::Size(0) => Err("Chunk size must be positive".into()),
+            Self::Size(size) if size > MAX_CHUNK_SIZE => {
+                Err(format!("Chunk size {} over {} bytes", size, MAX_CHUNK_SIZE).into())
+            }
+            Self::Rabin { min, .. } if min < MIN_RABIN_SIZE => {
+                Err(format!("Rabin min {} under {} bytes", min, MIN_RABIN_SIZE).into())
+            }
+            Self::Rabin { min, avg, max } if min >= avg || avg >= max => {
+                Err(format!("Rabin sizes {}-{}-{} not increasing", min, avg, max).into())
+            }
+            Self::Rabin { max, .. } if max > MAX_CHUNK_SIZE => {
+                Err(format!("Rabin max {} over {} bytes", max, MAX_CHUNK_SIZE).into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Value of the `chunker` option, e.g. `size-262144` or `rabin-87381-262144-393216`.
+impl fmt::Display for Chunker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Size(size) => write!(f, "size-{}", size),
+            Self::Rabin { min, avg, max } => write!(f, "rabin-{}-{}-{}", min, avg, max),
+            Self::Buzhash => write!(f, "buzhash"),
+        }
+    }
+}
+
+/// Parse a `chunker` option, also accepting `rabin` and `rabin-{avg}`.
+impl FromStr for Chunker {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split('-').collect();
+
+        let parse = |part: &str| {
+            part.parse::<u32>()
+                .map_err(|_| format!("Invalid chunker {}", s))
+        };
+
+        let chunker = match parts[..] {
+            ["size", size] => Self::Size(parse(size)?),
+            ["rabin"] => Self::rabin(DEFAULT_RABIN_AVG),
+            ["rabin", avg] => Self::rabin(parse(avg)?),
+            ["rabin", min, avg, max] => Self::Rabin {
+                min: parse(min)?,
+                avg: parse(avg)?,
+                max: parse(max)?,
+            },
+            ["buzhash"] => Self::Buzhash,
+            _ => return Err(format!("Invalid chunker {}", s).into()),
+        };
+
+        chunker.validate()?;
+
+        Ok(chunker)
+    }
+}
@@ -0,0 +1,29 @@
+use cid::{Cid, Version};
+
+/// Multicodec of UnixFS nodes, the only codec of CIDv0.
+const DAG_PB_CODEC: u64 = 0x70;
+
+/// Multihash code of the only hash function of CIDv0.
+const SHA2_256: u64 = 0x12;
+
+/// CIDv1 of this CID, CIDv0 being dag-pb.
+pub fn to_cid_v1(cid: &Cid) -> Cid {
+    match cid.version() {
+        Version::V0 => Cid::new_v1(DAG_PB_CODEC, *cid.hash()),
+        Version::V1 => *cid,
+    }
+}
+
+/// CIDv0 of this CID, if dag-pb hashed with sha2-256.
+pub fn to_cid_v0(cid: &Cid) -> Option<Cid> {
+    if cid.codec() != DAG_PB_CODEC || cid.hash().code() != SHA2_256 {
+        return None;
+    }
+
+    Cid::new_v0(*cid.hash()).ok()
+}
+
+/// Whether both CIDs are of the same data, whatever their version.
+pub fn same_cid(a: &Cid, b: &Cid) -> bool {
+    to_cid_v1(a) == to_cid_v1(b)
+}
@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+/// Monotonic time, for measuring elapsed time and deadlines.
+///
+/// Browsers lack `std::time::Instant`, where this is the time since the unix epoch.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn now() -> Duration {
+    since_epoch()
+}
+
+/// Monotonic time, for measuring elapsed time and deadlines.
+///
+/// Measured from the first call, unaffected by changes to the system clock.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now() -> Duration {
+    use std::{sync::OnceLock, time::Instant};
+
+    static START: OnceLock<Instant> = OnceLock::new();
+
+    START.get_or_init(Instant::now).elapsed()
+}
+
+/// Wall clock time elapsed since the unix epoch, for dates shared with other peers.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn since_epoch() -> Duration {
+    Duration::from_millis(js_sys::Date::now() as u64)
+}
+
+/// Wall clock time elapsed since the unix epoch, for dates shared with other peers.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn since_epoch() -> Duration {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
@@ -0,0 +1,325 @@
+use std::{collections::HashMap, fmt, rc::Rc};
+
+use bytes::Bytes;
+
+use cid::Cid;
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    multipart::{Form, Part},
+    Client, RequestBuilder, Url,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+
+use crate::{runtime::compat, Result};
+
+/// Default address of the ipfs-cluster REST API.
+pub const DEFAULT_CLUSTER_URI: &str = "http://127.0.0.1:9094/";
+
+/// Client of the ipfs-cluster REST API, pinning content on several Kubo nodes at once.
+#[derive(Clone)]
+pub struct ClusterClient {
+    client: Client,
+    base_url: Rc<Url>,
+    replication: Option<(i32, i32)>,
+    headers: Rc<HeaderMap>,
+}
+
+impl Default for ClusterClient {
+    fn default() -> Self {
+        let base_url = Url::parse(DEFAULT_CLUSTER_URI).expect("Parsing URI");
+
+        Self::new(base_url)
+    }
+}
+
+/// Pin of the shared cluster state.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClusterPin {
+    #[serde(deserialize_with = "deserialize_cid")]
+    pub cid: Cid,
+
+    #[serde(default)]
+    pub name: String,
+
+    /// Cluster peers asked to pin, empty if every peer is.
+    #[serde(default)]
+    pub allocations: Vec<String>,
+
+    /// -1 means every peer.
+    #[serde(default)]
+    pub replication_factor_min: i32,
+
+    #[serde(default)]
+    pub replication_factor_max: i32,
+}
+
+/// Status of a pin on each cluster peer.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClusterPinStatus {
+    #[serde(deserialize_with = "deserialize_cid")]
+    pub cid: Cid,
+
+    #[serde(default)]
+    pub name: String,
+
+    #[serde(default)]
+    pub peer_map: HashMap<String, PeerPinStatus>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PeerPinStatus {
+    #[serde(default)]
+    pub peername: String,
+
+    /// e.g. `pinned`, `pinning`, `pin_error` or `remote`.
+    pub status: String,
+
+    #[serde(default)]
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClusterPeer {
+    pub id: String,
+
+    #[serde(default)]
+    pub peername: String,
+
+    #[serde(default)]
+    pub version: String,
+
+    #[serde(default)]
+    pub addresses: Vec<String>,
+
+    #[serde(default)]
+    pub cluster_peers: Vec<String>,
+
+    /// Set if the peer could not be contacted.
+    #[serde(default)]
+    pub error: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClusterAddResponse {
+    #[serde(deserialize_with = "deserialize_cid")]
+    cid: Cid,
+}
+
+/// Error returned by the cluster API.
+#[derive(Debug, Deserialize)]
+pub struct ClusterError {
+    pub code: u16,
+    pub message: String,
+}
+
+impl fmt::Display for ClusterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cluster error {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ClusterError {}
+
+impl ClusterClient {
+    pub fn new(url: Url) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: Rc::new(url),
+            replication: None,
+            headers: Rc::default(),
+        }
+    }
+
+    /// Return a client asking for at least `min` and at most `max` copies of what it adds or pins.
+    ///
+    /// -1 pins on every peer, the cluster defaults apply otherwise.
+    pub fn with_replication(&self, min: i32, max: i32) -> Self {
+        Self {
+            replication: Some((min, max)),
+            ..self.clone()
+        }
+    }
+
+    /// Return a client sending this header with every request, e.g. `Authorization`.
+    pub fn with_header(&self, name: &str, value: &str) -> Result<Self> {
+        let name = HeaderName::from_bytes(name.as_bytes())?;
+        let value = HeaderValue::from_str(value)?;
+
+        let mut headers = (*self.headers).clone();
+        headers.insert(name, value);
+
+        Ok(Self {
+            headers: Rc::new(headers),
+            ..self.clone()
+        })
+    }
+
+    /// Add data to the cluster then pin it on the allocated peers. Return a CID.
+    pub async fn add(&self, bytes: Bytes) -> Result<Cid> {
+        let url = self.base_url.join("add")?;
+
+        let form = Form::new().part("file", Part::stream(bytes));
+
+        let request = self
+            .client
+            .post(url)
+            .query(&[("cid-version", "1")])
+            .multipart(form);
+
+        let request = self.with_replication_query(request);
+
+        let outputs: Vec<ClusterAddResponse> = self.send_list(request).await?;
+
+        match outputs.last() {
+            Some(output) => Ok(output.cid),
+            None => Err("Empty cluster add response".into()),
+        }
+    }
+
+    /// Pin a CID on the allocated peers.
+    pub async fn pin(&self, cid: Cid) -> Result<ClusterPin> {
+        self.pin_named(cid, "").await
+    }
+
+    /// Pin a CID under a name on the allocated peers.
+    pub async fn pin_named(&self, cid: Cid, name: &str) -> Result<ClusterPin> {
+        let url = self.base_url.join(&format!("pins/{}", cid))?;
+
+        let mut request = self.with_replication_query(self.client.post(url));
+
+        if !name.is_empty() {
+            request = request.query(&[("name", name)]);
+        }
+
+        self.send(request).await
+    }
+
+    /// Remove the pin from the cluster state, every peer then unpins.
+    pub async fn unpin(&self, cid: Cid) -> Result<ClusterPin> {
+        let url = self.base_url.join(&format!("pins/{}", cid))?;
+
+        self.send(self.client.delete(url)).await
+    }
+
+    /// Status of every pin on every peer.
+    pub async fn pins(&self) -> Result<Vec<ClusterPinStatus>> {
+        let url = self.base_url.join("pins")?;
+
+        self.send_list(self.client.get(url)).await
+    }
+
+    /// Status of the pin on every peer.
+    pub async fn pin_status(&self, cid: Cid) -> Result<ClusterPinStatus> {
+        let url = self.base_url.join(&format!("pins/{}", cid))?;
+
+        self.send(self.client.get(url)).await
+    }
+
+    /// Pins of the shared state, with the peers they are allocated to.
+    pub async fn allocations(&self) -> Result<Vec<ClusterPin>> {
+        let url = self.base_url.join("allocations")?;
+
+        self.send_list(self.client.get(url)).await
+    }
+
+    pub async fn allocation(&self, cid: Cid) -> Result<ClusterPin> {
+        let url = self.base_url.join(&format!("allocations/{}", cid))?;
+
+        self.send(self.client.get(url)).await
+    }
+
+    /// Members of the cluster.
+    pub async fn peers(&self) -> Result<Vec<ClusterPeer>> {
+        let url = self.base_url.join("peers")?;
+
+        self.send_list(self.client.get(url)).await
+    }
+
+    fn with_replication_query(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.replication {
+            Some((min, max)) => {
+                request.query(&[("replication-min", min), ("replication-max", max)])
+            }
+            None => request,
+        }
+    }
+
+    async fn execute(&self, mut request: RequestBuilder) -> Result<Bytes> {
+        if !self.headers.is_empty() {
+            request = request.headers((*self.headers).clone());
+        }
+
+        let response = compat(request.send()).await?;
+
+        let status = response.status();
+
+        let bytes = compat(response.bytes()).await?;
+
+        if !status.is_success() {
+            return match serde_json::from_slice::<ClusterError>(&bytes) {
+                Ok(error) => Err(error.into()),
+                Err(_) => Err(format!("Cluster responded with status {}", status).into()),
+            };
+        }
+
+        Ok(bytes)
+    }
+
+    async fn send<T>(&self, request: RequestBuilder) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let bytes = self.execute(request).await?;
+
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// Lists are a JSON array on old cluster versions, newline delimited objects since 1.0.
+    async fn send_list<T>(&self, request: RequestBuilder) -> Result<Vec<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let bytes = self.execute(request).await?;
+
+        if let Ok(list) = serde_json::from_slice::<Vec<T>>(&bytes) {
+            return Ok(list);
+        }
+
+        let mut list = Vec::new();
+
+        for line in bytes.split(|byte| *byte == b'\n') {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+
+            list.push(serde_json::from_slice(line)?);
+        }
+
+        Ok(list)
+    }
+}
+
+/// CIDs are `{"/": "..."}` objects since cluster 1.0, plain strings before.
+fn deserialize_cid<'de, D>(deserializer: D) -> std::result::Result<Cid, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum CidJson {
+        Link {
+            #[serde(rename = "/")]
+            cid: String,
+        },
+        Text(String),
+    }
+
+    let text = match CidJson::deserialize(deserializer)? {
+        CidJson::Link { cid } => cid,
+        CidJson::Text(cid) => cid,
+    };
+
+    Cid::try_from(text).map_err(serde::de::Error::custom)
+}
@@ -0,0 +1,105 @@
+use core::fmt;
+
+use std::{
+    cell::RefCell, collections::HashMap, error::Error, future::Future, rc::Rc, time::Duration,
+};
+
+use bytes::Bytes;
+
+use futures_util::{
+    future::{LocalBoxFuture, Shared},
+    FutureExt,
+};
+
+use crate::{cache::CacheKey, Result};
+
+type SharedResponse =
+    Shared<LocalBoxFuture<'static, std::result::Result<(u16, Bytes), SharedError>>>;
+
+/// Error of a request whose result was shared by several callers.
+#[derive(Clone)]
+pub(crate) struct SharedError(Rc<Box<dyn Error>>);
+
+impl Error for SharedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.0.as_ref().as_ref())
+    }
+}
+
+impl fmt::Display for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Debug for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+/// The error the request failed with, looking through sharing.
+pub(crate) fn original<'a>(error: &'a (dyn Error + 'static)) -> &'a (dyn Error + 'static) {
+    match error.downcast_ref::<SharedError>() {
+        Some(shared) => shared.0.as_ref().as_ref(),
+        None => error,
+    }
+}
+
+/// Identify a read and the options of the service changing its response.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CoalesceKey {
+    pub read: CacheKey,
+    pub offline: bool,
+    pub verify: bool,
+    pub max_response_size: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+/// Identical reads in flight, later callers wait on the first request instead of sending their own.
+#[derive(Default)]
+pub(crate) struct Coalescer {
+    pending: RefCell<HashMap<CoalesceKey, SharedResponse>>,
+}
+
+impl Coalescer {
+    /// Wait on the request in flight for this key or start one.
+    pub async fn run<F>(self: &Rc<Self>, key: CoalesceKey, request: F) -> Result<(u16, Bytes)>
+    where
+        F: Future<Output = Result<(u16, Bytes)>> + 'static,
+    {
+        let shared = {
+            let mut pending = self.pending.borrow_mut();
+
+            match pending.get(&key) {
+                Some(shared) => shared.clone(),
+                None => {
+                    let coalescer = Rc::downgrade(self);
+                    let done = key.clone();
+
+                    let shared = async move {
+                        let res = request.await.map_err(|e| SharedError(Rc::new(e)));
+
+                        if let Some(coalescer) = coalescer.upgrade() {
+                            coalescer.pending.borrow_mut().remove(&done);
+                        }
+
+                        res
+                    }
+                    .boxed_local()
+                    .shared();
+
+                    pending.insert(key, shared.clone());
+
+                    shared
+                }
+            }
+        };
+
+        // The only caller gets the error as is
+        shared.await.map_err(|e| match Rc::try_unwrap(e.0) {
+            Ok(error) => error,
+            Err(shared) => SharedError(shared).into(),
+        })
+    }
+}
@@ -0,0 +1,39 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Result;
+
+/// Serialization format used to encode typed pubsub messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    #[default]
+    Json,
+    Cbor,
+}
+
+impl Codec {
+    pub fn encode<T>(&self, value: &T) -> Result<Vec<u8>>
+    where
+        T: ?Sized + Serialize,
+    {
+        match self {
+            Self::Json => Ok(serde_json::to_vec(value)?),
+            Self::Cbor => {
+                let mut bytes = Vec::new();
+
+                ciborium::ser::into_writer(value, &mut bytes)?;
+
+                Ok(bytes)
+            }
+        }
+    }
+
+    pub fn decode<T>(&self, bytes: &[u8]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            Self::Json => Ok(serde_json::from_slice(bytes)?),
+            Self::Cbor => Ok(ciborium::de::from_reader(bytes)?),
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use std::{fmt, time::Duration};
+
+use cid::multibase::{self, Base};
+
+use reqwest::Url;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    pool::Routing,
+    redact::{self, REDACTED},
+    retry::RetryPolicy,
+    IpfsPool, IpfsService, Result, DEFAULT_URI,
+};
+
+/// Address of the node API, its path defaulting to `DEFAULT_API_PATH`.
+pub const ENV_API_URL: &str = "IPFS_API_URL";
+
+/// Username of basic authentication, with `ENV_API_PASSWORD`.
+pub const ENV_API_USERNAME: &str = "IPFS_API_USERNAME";
+
+pub const ENV_API_PASSWORD: &str = "IPFS_API_PASSWORD";
+
+/// Token of bearer authentication, used if no username is set.
+pub const ENV_API_TOKEN: &str = "IPFS_API_TOKEN";
+
+/// Whole client setup, e.g. loaded from a TOML or JSON file.
+///
+/// Durations are written like `30s`, `500ms` or `1h`.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IpfsConfig {
+    /// Addresses of the node APIs, the local node if empty.
+    pub nodes: Vec<String>,
+
+    pub auth: Option<Auth>,
+
+    /// Time to complete a request, see `IpfsService::with_timeout`.
+    #[serde(with = "optional_duration", skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<Duration>,
+
+    pub retry: Option<RetryPolicy>,
+
+    pub routing: Routing,
+}
+
+/// Passwords of the node addresses are redacted.
+impl fmt::Debug for IpfsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Unparsable addresses may hide credentials anywhere
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|node| match Url::parse(node) {
+                Ok(url) => redact::url(&url).to_string(),
+                Err(_) => REDACTED.to_owned(),
+            })
+            .collect();
+
+        f.debug_struct("IpfsConfig")
+            .field("nodes", &nodes)
+            .field("auth", &self.auth)
+            .field("timeout", &self.timeout)
+            .field("retry", &self.retry)
+            .field("routing", &self.routing)
+            .finish()
+    }
+}
+
+/// Credentials sent with every request to the nodes.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Auth {
+    Basic { username: String, password: String },
+
+    Bearer { token: String },
+}
+
+impl Auth {
+    /// Value of the `Authorization` header.
+    pub(crate) fn header_value(&self) -> String {
+        match self {
+            Self::Basic { username, password } => {
+                let credentials = format!("{}:{}", username, password);
+
+                // Skip the multibase prefix
+                let encoded = multibase::encode(Base::Base64Pad, credentials);
+
+                format!("Basic {}", &encoded[1..])
+            }
+            Self::Bearer { token } => format!("Bearer {}", token),
+        }
+    }
+}
+
+/// Passwords and tokens are redacted.
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .field("password", &REDACTED)
+                .finish(),
+            Self::Bearer { .. } => f.debug_struct("Bearer").field("token", &REDACTED).finish(),
+        }
+    }
+}
+
+impl IpfsConfig {
+    /// Node and credentials of the environment variables, the local node without credentials if unset.
+    ///
+    /// See `ENV_API_URL`, `ENV_API_USERNAME`, `ENV_API_PASSWORD` and `ENV_API_TOKEN`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_env() -> Result<Self> {
+        let mut config = Self::default();
+
+        if let Some(url) = env_var(ENV_API_URL)? {
+            let mut url = Url::parse(&url)?;
+
+            if url.path() == "/" {
+                url.set_path(crate::DEFAULT_API_PATH);
+            }
+
+            config.nodes.push(url.into());
+        }
+
+        let username = env_var(ENV_API_USERNAME)?;
+        let password = env_var(ENV_API_PASSWORD)?;
+        let token = env_var(ENV_API_TOKEN)?;
+
+        config.auth = match (username, password, token) {
+            (Some(username), password, _) => Some(Auth::Basic {
+                username,
+                password: password.unwrap_or_default(),
+            }),
+            (None, Some(_), _) => {
+                return Err(format!("{} set without {}", ENV_API_PASSWORD, ENV_API_USERNAME).into())
+            }
+            (None, None, Some(token)) => Some(Auth::Bearer { token }),
+            (None, None, None) => None,
+        };
+
+        Ok(config)
+    }
+
+    /// Service of each node, configured but for the routing.
+    fn services(&self) -> Result<Vec<IpfsService>> {
+        let urls = match self.nodes.is_empty() {
+            true => vec![Url::parse(DEFAULT_URI)?],
+            false => self
+                .nodes
+                .iter()
+                .map(|node| Url::parse(node))
+                .collect::<std::result::Result<_, _>>()?,
+        };
+
+        urls.into_iter()
+            .map(|url| {
+                let mut service = IpfsService::new(url);
+
+                if let Some(auth) = &self.auth {
+                    service = service.with_auth(auth)?;
+                }
+
+                if let Some(timeout) = self.timeout {
+                    service = service.with_timeout(timeout);
+                }
+
+                if let Some(policy) = &self.retry {
+                    service = service.with_retry(policy.clone());
+                }
+
+                Ok(service)
+            })
+            .collect()
+    }
+}
+
+/// Value of the variable, `None` if unset or empty.
+#[cfg(not(target_arch = "wasm32"))]
+fn env_var(name: &str) -> Result<Option<String>> {
+    match std::env::var(name) {
+        Ok(value) if value.is_empty() => Ok(None),
+        Ok(value) => Ok(Some(value)),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(e) => Err(format!("{}: {}", name, e).into()),
+    }
+}
+
+impl IpfsService {
+    /// Service of the node and credentials of the environment variables, see `IpfsConfig::from_env`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_env() -> Result<Self> {
+        Self::from_config(&IpfsConfig::from_env()?)
+    }
+
+    /// Service of the first node of the configuration, see `IpfsPool::from_config` to use them all.
+    pub fn from_config(config: &IpfsConfig) -> Result<Self> {
+        let mut services = config.services()?;
+
+        Ok(services.swap_remove(0))
+    }
+
+    /// Return a service sending these credentials with every request.
+    pub fn with_auth(&self, auth: &Auth) -> Result<Self> {
+        self.with_header("authorization", &auth.header_value())
+    }
+}
+
+impl IpfsPool {
+    /// Pool of the nodes of the configuration.
+    pub fn from_config(config: &IpfsConfig) -> Result<Self> {
+        let pool = Self::from_services(config.services()?);
+
+        Ok(pool.with_routing(config.routing))
+    }
+}
+
+/// Durations written as Go parses them, e.g. `30s`.
+pub(crate) mod duration {
+    use std::time::Duration;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&crate::go_duration(*duration))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+
+        parse(&value).ok_or_else(|| D::Error::custom(format!("Invalid duration {}", value)))
+    }
+
+    /// Parse a whole number of hours, minutes, seconds or milliseconds.
+    fn parse(value: &str) -> Option<Duration> {
+        let split = value.find(|c: char| !c.is_ascii_digit())?;
+        let (number, unit) = value.split_at(split);
+        let number: u64 = number.parse().ok()?;
+
+        match unit {
+            "h" => Some(Duration::from_secs(number.checked_mul(3600)?)),
+            "m" => Some(Duration::from_secs(number.checked_mul(60)?)),
+            "s" => Some(Duration::from_secs(number)),
+            "ms" => Some(Duration::from_millis(number)),
+            _ => None,
+        }
+    }
+}
+
+/// Optional durations, see `duration`.
+pub(crate) mod optional_duration {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match duration {
+            Some(duration) => super::duration::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(with = "super::duration")] Duration);
+
+        let duration = Option::<Wrapper>::deserialize(deserializer)?;
+
+        Ok(duration.map(|Wrapper(duration)| duration))
+    }
+}
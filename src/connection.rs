@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+#[cfg(feature = "rustls")]
+use reqwest::{Certificate, Identity};
+use reqwest::{Client, Proxy, Url};
+
+use crate::Result;
+
+/// Connection pooling of the native HTTP client, browsers manage their own connections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionConfig {
+    /// How long an idle connection is kept open, `None` to never close it.
+    pub idle_timeout: Option<Duration>,
+
+    pub max_idle_per_host: usize,
+
+    /// Interval of TCP keepalive probes, `None` to disable them.
+    pub tcp_keepalive: Option<Duration>,
+
+    /// Speak HTTP/2 without negotiating it, the node must support it.
+    pub http2_prior_knowledge: bool,
+
+    /// Ask for gzip or brotli encoded responses, requires the `compression` feature.
+    pub compression: bool,
+
+    /// HTTP, HTTPS or SOCKS proxy all requests go through, credentials may be part of the URL.
+    ///
+    /// SOCKS requires the `socks` feature.
+    pub proxy: Option<Url>,
+
+    /// PEM certificates trusted in addition to the default roots, e.g. of a self-signed node.
+    ///
+    /// TLS options require the `rustls` feature.
+    pub root_certificates: Vec<Vec<u8>>,
+
+    /// PEM private key and certificate chain presented to nodes requiring client authentication.
+    pub identity: Option<Vec<u8>>,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Some(Duration::from_secs(90)),
+            max_idle_per_host: usize::MAX,
+            tcp_keepalive: None,
+            http2_prior_knowledge: false,
+            compression: true,
+            proxy: None,
+            root_certificates: Vec::new(),
+            identity: None,
+        }
+    }
+}
+
+impl ConnectionConfig {
+    pub(crate) fn client(&self) -> Result<Client> {
+        let mut builder = Client::builder()
+            .pool_idle_timeout(self.idle_timeout)
+            .pool_max_idle_per_host(self.max_idle_per_host)
+            .tcp_keepalive(self.tcp_keepalive);
+
+        #[cfg(feature = "compression")]
+        {
+            builder = builder.gzip(self.compression).brotli(self.compression);
+        }
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy.clone())?);
+        }
+
+        #[cfg(feature = "rustls")]
+        {
+            for pem in self.root_certificates.iter() {
+                builder = builder.add_root_certificate(Certificate::from_pem(pem)?);
+            }
+
+            if let Some(pem) = &self.identity {
+                builder = builder.identity(Identity::from_pem(pem)?);
+            }
+        }
+
+        #[cfg(not(feature = "rustls"))]
+        if !self.root_certificates.is_empty() || self.identity.is_some() {
+            return Err("TLS options require the rustls feature".into());
+        }
+
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+
+        Ok(builder.build()?)
+    }
+}
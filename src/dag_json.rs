@@ -0,0 +1,163 @@
+//! Serde helpers for the IPLD kinds JSON cannot represent, encoded as dag-json.
+//!
+//! `dag_put` and `dag_get` exchange dag-json, use these on fields of the nodes, e.g.
+//! `#[serde(with = "ipfs_multi_client::dag_json::bytes")]`, or build nodes with `NodeBuilder`.
+
+use cid::{multibase::Base, Cid};
+
+use serde::{Deserialize, Serialize};
+
+use serde_json::{Map, Value};
+
+use crate::Result;
+
+#[derive(Serialize, Deserialize)]
+struct Slash<T> {
+    #[serde(rename = "/")]
+    inner: T,
+}
+
+/// Byte strings, `{"/": {"bytes": "<base64>"}}` in dag-json.
+///
+/// Works with any type convertible from `Vec<u8>` and viewable as `&[u8]`, e.g. `Bytes`.
+pub mod bytes {
+    use cid::multibase::Base;
+
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Slash;
+
+    #[derive(Serialize, Deserialize)]
+    struct BytesValue {
+        bytes: String,
+    }
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]>,
+        S: Serializer,
+    {
+        let inner = BytesValue {
+            bytes: Base::Base64.encode(value.as_ref()),
+        };
+
+        Slash { inner }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: From<Vec<u8>>,
+        D: Deserializer<'de>,
+    {
+        let Slash { inner } = Slash::<BytesValue>::deserialize(deserializer)?;
+
+        // The spec omits padding but some encoders add it.
+        let data = Base::Base64
+            .decode(inner.bytes.trim_end_matches('='))
+            .map_err(de::Error::custom)?;
+
+        Ok(data.into())
+    }
+}
+
+/// Links to other nodes, `{"/": "<cid>"}` in dag-json.
+pub mod link {
+    use cid::Cid;
+
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Slash;
+
+    pub fn serialize<S>(cid: &Cid, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Slash {
+            inner: cid.to_string(),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Cid, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let Slash { inner } = Slash::<String>::deserialize(deserializer)?;
+
+        Cid::try_from(inner).map_err(de::Error::custom)
+    }
+}
+
+/// Build a dag node field by field, links and bytes being encoded as dag-json expects.
+///
+/// Fields are checked so that no value is mistaken for a link, e.g. a map with a `/` key.
+#[derive(Debug, Clone, Default)]
+pub struct NodeBuilder {
+    map: Map<String, Value>,
+}
+
+impl NodeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn link(mut self, name: &str, cid: Cid) -> Self {
+        let value = serde_json::json!({ "/": cid.to_string() });
+
+        self.map.insert(name.to_owned(), value);
+
+        self
+    }
+
+    pub fn bytes(mut self, name: &str, data: &[u8]) -> Self {
+        let value = serde_json::json!({ "/": { "bytes": Base::Base64.encode(data) } });
+
+        self.map.insert(name.to_owned(), value);
+
+        self
+    }
+
+    /// Any serializable value, failing if it cannot be represented in dag-json.
+    pub fn field<T>(mut self, name: &str, value: &T) -> Result<Self>
+    where
+        T: ?Sized + Serialize,
+    {
+        let value = serde_json::to_value(value)?;
+
+        check(&value)?;
+
+        self.map.insert(name.to_owned(), value);
+
+        Ok(self)
+    }
+
+    /// The node, to pass to `dag_put`.
+    pub fn build(self) -> Value {
+        Value::Object(self.map)
+    }
+}
+
+/// Fail if a map has the `/` key reserved for links and bytes without being one.
+fn check(value: &Value) -> Result<()> {
+    match value {
+        Value::Array(list) => list.iter().try_for_each(check),
+        Value::Object(map) => match map.get("/") {
+            None => map.values().try_for_each(check),
+            Some(inner) if map.len() == 1 => match inner {
+                Value::String(cid) => {
+                    Cid::try_from(cid.as_str())?;
+
+                    Ok(())
+                }
+                Value::Object(bytes)
+                    if bytes.len() == 1 && bytes.get("bytes").is_some_and(Value::is_string) =>
+                {
+                    Ok(())
+                }
+                _ => Err(format!("Invalid link or bytes {}", value).into()),
+            },
+            Some(_) => Err(format!("Key / is reserved for links and bytes in {}", value).into()),
+        },
+        _ => Ok(()),
+    }
+}
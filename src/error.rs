@@ -0,0 +1,184 @@
+use core::fmt;
+
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use reqwest::Url;
+
+use crate::{path::IpfsPath, redact, responses::IPFSError};
+
+/// Parts of the messages Kubo answers with when a path segment cannot be resolved.
+const MISSING_LINK_MESSAGES: [&str; 2] = ["no link named", "could not find"];
+
+/// Failure of a call, with the context needed to tell which node and endpoint failed.
+#[derive(Debug)]
+pub enum Error {
+    /// The node could not be reached, did not answer in time or answered with another error status.
+    Request {
+        /// Address of the node API.
+        node: Url,
+
+        /// Path of the endpoint, e.g. `dag/get`.
+        endpoint: String,
+
+        /// HTTP status code, `None` if no response was received.
+        status: Option<u16>,
+
+        source: Box<dyn std::error::Error>,
+    },
+
+    /// The node answered 429 or 503, asking to slow down.
+    RateLimited {
+        /// Address of the node API.
+        node: Url,
+
+        /// Path of the endpoint, e.g. `dag/get`.
+        endpoint: String,
+
+        status: u16,
+
+        /// Delay asked for by the `Retry-After` header, if any.
+        retry_after: Option<Duration>,
+
+        source: Box<dyn std::error::Error>,
+    },
+
+    /// The node has no link or field at this path.
+    PathNotFound {
+        /// Address of the node API.
+        node: Url,
+
+        path: IpfsPath,
+    },
+}
+
+impl Error {
+    /// HTTP status code of the response, if any.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::Request { status, .. } => *status,
+            Self::RateLimited { status, .. } => Some(*status),
+            Self::PathNotFound { .. } => None,
+        }
+    }
+
+    /// Delay the node asked to wait before sending again, if rate limited.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// Address of the node API that failed, its password redacted.
+    pub fn node(&self) -> &Url {
+        match self {
+            Self::Request { node, .. } => node,
+            Self::RateLimited { node, .. } => node,
+            Self::PathNotFound { node, .. } => node,
+        }
+    }
+
+    /// True if the node failed to resolve a segment of the path.
+    pub(crate) fn is_missing_link(&self) -> bool {
+        let source = match self {
+            Self::Request { source, .. } | Self::RateLimited { source, .. } => source,
+            Self::PathNotFound { .. } => return true,
+        };
+
+        match source.downcast_ref::<IPFSError>() {
+            Some(error) => MISSING_LINK_MESSAGES
+                .iter()
+                .any(|message| error.message.contains(message)),
+            None => false,
+        }
+    }
+
+    /// Error for a response with this error status, `RateLimited` if 429 or 503.
+    ///
+    /// Credentials in the node address are redacted.
+    pub(crate) fn from_status(
+        node: &Url,
+        endpoint: String,
+        status: u16,
+        bytes: &Bytes,
+        retry_after: Option<Duration>,
+    ) -> Self {
+        let node = redact::url(node).into_owned();
+        let source = Self::from_response(bytes);
+
+        match status {
+            429 | 503 => Self::RateLimited {
+                node,
+                endpoint,
+                status,
+                retry_after,
+                source,
+            },
+            _ => Self::Request {
+                node,
+                endpoint,
+                status: Some(status),
+                source,
+            },
+        }
+    }
+
+    /// Error response body, as an IPFS error if it can be parsed as one.
+    pub(crate) fn from_response(bytes: &Bytes) -> Box<dyn std::error::Error> {
+        match serde_json::from_slice::<IPFSError>(bytes) {
+            Ok(error) => error.into(),
+            Err(_) => String::from_utf8_lossy(bytes).into_owned().into(),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Request { source, .. } | Self::RateLimited { source, .. } => {
+                Some(source.as_ref())
+            }
+            Self::PathNotFound { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Request {
+                node,
+                endpoint,
+                status: Some(status),
+                source,
+            } => write!(f, "{}{} returned {}: {}", node, endpoint, status, source),
+            Self::Request {
+                node,
+                endpoint,
+                status: None,
+                source,
+            } => write!(f, "{}{} failed: {}", node, endpoint, source),
+            Self::RateLimited {
+                node,
+                endpoint,
+                status,
+                retry_after: Some(delay),
+                source,
+            } => write!(
+                f,
+                "{}{} returned {}, retry after {:?}: {}",
+                node, endpoint, status, delay, source
+            ),
+            Self::RateLimited {
+                node,
+                endpoint,
+                status,
+                retry_after: None,
+                source,
+            } => write!(f, "{}{} returned {}: {}", node, endpoint, status, source),
+            Self::PathNotFound { node, path } => write!(f, "{} has nothing at {}", node, path),
+        }
+    }
+}
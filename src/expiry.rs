@@ -0,0 +1,173 @@
+use std::{cell::RefCell, collections::BTreeMap, time::Duration};
+
+use cid::Cid;
+
+#[cfg(feature = "diagnostics")]
+use futures_util::StreamExt;
+
+use crate::{batch::PinBatchReport, clock, IpfsService, Result};
+
+/// Unpin requests in flight at once when expiring.
+const EXPIRE_CONCURRENCY: usize = 8;
+
+/// Pins removed by `PinExpiry::expire_pins_now`.
+#[derive(Debug, Default)]
+pub struct ExpiryReport {
+    pub unpinned: PinBatchReport,
+
+    /// Blocks removed by the garbage collection, if run.
+    pub collected: Vec<Cid>,
+}
+
+/// Pins with an expiry time, recorded in a dag node pinned on the node.
+///
+/// The dag node maps CIDs to expiry times, in seconds since the unix epoch.
+/// Keep its CID, from `root`, to `load` the expiries later.
+pub struct PinExpiry {
+    ipfs: IpfsService,
+    expiries: RefCell<BTreeMap<Cid, u64>>,
+    root: RefCell<Option<Cid>>,
+}
+
+impl PinExpiry {
+    /// Start with no expiry recorded.
+    pub fn new(ipfs: IpfsService) -> Self {
+        Self {
+            ipfs,
+            expiries: RefCell::default(),
+            root: RefCell::default(),
+        }
+    }
+
+    /// Read the expiries recorded in this dag node.
+    pub async fn load(ipfs: IpfsService, root: Cid) -> Result<Self> {
+        let recorded: BTreeMap<String, u64> = ipfs.dag_get(root).await?;
+
+        let mut expiries = BTreeMap::new();
+
+        for (cid, expires_at) in recorded {
+            expiries.insert(Cid::try_from(cid)?, expires_at);
+        }
+
+        Ok(Self {
+            ipfs,
+            expiries: RefCell::new(expiries),
+            root: RefCell::new(Some(root)),
+        })
+    }
+
+    /// CID of the dag node of the expiries, `None` until one is recorded.
+    pub fn root(&self) -> Option<Cid> {
+        *self.root.borrow()
+    }
+
+    /// When the pin of this CID expires, since the unix epoch.
+    pub fn expiry(&self, cid: &Cid) -> Option<Duration> {
+        self.expiries
+            .borrow()
+            .get(cid)
+            .map(|secs| Duration::from_secs(*secs))
+    }
+
+    /// Pin recursively until `ttl` elapsed.
+    ///
+    /// Pinning an already recorded CID again replaces its expiry.
+    pub async fn pin_with_ttl(&self, cid: Cid, ttl: Duration) -> Result<()> {
+        self.ipfs.pin_add(cid, true).await?;
+
+        let expires_at = (clock::since_epoch() + ttl).as_secs();
+
+        self.expiries.borrow_mut().insert(cid, expires_at);
+
+        self.save().await
+    }
+
+    /// Unpin every expired CID then, if `gc`, garbage collect the repository.
+    ///
+    /// CIDs failing to unpin are kept, to try again next time.
+    /// Fails if the garbage collection fails, pins are removed by then.
+    /// Garbage collection requires the `diagnostics` feature.
+    pub async fn expire_pins_now(&self, gc: bool) -> Result<ExpiryReport> {
+        let now = clock::since_epoch().as_secs();
+
+        let expired: Vec<Cid> = self
+            .expiries
+            .borrow()
+            .iter()
+            .filter(|(_, expires_at)| **expires_at <= now)
+            .map(|(cid, _)| *cid)
+            .collect();
+
+        let unpinned = self
+            .ipfs
+            .pin_rm_many(expired, true, EXPIRE_CONCURRENCY)
+            .await;
+
+        if !unpinned.succeeded.is_empty() {
+            {
+                let mut expiries = self.expiries.borrow_mut();
+
+                for cid in unpinned.succeeded.iter() {
+                    expiries.remove(cid);
+                }
+            }
+
+            self.save().await?;
+        }
+
+        let collected = if gc {
+            self.collect_garbage().await?
+        } else {
+            Vec::new()
+        };
+
+        Ok(ExpiryReport {
+            unpinned,
+            collected,
+        })
+    }
+
+    /// Garbage collect the repository, returning the CIDs of the removed blocks.
+    #[cfg(feature = "diagnostics")]
+    async fn collect_garbage(&self) -> Result<Vec<Cid>> {
+        let mut removed = self.ipfs.repo_gc().await?.boxed_local();
+
+        let mut collected = Vec::new();
+
+        while let Some(cid) = removed.next().await {
+            collected.push(cid?);
+        }
+
+        Ok(collected)
+    }
+
+    #[cfg(not(feature = "diagnostics"))]
+    async fn collect_garbage(&self) -> Result<Vec<Cid>> {
+        Err("Garbage collection requires the diagnostics feature".into())
+    }
+
+    /// Record the expiries in a new pinned dag node, unpinning the previous one.
+    async fn save(&self) -> Result<()> {
+        let recorded: BTreeMap<String, u64> = self
+            .expiries
+            .borrow()
+            .iter()
+            .map(|(cid, expires_at)| (cid.to_string(), *expires_at))
+            .collect();
+
+        let root = self.ipfs.dag_put(&recorded).await?;
+
+        self.ipfs.pin_add(root, true).await?;
+
+        let previous = self.root.borrow_mut().replace(root);
+
+        match previous {
+            Some(previous) if previous != root => {
+                self.ipfs.pin_rm(previous, true).await?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
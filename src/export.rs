@@ -0,0 +1,39 @@
+use bytes::Bytes;
+
+use cid::Cid;
+
+use futures_util::{stream, StreamExt, TryStreamExt};
+
+use crate::{car::write_car, IpfsService, Result};
+
+/// Number of blocks downloaded at once when exporting.
+const EXPORT_CONCURRENCY: usize = 8;
+
+impl IpfsService {
+    /// CAR file of the DAG, following links at most `max_depth` levels deep, `None` to follow all of them.
+    ///
+    /// Assembled from the blocks, see `car_export_blocks`.
+    pub async fn car_export(&self, root: Cid, max_depth: Option<usize>) -> Result<Bytes> {
+        let refs = self.refs(root, max_depth).await?;
+
+        self.car_export_blocks(root, refs).await
+    }
+
+    /// CAR file with the root block followed by these blocks, e.g. the ones listed by `refs`.
+    ///
+    /// Each block is downloaded with `block_get`.
+    pub async fn car_export_blocks<I>(&self, root: Cid, blocks: I) -> Result<Bytes>
+    where
+        I: IntoIterator<Item = Cid>,
+    {
+        let cids = std::iter::once(root).chain(blocks.into_iter().filter(|cid| *cid != root));
+
+        let blocks: Vec<(Cid, Bytes)> = stream::iter(cids)
+            .map(|cid| async move { self.block_get(cid).await.map(|data| (cid, data)) })
+            .buffered(EXPORT_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        Ok(write_car(&[root], &blocks))
+    }
+}
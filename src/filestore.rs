@@ -0,0 +1,92 @@
+use std::{fs::File, io::Read, path::Path};
+
+use bytes::Bytes;
+
+use cid::Cid;
+
+use futures_util::stream;
+
+use reqwest::{
+    header::{HeaderMap, HeaderValue},
+    multipart::Part,
+};
+
+use crate::{IpfsService, Result, READ_CHUNK_SIZE};
+
+impl IpfsService {
+    /// Return a service asking the node to reference files added with `add_file` instead of copying them.
+    ///
+    /// The node must have the filestore enabled and read the file at the same absolute path.
+    pub fn with_nocopy(&self, nocopy: bool) -> Self {
+        Self {
+            nocopy,
+            ..self.clone()
+        }
+    }
+
+    /// Return a service asking the node to reuse the blocks already in its filestore when adding.
+    pub fn with_fscache(&self, fscache: bool) -> Self {
+        Self {
+            fscache,
+            ..self.clone()
+        }
+    }
+
+    /// Add the file at this path, sent while it is read.
+    ///
+    /// Its absolute path is sent along, see `with_nocopy`.
+    pub async fn add_file<P>(&self, path: P) -> Result<Cid>
+    where
+        P: AsRef<Path>,
+    {
+        let path = std::fs::canonicalize(path)?;
+
+        let abspath = match path.to_str() {
+            Some(abspath) => abspath,
+            None => return Err(format!("{} is not valid UTF-8", path.display()).into()),
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert("Abspath", HeaderValue::from_str(abspath)?);
+
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => return Err(format!("{} is not a file", path.display()).into()),
+        };
+
+        let file = File::open(&path)?;
+
+        let body = reqwest::Body::wrap_stream(stream::iter(chunks(file)));
+
+        let part = Part::stream(body).file_name(name).headers(headers);
+
+        self.add_part(part).await
+    }
+}
+
+/// Read the file chunk by chunk.
+fn chunks(mut file: File) -> impl Iterator<Item = std::io::Result<Bytes>> {
+    let mut done = false;
+
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+
+        let mut buf = vec![0; READ_CHUNK_SIZE];
+
+        match file.read(&mut buf) {
+            Ok(0) => None,
+            Ok(read) => {
+                buf.truncate(read);
+
+                Some(Ok(Bytes::from(buf)))
+            }
+            Err(e) => {
+                done = true;
+
+                Some(Err(e))
+            }
+        }
+    })
+}
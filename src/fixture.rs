@@ -0,0 +1,102 @@
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use bytes::Bytes;
+
+use cid::multibase::{self, Base};
+
+use reqwest::{Request, Url};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{endpoint, Result};
+
+/// Node responses keyed by request, e.g. `dag/get?arg=...&output-codec=dag-json`.
+///
+/// Serialize to JSON to store as a fixture file.
+/// Request bodies are not part of the key, uploads with the same query share a response.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fixtures {
+    pub responses: BTreeMap<String, Recorded>,
+}
+
+/// Response of the node to one request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recorded {
+    pub status: u16,
+
+    /// Body if valid UTF-8.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+
+    /// Body as multibase otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary: Option<String>,
+}
+
+impl Recorded {
+    fn new(status: u16, body: &[u8]) -> Self {
+        match std::str::from_utf8(body) {
+            Ok(text) => Self {
+                status,
+                text: Some(text.to_owned()),
+                binary: None,
+            },
+            Err(_) => Self {
+                status,
+                text: None,
+                binary: Some(multibase::encode(Base::Base64, body)),
+            },
+        }
+    }
+
+    fn body(&self) -> Result<Bytes> {
+        match (&self.text, &self.binary) {
+            (Some(text), _) => Ok(Bytes::from(text.clone())),
+            (None, Some(binary)) => Ok(multibase::decode(binary)?.1.into()),
+            (None, None) => Ok(Bytes::new()),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) enum FixtureMode {
+    Record(Rc<RefCell<Fixtures>>),
+    Replay(Rc<Fixtures>),
+}
+
+/// Key of the request, its path relative to the node API and its query.
+pub(crate) fn key(base_url: &Url, request: &Request) -> String {
+    let url = request.url();
+
+    let endpoint = endpoint(base_url, url);
+
+    match url.query() {
+        Some(query) => format!("{}?{}", endpoint, query),
+        None => endpoint.to_owned(),
+    }
+}
+
+impl FixtureMode {
+    pub fn record(&self, key: String, result: &Result<(u16, Bytes)>) {
+        if let (Self::Record(fixtures), Ok((status, bytes))) = (self, result) {
+            let recorded = Recorded::new(*status, bytes);
+
+            fixtures.borrow_mut().responses.insert(key, recorded);
+        }
+    }
+
+    /// The recorded response, `None` if recording.
+    pub fn replay(&self, key: &str) -> Option<Result<(u16, Bytes)>> {
+        let fixtures = match self {
+            Self::Replay(fixtures) => fixtures,
+            Self::Record(_) => return None,
+        };
+
+        let res = match fixtures.responses.get(key) {
+            Some(recorded) => recorded.body().map(|body| (recorded.status, body)),
+            None => Err(format!("No fixture for {}", key).into()),
+        };
+
+        Some(res)
+    }
+}
@@ -0,0 +1,171 @@
+use std::rc::Rc;
+
+use bytes::Bytes;
+
+use cid::Cid;
+
+use reqwest::{Client, Url};
+
+use crate::{car::read_car, path::IpfsPath, runtime::compat, verify::verify, Result};
+
+#[cfg(feature = "ipns")]
+use crate::ipns::IpnsRecord;
+
+/// Public HTTP gateways used for reads when no pool node answers.
+pub(crate) struct Gateways {
+    client: Client,
+    urls: Vec<Url>,
+}
+
+impl Gateways {
+    pub fn new(urls: Vec<Url>) -> Self {
+        Self {
+            client: Client::new(),
+            urls,
+        }
+    }
+
+    /// Fetch the path from the first gateway answering.
+    ///
+    /// The response format is left to the gateway if `format` is `None`.
+    pub async fn get(&self, path: &IpfsPath, format: Option<&str>) -> Result<Bytes> {
+        self.get_checked(path, format, Ok).await
+    }
+
+    /// Like `get` but the next gateway is tried if the response fails the check.
+    pub async fn get_checked<F, T>(
+        &self,
+        path: &IpfsPath,
+        format: Option<&str>,
+        check: F,
+    ) -> Result<T>
+    where
+        F: Fn(Bytes) -> Result<T>,
+    {
+        let path = path.to_string();
+
+        let mut last_error = None;
+
+        for gateway in self.urls.iter() {
+            let url = gateway.join(path.trim_start_matches('/'))?;
+
+            let mut request = self.client.get(url);
+
+            if let Some(format) = format {
+                request = request.query(&[("format", format)]);
+            }
+
+            let res = match compat(request.send()).await {
+                Ok(response) => response.error_for_status(),
+                Err(e) => Err(e),
+            };
+
+            let bytes = match res {
+                Ok(response) => compat(response.bytes()).await,
+                Err(e) => Err(e),
+            };
+
+            match bytes.map_err(Into::into).and_then(&check) {
+                Ok(res) => return Ok(res),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| "No gateway configured".into()))
+    }
+}
+
+/// Read only client for trustless gateways.
+///
+/// Every block is hashed locally and checked against its CID, gateways need not be trusted.
+#[derive(Clone)]
+pub struct TrustlessGateway {
+    gateways: Rc<Gateways>,
+}
+
+impl TrustlessGateway {
+    /// Gateway URLs are the origin only, e.g. `https://ipfs.io/`.
+    pub fn new<I>(urls: I) -> Self
+    where
+        I: IntoIterator<Item = Url>,
+    {
+        let gateways = Gateways::new(urls.into_iter().collect());
+
+        Self {
+            gateways: Rc::new(gateways),
+        }
+    }
+
+    /// Download the raw block with this CID.
+    pub async fn block_get(&self, cid: Cid) -> Result<Bytes> {
+        self.gateways
+            .get_checked(&IpfsPath::ipfs(cid), Some("raw"), |bytes| {
+                verify(&cid, &bytes)?;
+
+                Ok(bytes)
+            })
+            .await
+    }
+
+    /// Download the blocks from the root to the end of the path, and the DAG under it, as a CAR.
+    ///
+    /// Blocks are returned in the order the gateway sent them.
+    /// Only `/ipfs/` paths can be verified.
+    pub async fn car_get<P>(&self, path: P) -> Result<Vec<(Cid, Bytes)>>
+    where
+        P: Into<IpfsPath>,
+    {
+        let path = path.into();
+
+        let cid = match path.cid() {
+            Some(cid) => cid,
+            None => return Err("Only /ipfs/ paths can be verified".into()),
+        };
+
+        self.gateways
+            .get_checked(&path, Some("car"), |bytes| {
+                let blocks = read_car(bytes)?;
+
+                if !blocks.iter().any(|(block_cid, _)| *block_cid == cid) {
+                    return Err(format!("CAR does not contain {}", cid).into());
+                }
+
+                for (block_cid, data) in blocks.iter() {
+                    verify(block_cid, data)?;
+                }
+
+                Ok(blocks)
+            })
+            .await
+    }
+
+    /// Download the IPNS record of the name and check its signature.
+    ///
+    /// The record may be expired, see `IpnsRecord::is_expired`.
+    #[cfg(feature = "ipns")]
+    pub async fn ipns_record(&self, name: Cid) -> Result<IpnsRecord> {
+        let path = IpfsPath::ipns(name.to_string())?;
+
+        self.gateways
+            .get_checked(&path, Some("ipns-record"), |bytes| {
+                let record = IpnsRecord::parse(&bytes)?;
+
+                record.verify(&name)?;
+
+                Ok(record)
+            })
+            .await
+    }
+
+    /// Resolve the IPNS name from a verified, unexpired record.
+    #[cfg(feature = "ipns")]
+    pub async fn name_resolve(&self, name: Cid) -> Result<IpfsPath> {
+        let record = self.ipns_record(name).await?;
+
+        if record.is_expired()? {
+            return Err(format!("IPNS record of {} expired at {}", name, record.validity).into());
+        }
+
+        record.path()
+    }
+}
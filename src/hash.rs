@@ -0,0 +1,66 @@
+use multihash::{Code, Multihash, MultihashDigest};
+
+/// Hash function of the CIDs the node creates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashFunction {
+    #[default]
+    Sha2_256,
+    Sha2_512,
+    Sha3_256,
+    Sha3_512,
+    Blake2b256,
+    Blake2b512,
+    Blake3,
+}
+
+impl HashFunction {
+    /// Name of the function for the node, e.g. `blake3`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sha2_256 => "sha2-256",
+            Self::Sha2_512 => "sha2-512",
+            Self::Sha3_256 => "sha3-256",
+            Self::Sha3_512 => "sha3-512",
+            Self::Blake2b256 => "blake2b-256",
+            Self::Blake2b512 => "blake2b-512",
+            Self::Blake3 => "blake3",
+        }
+    }
+
+    /// Multihash code of the function.
+    pub fn code(&self) -> u64 {
+        self.multihash_code().into()
+    }
+
+    /// Function of this multihash code, if supported.
+    pub fn from_code(code: u64) -> Option<Self> {
+        [
+            Self::Sha2_256,
+            Self::Sha2_512,
+            Self::Sha3_256,
+            Self::Sha3_512,
+            Self::Blake2b256,
+            Self::Blake2b512,
+            Self::Blake3,
+        ]
+        .into_iter()
+        .find(|hash| hash.code() == code)
+    }
+
+    /// Hash the data, e.g. to compute a CID locally.
+    pub fn digest(&self, data: &[u8]) -> Multihash {
+        self.multihash_code().digest(data)
+    }
+
+    fn multihash_code(&self) -> Code {
+        match self {
+            Self::Sha2_256 => Code::Sha2_256,
+            Self::Sha2_512 => Code::Sha2_512,
+            Self::Sha3_256 => Code::Sha3_256,
+            Self::Sha3_512 => Code::Sha3_512,
+            Self::Blake2b256 => Code::Blake2b256,
+            Self::Blake2b512 => Code::Blake2b512,
+            Self::Blake3 => Code::Blake3_256,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use reqwest::Url;
+
+use crate::breaker::CircuitState;
+
+/// How often pool nodes are probed and how long a probe may take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthCheck {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Outcome of the last health probe of a node.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeHealth {
+    /// `None` until the node has been probed once.
+    pub up: Option<bool>,
+
+    /// Round trip time of the last successful probe.
+    pub latency: Option<Duration>,
+
+    /// Time of the last probe, since the unix epoch.
+    pub checked_at: Option<Duration>,
+}
+
+impl NodeHealth {
+    pub(crate) fn is_down(&self) -> bool {
+        self.up == Some(false)
+    }
+}
+
+/// Weight of the newest sample in the rolling averages of `NodeStats`.
+const SMOOTHING: f64 = 0.2;
+
+/// Rolling averages of the requests a pool node served, more recent ones weighing more.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NodeStats {
+    /// Time to answer, `None` until the node answered once.
+    pub latency: Option<Duration>,
+
+    /// Share of requests the node failed to answer, from 0 to 1.
+    pub error_rate: f64,
+}
+
+impl NodeStats {
+    pub(crate) fn record_latency(&mut self, latency: Duration) {
+        self.latency = Some(match self.latency {
+            Some(average) => average.mul_f64(1.0 - SMOOTHING) + latency.mul_f64(SMOOTHING),
+            None => latency,
+        });
+    }
+
+    pub(crate) fn record_outcome(&mut self, failed: bool) {
+        let sample = if failed { 1.0 } else { 0.0 };
+
+        self.error_rate = self.error_rate * (1.0 - SMOOTHING) + sample * SMOOTHING;
+    }
+
+    /// Expected cost of a request, the latency divided by the success rate.
+    ///
+    /// Zero for nodes not tried yet so that they are, infinite for nodes that never answered.
+    pub(crate) fn score(&self) -> f64 {
+        let latency = match self.latency {
+            Some(latency) => latency.as_secs_f64(),
+            None if self.error_rate > 0.0 => return f64::INFINITY,
+            None => return 0.0,
+        };
+
+        latency / (1.0 - self.error_rate).max(0.01)
+    }
+}
+
+/// Health and routing state of a pool node.
+#[derive(Debug, Clone)]
+pub struct NodeStatus {
+    pub url: Url,
+    pub health: NodeHealth,
+    pub circuit: CircuitState,
+    pub stats: NodeStats,
+}
@@ -0,0 +1,532 @@
+use std::time::Duration;
+
+use cid::{multibase::Base, Cid};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+use multihash::{Code, Multihash, MultihashDigest};
+
+use reqwest::multipart::{Form, Part};
+
+use serde::Deserialize;
+
+use crate::{
+    clock,
+    path::IpfsPath,
+    peer_id::{PeerId, LIBP2P_KEY_CODEC},
+    responses::IPFSError,
+    IpfsService, Result,
+};
+
+/// Prefix of the data covered by the V2 signature.
+const SIGNATURE_V2_PREFIX: &[u8] = b"ipns-signature:";
+
+/// Multihash of public keys small enough to be embedded in the peer id.
+const IDENTITY: u64 = 0x00;
+
+/// Key type of ed25519 keys in the libp2p `PublicKey` protobuf.
+const ED25519_KEY_TYPE: u64 = 1;
+
+/// IPNS record, the signed pointer an IPNS name resolves to.
+///
+/// Fields are read from the signed CBOR data, only V2 signatures are supported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpnsRecord {
+    /// Path the name points to, e.g. `/ipfs/<cid>`.
+    pub value: String,
+
+    /// Incremented on each publication, the highest wins.
+    pub sequence: u64,
+
+    /// End of validity as RFC 3339.
+    pub validity: String,
+
+    /// How long the record may be cached.
+    pub ttl: Option<Duration>,
+
+    /// Protobuf encoded public key, absent if embedded in the name.
+    pub public_key: Option<Vec<u8>>,
+
+    signature: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl IpnsRecord {
+    /// Parse the protobuf record, e.g. from `routing_get`.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = bytes;
+
+        let mut public_key = None;
+        let mut signature = None;
+        let mut data = None;
+
+        while !reader.is_empty() {
+            let key = read_uvarint(&mut reader)?;
+
+            match (key >> 3, key & 0x07) {
+                (_, 0) => {
+                    read_uvarint(&mut reader)?;
+                }
+                (field, 2) => {
+                    let len = read_uvarint(&mut reader)? as usize;
+                    let value = take(&mut reader, len)?.to_vec();
+
+                    match field {
+                        7 => public_key = Some(value),
+                        8 => signature = Some(value),
+                        9 => data = Some(value),
+                        _ => {}
+                    }
+                }
+                _ => return Err("Unsupported protobuf field in IPNS record".into()),
+            }
+        }
+
+        let (signature, data) = match (signature, data) {
+            (Some(signature), Some(data)) => (signature, data),
+            _ => return Err("IPNS record has no V2 signature".into()),
+        };
+
+        let mut record = Self {
+            value: String::new(),
+            sequence: 0,
+            validity: String::new(),
+            ttl: None,
+            public_key,
+            signature,
+            data,
+        };
+
+        record.read_data()?;
+
+        Ok(record)
+    }
+
+    /// Create a record pointing the name of the key to the path, signed locally.
+    ///
+    /// The record is valid for `lifetime` from now and may be cached for `ttl`.
+    pub fn create(
+        key: &SigningKey,
+        value: &IpfsPath,
+        sequence: u64,
+        lifetime: Duration,
+        ttl: Duration,
+    ) -> Self {
+        let value = value.to_string();
+        let validity = format_rfc3339(clock::since_epoch() + lifetime);
+
+        // DAG-CBOR map, keys sorted by length then bytes
+        let mut data = Vec::new();
+        cbor_head(&mut data, 5, 5);
+        cbor_text(&mut data, "TTL");
+        cbor_head(&mut data, 0, ttl.as_nanos() as u64);
+        cbor_text(&mut data, "Value");
+        cbor_head(&mut data, 2, value.len() as u64);
+        data.extend_from_slice(value.as_bytes());
+        cbor_text(&mut data, "Sequence");
+        cbor_head(&mut data, 0, sequence);
+        cbor_text(&mut data, "Validity");
+        cbor_head(&mut data, 2, validity.len() as u64);
+        data.extend_from_slice(validity.as_bytes());
+        cbor_text(&mut data, "ValidityType");
+        cbor_head(&mut data, 0, 0);
+
+        let mut signed = SIGNATURE_V2_PREFIX.to_vec();
+        signed.extend_from_slice(&data);
+
+        let signature = key.sign(&signed).to_bytes().to_vec();
+
+        Self {
+            value,
+            sequence,
+            validity,
+            ttl: Some(ttl),
+            public_key: None,
+            signature,
+            data,
+        }
+    }
+
+    /// IPNS name of an ed25519 key, the public key being embedded in it.
+    pub fn name(key: &VerifyingKey) -> Cid {
+        let mut public_key = vec![0x08, ED25519_KEY_TYPE as u8, 0x12, 32];
+        public_key.extend_from_slice(key.as_bytes());
+
+        let multihash = Multihash::wrap(IDENTITY, &public_key).expect("Key fits a multihash");
+
+        Cid::new_v1(LIBP2P_KEY_CODEC, multihash)
+    }
+
+    /// Protobuf encoding, with the V1 fields older nodes read.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        protobuf_bytes(&mut out, 1, self.value.as_bytes());
+        protobuf_varint(&mut out, 3, 0);
+        protobuf_bytes(&mut out, 4, self.validity.as_bytes());
+        protobuf_varint(&mut out, 5, self.sequence);
+
+        if let Some(ttl) = self.ttl {
+            protobuf_varint(&mut out, 6, ttl.as_nanos() as u64);
+        }
+
+        if let Some(public_key) = &self.public_key {
+            protobuf_bytes(&mut out, 7, public_key);
+        }
+
+        protobuf_bytes(&mut out, 8, &self.signature);
+        protobuf_bytes(&mut out, 9, &self.data);
+
+        out
+    }
+
+    /// Fields of the signed CBOR map.
+    fn read_data(&mut self) -> Result<()> {
+        let data = self.data.clone();
+        let mut reader = data.as_slice();
+
+        let entries = match read_cbor_head(&mut reader)? {
+            (5, entries) => entries,
+            _ => return Err("IPNS record data is not a map".into()),
+        };
+
+        for _ in 0..entries {
+            let key = match read_cbor_head(&mut reader)? {
+                (3, len) => take(&mut reader, len as usize)?,
+                _ => return Err("IPNS record data key is not a string".into()),
+            };
+
+            let (major, value) = read_cbor_head(&mut reader)?;
+
+            match (key, major) {
+                (b"Value", 2) => {
+                    let bytes = take(&mut reader, value as usize)?;
+
+                    self.value = String::from_utf8(bytes.to_vec())?;
+                }
+                (b"Validity", 2) => {
+                    let bytes = take(&mut reader, value as usize)?;
+
+                    self.validity = String::from_utf8(bytes.to_vec())?;
+                }
+                (b"Sequence", 0) => self.sequence = value,
+                (b"TTL", 0) => self.ttl = Some(Duration::from_nanos(value)),
+                (_, 0) | (_, 1) => {}
+                (_, 2) | (_, 3) => {
+                    take(&mut reader, value as usize)?;
+                }
+                _ => return Err("Unsupported value in IPNS record data".into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Path the name points to.
+    pub fn path(&self) -> Result<IpfsPath> {
+        Ok(self.value.parse()?)
+    }
+
+    /// Check the signature against the public key of the IPNS name.
+    ///
+    /// Only ed25519 keys are supported.
+    pub fn verify(&self, name: &Cid) -> Result<()> {
+        let peer_id = PeerId::try_from(*name)?;
+        let multihash = peer_id.multihash();
+
+        let public_key = match (multihash.code(), &self.public_key) {
+            (IDENTITY, _) => multihash.digest(),
+            (_, Some(public_key)) => {
+                if Code::Sha2_256.digest(public_key) != *multihash {
+                    return Err("IPNS record public key does not match the name".into());
+                }
+
+                public_key.as_slice()
+            }
+            (_, None) => return Err("IPNS record has no public key".into()),
+        };
+
+        let key = ed25519_key(public_key)?;
+        let signature = Signature::from_slice(&self.signature)?;
+
+        let mut signed = SIGNATURE_V2_PREFIX.to_vec();
+        signed.extend_from_slice(&self.data);
+
+        key.verify_strict(&signed, &signature)?;
+
+        Ok(())
+    }
+
+    /// True once the end of validity has passed.
+    pub fn is_expired(&self) -> Result<bool> {
+        let end = parse_rfc3339(&self.validity)?;
+
+        Ok(clock::since_epoch() >= end)
+    }
+}
+
+/// Routing query event of a value found, the record in base64.
+#[derive(Deserialize)]
+struct ValueEvent {
+    #[serde(rename = "Extra")]
+    extra: String,
+}
+
+impl IpfsService {
+    /// Fetch the IPNS record of the name from the routing system, check it with `IpnsRecord::verify`.
+    pub async fn routing_get(&self, name: Cid) -> Result<IpnsRecord> {
+        let url = self.base_url.join("routing/get")?;
+
+        let request = self
+            .post_fetch(url)
+            .query(&[("arg", &format!("/ipns/{}", name))]);
+
+        let bytes = self.send_idempotent(request).await?;
+
+        if let Ok(res) = serde_json::from_slice::<ValueEvent>(&bytes) {
+            return IpnsRecord::parse(&Base::Base64Pad.decode(res.extra)?);
+        }
+
+        let error = serde_json::from_slice::<IPFSError>(&bytes)?;
+
+        Err(error.into())
+    }
+
+    /// Publish a record signed outside the node, e.g. by `IpnsRecord::create`.
+    pub async fn routing_put(&self, name: Cid, record: &IpnsRecord) -> Result<()> {
+        let url = self.base_url.join("routing/put")?;
+
+        let form = Form::new().part("value-file", Part::bytes(record.to_bytes()));
+
+        let request = self
+            .client
+            .post(url)
+            .query(&[("arg", &format!("/ipns/{}", name))])
+            .query(&[("allow-offline", "true")])
+            .multipart(form);
+
+        let bytes = self.send(request).await?;
+
+        match serde_json::from_slice::<IPFSError>(&bytes) {
+            Ok(error) => Err(error.into()),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+/// Key of the libp2p `PublicKey` protobuf, `{ 1: type, 2: data }`.
+fn ed25519_key(mut reader: &[u8]) -> Result<VerifyingKey> {
+    let mut key_type = None;
+    let mut data = None;
+
+    while !reader.is_empty() {
+        match read_uvarint(&mut reader)? {
+            0x08 => key_type = Some(read_uvarint(&mut reader)?),
+            0x12 => {
+                let len = read_uvarint(&mut reader)? as usize;
+
+                data = Some(take(&mut reader, len)?);
+            }
+            _ => return Err("Invalid public key protobuf".into()),
+        }
+    }
+
+    match (key_type, data) {
+        (Some(ED25519_KEY_TYPE), Some(data)) => Ok(VerifyingKey::try_from(data)?),
+        (Some(_), Some(_)) => Err("Only ed25519 IPNS keys are supported".into()),
+        _ => Err("Invalid public key protobuf".into()),
+    }
+}
+
+/// Time since the unix epoch of a `2006-01-02T15:04:05.999999999Z07:00` timestamp.
+fn parse_rfc3339(text: &str) -> Result<Duration> {
+    let invalid = || format!("Invalid RFC 3339 time {}", text);
+
+    let number = |range: std::ops::Range<usize>| -> Result<i64> {
+        let digits = text.get(range).ok_or_else(invalid)?;
+
+        digits.parse::<i64>().map_err(|_| invalid().into())
+    };
+
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+
+    let mut rest = text.get(19..).ok_or_else(invalid)?;
+    let mut nanos = 0;
+
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = fraction
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(fraction.len());
+
+        let digits = &fraction[..len.min(9)];
+
+        nanos = digits.parse::<u32>().map_err(|_| invalid())? * 10u32.pow(9 - digits.len() as u32);
+
+        rest = &fraction[len..];
+    }
+
+    let offset = match rest {
+        "Z" => 0,
+        _ if rest.len() == 6 && rest.is_ascii() => {
+            let sign = match &rest[..1] {
+                "+" => 1,
+                "-" => -1,
+                _ => return Err(invalid().into()),
+            };
+
+            let hours = rest[1..3].parse::<i64>().map_err(|_| invalid())?;
+            let minutes = rest[4..6].parse::<i64>().map_err(|_| invalid())?;
+
+            sign * (hours * 3600 + minutes * 60)
+        }
+        _ => return Err(invalid().into()),
+    };
+
+    // Days from civil, http://howardhinnant.github.io/date_algorithms.html
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let seconds = days * 86400 + hour * 3600 + minute * 60 + second - offset;
+
+    match u64::try_from(seconds) {
+        Ok(seconds) => Ok(Duration::new(seconds, nanos)),
+        Err(_) => Ok(Duration::ZERO),
+    }
+}
+
+fn take<'a>(reader: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if len > reader.len() {
+        return Err("Truncated IPNS record".into());
+    }
+
+    let (head, tail) = reader.split_at(len);
+    *reader = tail;
+
+    Ok(head)
+}
+
+fn read_uvarint(reader: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+
+    for shift in (0..64).step_by(7) {
+        let (byte, tail) = match reader.split_first() {
+            Some(split) => split,
+            None => return Err("Truncated IPNS record".into()),
+        };
+
+        *reader = tail;
+        value |= ((byte & 0x7F) as u64) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err("Varint overflow in IPNS record".into())
+}
+
+/// CBOR major type and argument.
+fn read_cbor_head(reader: &mut &[u8]) -> Result<(u8, u64)> {
+    let initial = take(reader, 1)?[0];
+
+    let major = initial >> 5;
+
+    let value = match initial & 0x1F {
+        info @ 0..=23 => info as u64,
+        info @ 24..=27 => {
+            let len = 1 << (info - 24);
+
+            take(reader, len)?
+                .iter()
+                .fold(0, |value, byte| value << 8 | *byte as u64)
+        }
+        _ => return Err("Unsupported CBOR in IPNS record".into()),
+    };
+
+    Ok((major, value))
+}
+
+/// Timestamp of a time since the unix epoch, as `2006-01-02T15:04:05.999999999Z`.
+fn format_rfc3339(time: Duration) -> String {
+    let seconds = time.as_secs() as i64;
+
+    // Civil from days, http://howardhinnant.github.io/date_algorithms.html
+    let days = seconds.div_euclid(86400) + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let time_of_day = seconds.rem_euclid(86400);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60,
+        time.subsec_nanos()
+    )
+}
+
+fn write_uvarint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+
+    out.push(value as u8);
+}
+
+fn protobuf_varint(out: &mut Vec<u8>, field: u64, value: u64) {
+    write_uvarint(out, field << 3);
+    write_uvarint(out, value);
+}
+
+fn protobuf_bytes(out: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    write_uvarint(out, field << 3 | 2);
+    write_uvarint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn cbor_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+
+    match value {
+        0..=23 => out.push(major | value as u8),
+        24..=0xFF => out.extend_from_slice(&[major | 24, value as u8]),
+        0x100..=0xFFFF => {
+            out.push(major | 25);
+            out.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xFFFF_FFFF => {
+            out.push(major | 26);
+            out.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+fn cbor_text(out: &mut Vec<u8>, text: &str) {
+    cbor_head(out, 3, text.len() as u64);
+    out.extend_from_slice(text.as_bytes());
+}
@@ -1,46 +1,269 @@
+mod abort;
+mod api;
+mod backoff;
+mod base;
+mod batch;
+mod breaker;
+mod cache;
+mod capability;
+mod car;
+mod chunker;
+mod cidv0;
+mod clock;
+#[cfg(feature = "cluster")]
+mod cluster;
+mod coalesce;
+#[cfg(feature = "pubsub")]
+mod codec;
+mod config;
+#[cfg(not(target_arch = "wasm32"))]
+mod connection;
+pub mod dag_json;
+mod error;
+mod expiry;
+mod export;
+#[cfg(not(target_arch = "wasm32"))]
+mod filestore;
+#[cfg(feature = "testing")]
+mod fixture;
+mod gateway;
+mod hash;
+mod health;
+#[cfg(feature = "ipns")]
+mod ipns;
+mod limit;
+#[cfg(feature = "testing")]
+mod memory;
+mod metrics;
+#[cfg(feature = "mfs")]
+mod mfs;
+#[cfg(feature = "testing")]
+mod mock;
+mod multipart;
+mod ndjson;
+mod path;
+mod peer_id;
+mod pool;
+#[cfg(feature = "pubsub")]
+mod presence;
+#[cfg(feature = "dht")]
+mod provide;
+#[cfg(feature = "pubsub")]
+mod pubsub;
+mod reader;
+mod redact;
+mod repair;
+mod replication;
+#[cfg(not(target_arch = "wasm32"))]
+mod republish;
 mod responses;
-
-use std::{borrow::Cow, rc::Rc};
+mod retry;
+mod ring;
+#[cfg(feature = "pubsub")]
+mod rpc;
+mod runtime;
+#[cfg(feature = "tracing")]
+mod trace;
+mod unixfs;
+#[cfg(target_arch = "wasm32")]
+mod upload;
+mod verify;
+mod warm;
+
+pub use api::IpfsApi;
+#[cfg(feature = "pubsub")]
+pub use api::MsgStream;
+pub use backoff::Backoff;
+pub use base::IpnsBase;
+pub use batch::PinBatchReport;
+pub use breaker::{CircuitBreakerConfig, CircuitState};
+pub use cache::CacheConfig;
+pub use capability::{Capabilities, CompatMode};
+pub use car::{write_car, write_car_v2, CarBlocks, CarFile};
+pub use chunker::Chunker;
+pub use cidv0::{same_cid, to_cid_v0, to_cid_v1};
+#[cfg(feature = "cluster")]
+pub use cluster::{
+    ClusterClient, ClusterError, ClusterPeer, ClusterPin, ClusterPinStatus, PeerPinStatus,
+    DEFAULT_CLUSTER_URI,
+};
+#[cfg(feature = "pubsub")]
+pub use codec::Codec;
+pub use config::{
+    Auth, IpfsConfig, ENV_API_PASSWORD, ENV_API_TOKEN, ENV_API_URL, ENV_API_USERNAME,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use connection::ConnectionConfig;
+pub use dag_json::NodeBuilder;
+#[cfg(feature = "ipns")]
+pub use ed25519_dalek::SigningKey;
+pub use error::Error;
+pub use expiry::{ExpiryReport, PinExpiry};
+#[cfg(feature = "testing")]
+pub use fixture::{Fixtures, Recorded};
+pub use gateway::TrustlessGateway;
+pub use hash::HashFunction;
+pub use health::{HealthCheck, NodeHealth, NodeStats, NodeStatus};
+#[cfg(feature = "ipns")]
+pub use ipns::IpnsRecord;
+#[cfg(feature = "testing")]
+pub use memory::{MemoryIpfs, DAG_JSON_CODEC};
+pub use metrics::{EndpointMetrics, MetricsSnapshot, LATENCY_BUCKETS};
+#[cfg(feature = "testing")]
+pub use mock::{MockCall, MockIpfs};
+pub use multipart::FileMetadata;
+pub use ndjson::response_to_json_stream;
+pub use path::{IpfsPath, PathError, PathRoot};
+pub use peer_id::{PeerId, LIBP2P_KEY_CODEC};
+pub use pool::{Hedging, IpfsPool, NodeOutcome, Routing};
+#[cfg(feature = "pubsub")]
+pub use presence::{Presence, PresenceConfig, PresenceEvent};
+#[cfg(feature = "dht")]
+pub use provide::ProvidersReport;
+#[cfg(feature = "pubsub")]
+pub use pubsub::{pubsub_sub_stream, PubsubEvent, Subscription, SubscriptionHandle};
+pub use reader::UnixFsReader;
+pub use repair::ReadRepair;
+pub use replication::{
+    sync_pins, PinDiffReport, PinReplication, PinSyncReport, ReplicationError, ReplicationStatus,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use republish::Republisher;
+#[cfg(feature = "mfs")]
+pub use responses::FilesStat;
+#[cfg(feature = "dht")]
+pub use responses::Provider;
+#[cfg(feature = "pubsub")]
+pub use responses::PubSubMsg;
+pub use responses::{
+    AddEntry, AddOutput, BlockStat, IdResponse, KeyInfo, KeyList, KeySignature, LsEntry,
+    NamePublishOptions, NamePublishResponse, NamedPinList, PinFilter, PinInfo, PinList, PinType,
+    PingReply,
+};
+#[cfg(feature = "diagnostics")]
+pub use responses::{DhtBucket, DhtPeer, DhtStats, ProvideStats};
+pub use retry::{is_idempotent, RetryPolicy};
+#[cfg(feature = "pubsub")]
+pub use rpc::RpcOptions;
+pub use unixfs::{UnixFsBuilder, UnixFsDag};
+pub use verify::IntegrityError;
+pub use warm::WarmProgress;
+
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    fmt,
+    rc::Rc,
+    time::Duration,
+};
 
 use futures_util::{
-    future::{AbortRegistration, Abortable},
-    AsyncBufReadExt, Stream, StreamExt, TryStreamExt,
+    future::{self, Abortable, Either},
+    stream, AsyncWrite, AsyncWriteExt, Stream, StreamExt, TryStreamExt,
 };
 
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::responses::*;
+use crate::{
+    abort::InFlight,
+    cache::{Cache, CacheKey},
+    coalesce::{CoalesceKey, Coalescer},
+    limit::Limiter,
+    metrics::Metrics,
+    responses::*,
+    runtime::compat_stream,
+};
+
+#[cfg(feature = "testing")]
+use crate::fixture::FixtureMode;
 
 use cid::{
-    multibase::{encode, Base},
-    Cid,
+    multibase::{self, Base},
+    Cid, Version,
 };
 
 use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, USER_AGENT},
     multipart::{Form, Part},
-    Client, Response, Url,
+    Client, Request, RequestBuilder, Response, Url,
 };
 
 use bytes::Bytes;
 
 pub const DEFAULT_URI: &str = "http://127.0.0.1:5001/api/v0/";
 
+/// Path of the RPC API on Kubo nodes.
+pub const DEFAULT_API_PATH: &str = "/api/v0/";
+
+/// Size of the chunks readers are read in.
+#[cfg(not(target_arch = "wasm32"))]
+const READ_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Multicodec of blocks holding plain bytes.
+pub const RAW_CODEC: u64 = 0x55;
+
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 #[derive(Clone)]
 pub struct IpfsService {
     client: Client,
     base_url: Rc<Url>,
+    timeout: Option<Duration>,
+    retry: Option<Rc<RetryPolicy>>,
+    in_flight: Rc<InFlight>,
+    subscriptions: Rc<InFlight>,
+    verify: bool,
+    cache: Option<Rc<RefCell<Cache>>>,
+    keys: Option<Rc<RefCell<Option<Vec<KeyInfo>>>>>,
+    coalescer: Rc<Coalescer>,
+    limiter: Option<Rc<Limiter>>,
+    max_response_size: Option<usize>,
+    metrics: Option<Rc<Metrics>>,
+    offline: bool,
+    only_hash: bool,
+    cid_version: Version,
+    cid_upgrade: bool,
+    inline_limit: Option<u32>,
+    chunker: Option<Chunker>,
+    hash: Option<HashFunction>,
+    mode: Option<u32>,
+    mtime: Option<i64>,
+    preserve_mode: bool,
+    preserve_mtime: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    nocopy: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    fscache: bool,
+    ipns_base: IpnsBase,
+    compat: Option<CompatMode>,
+    detected_compat: Rc<Cell<Option<CompatMode>>>,
+    /// Time of the last failed version probe.
+    compat_probe_failure: Rc<Cell<Option<Duration>>>,
+    #[cfg(feature = "testing")]
+    fixture: Option<FixtureMode>,
+    headers: Rc<HeaderMap>,
+}
+
+/// Credentials in the address and headers are redacted.
+impl fmt::Debug for IpfsService {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IpfsService")
+            .field("url", &redact::url(&self.base_url).as_str())
+            .field("headers", &self.headers)
+            .field("timeout", &self.timeout)
+            .field("retry", &self.retry)
+            .field("offline", &self.offline)
+            .field("verify", &self.verify)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Default for IpfsService {
     fn default() -> Self {
         let base_url = Url::parse(DEFAULT_URI).expect("Pasrsing URI");
-        let base_url = Rc::from(base_url);
 
-        let client = Client::new();
-
-        Self { client, base_url }
+        Self::new(base_url)
     }
 }
 
@@ -50,32 +273,870 @@ impl IpfsService {
 
         let client = Client::new();
 
-        Self { client, base_url }
+        Self {
+            client,
+            base_url,
+            timeout: None,
+            retry: None,
+            in_flight: Rc::default(),
+            subscriptions: Rc::default(),
+            verify: false,
+            cache: None,
+            keys: None,
+            coalescer: Rc::default(),
+            limiter: None,
+            max_response_size: None,
+            metrics: None,
+            offline: false,
+            only_hash: false,
+            cid_version: Version::V1,
+            cid_upgrade: false,
+            inline_limit: None,
+            chunker: None,
+            hash: None,
+            mode: None,
+            mtime: None,
+            preserve_mode: false,
+            preserve_mtime: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            nocopy: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            fscache: false,
+            ipns_base: IpnsBase::default(),
+            compat: None,
+            detected_compat: Rc::default(),
+            compat_probe_failure: Rc::default(),
+            #[cfg(feature = "testing")]
+            fixture: None,
+            headers: Rc::default(),
+        }
+    }
+
+    /// Address of the node API.
+    pub fn url(&self) -> &Url {
+        &self.base_url
+    }
+
+    /// Return a service sending requests under this path of the node, e.g. `/ipfs-api/v0/` behind a proxy.
+    ///
+    /// The path replaces the one of the service address, `DEFAULT_API_PATH` by default.
+    pub fn with_api_path(&self, path: &str) -> Self {
+        let path = path.trim_matches('/');
+
+        let mut url = (*self.base_url).clone();
+
+        match path.is_empty() {
+            true => url.set_path("/"),
+            false => url.set_path(&format!("/{}/", path)),
+        }
+
+        Self {
+            base_url: Rc::new(url),
+            ..self.clone()
+        }
+    }
+
+    /// Return a service whose requests fail if not completed within timeout.
+    ///
+    /// Pubsub subscriptions are long-lived and are not affected.
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self.clone()
+        }
+    }
+
+    /// Return a service retrying idempotent requests that fail transiently.
+    pub fn with_retry(&self, policy: RetryPolicy) -> Self {
+        Self {
+            retry: Some(Rc::new(policy)),
+            ..self.clone()
+        }
+    }
+
+    /// Return a service hashing the block data it receives and failing if it does not match the CID.
+    ///
+    /// `cat` can then only be used on raw blocks, the data of other CIDs being reassembled by the node.
+    pub fn with_verification(&self, verify: bool) -> Self {
+        Self {
+            verify,
+            ..self.clone()
+        }
+    }
+
+    /// Return a service keeping `cat` and `dag_get` responses in memory, shared with its clones.
+    pub fn with_cache(&self, config: CacheConfig) -> Self {
+        Self {
+            cache: Some(Rc::new(RefCell::new(Cache::new(config)))),
+            ..self.clone()
+        }
+    }
+
+    /// Return a service keeping the `key_list` response in memory until `invalidate_keys`, shared with its clones.
+    pub fn with_key_cache(&self) -> Self {
+        Self {
+            keys: Some(Rc::default()),
+            ..self.clone()
+        }
+    }
+
+    /// Forget the cached key list, e.g. after creating or removing keys with another client.
+    pub fn invalidate_keys(&self) {
+        if let Some(keys) = &self.keys {
+            keys.borrow_mut().take();
+        }
+    }
+
+    /// Return a service sending at most `max` requests at once, shared with its clones.
+    ///
+    /// Other requests wait for a slot, the wait counts toward the timeout.
+    /// Pubsub subscriptions are long-lived and are not counted.
+    pub fn with_max_concurrent_requests(&self, max: usize) -> Self {
+        Self {
+            limiter: Some(Rc::new(Limiter::new(max))),
+            ..self.clone()
+        }
+    }
+
+    /// Return a service failing requests whose response is larger than `max` bytes.
+    ///
+    /// The body is read until it exceeds the limit, instead of buffering it whole.
+    /// Streamed responses, e.g. `cat_stream`, are not buffered and not limited.
+    pub fn with_max_response_size(&self, max: usize) -> Self {
+        Self {
+            max_response_size: Some(max),
+            ..self.clone()
+        }
+    }
+
+    /// Return a service asking the node to only use local data.
+    ///
+    /// Applies to `cat`, `block_get`, `dag_get`, `pin_add` and `name_resolve`,
+    /// which then fail instead of fetching from the network.
+    pub fn with_offline(&self, offline: bool) -> Self {
+        Self {
+            offline,
+            ..self.clone()
+        }
+    }
+
+    /// Return a service computing the CID of added data without storing it.
+    ///
+    /// The node chunks the data with its own settings, the CID is the one an add would return.
+    pub fn with_only_hash(&self, only_hash: bool) -> Self {
+        Self {
+            only_hash,
+            ..self.clone()
+        }
+    }
+
+    /// Return a service adding data as CIDv0, for legacy content and tools, or CIDv1 by default.
+    ///
+    /// CIDv0 are dag-pb, small files are then not stored as raw blocks.
+    pub fn with_cid_version(&self, version: Version) -> Self {
+        Self {
+            cid_version: version,
+            ..self.clone()
+        }
+    }
+
+    /// Return a service converting the CIDv0 of `add` outputs and pin lists to CIDv1.
+    ///
+    /// Pins of legacy content are listed as CIDv0, this lets them be looked up by CIDv1.
+    pub fn with_cid_upgrade(&self, upgrade: bool) -> Self {
+        Self {
+            cid_upgrade: upgrade,
+            ..self.clone()
+        }
+    }
+
+    /// Return a service embedding added data of at most `limit` bytes in identity CIDs.
+    ///
+    /// Such CIDs are read without any block lookup. `None` stores all data in blocks.
+    pub fn with_inline(&self, limit: Option<u32>) -> Self {
+        Self {
+            inline_limit: limit,
+            ..self.clone()
+        }
+    }
+
+    /// Return a service splitting added data with this chunker. `None` uses the node default.
+    ///
+    /// Adding fails if the chunker sizes are invalid, see `Chunker::validate`.
+    pub fn with_chunker(&self, chunker: Option<Chunker>) -> Self {
+        Self {
+            chunker,
+            ..self.clone()
+        }
+    }
+
+    /// Return a service hashing the data it adds and puts with this function. `None` uses the node default, sha2-256.
+    ///
+    /// Applies to `add`, `block_put` and `dag_put`.
+    pub fn with_hash(&self, hash: Option<HashFunction>) -> Self {
+        Self {
+            hash,
+            ..self.clone()
+        }
+    }
+
+    /// Return a service storing this POSIX mode, e.g. `0o644`, in the UnixFS entries it adds or creates in MFS.
+    pub fn with_mode(&self, mode: Option<u32>) -> Self {
+        Self {
+            mode,
+            ..self.clone()
+        }
+    }
+
+    /// Return a service storing this modification time, in seconds since the Unix epoch, in the UnixFS entries it adds or creates in MFS.
+    pub fn with_mtime(&self, mtime: Option<i64>) -> Self {
+        Self {
+            mtime,
+            ..self.clone()
+        }
+    }
+
+    /// Return a service storing the mode of the files added with `add_files_with_metadata`.
+    pub fn with_preserve_mode(&self, preserve_mode: bool) -> Self {
+        Self {
+            preserve_mode,
+            ..self.clone()
+        }
+    }
+
+    /// Return a service storing the modification time of the files added with `add_files_with_metadata`.
+    pub fn with_preserve_mtime(&self, preserve_mtime: bool) -> Self {
+        Self {
+            preserve_mtime,
+            ..self.clone()
+        }
+    }
+
+    /// Return a service asking the node for IPNS names in this base.
+    pub fn with_ipns_base(&self, base: IpnsBase) -> Self {
+        Self {
+            ipns_base: base,
+            ..self.clone()
+        }
+    }
+
+    /// Return a service recording metrics of its requests, shared with its clones.
+    pub fn with_metrics(&self) -> Self {
+        Self {
+            metrics: Some(Rc::default()),
+            ..self.clone()
+        }
+    }
+
+    /// Request metrics per endpoint, empty unless enabled with `with_metrics`.
+    ///
+    /// Pubsub subscriptions are not recorded.
+    pub fn metrics(&self) -> MetricsSnapshot {
+        match &self.metrics {
+            Some(metrics) => metrics.snapshot(),
+            None => MetricsSnapshot::default(),
+        }
+    }
+
+    /// Return a service connecting to the node with these settings.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_connection(&self, config: ConnectionConfig) -> Result<Self> {
+        Ok(Self {
+            client: config.client()?,
+            ..self.clone()
+        })
+    }
+
+    /// Return a service sending this header with every request, replacing any previous value.
+    pub fn with_header(&self, name: &str, value: &str) -> Result<Self> {
+        let name = HeaderName::from_bytes(name.as_bytes())?;
+        let mut value = HeaderValue::from_str(value)?;
+
+        // Hidden from debug output
+        value.set_sensitive(redact::is_sensitive(&name));
+
+        let mut headers = (*self.headers).clone();
+        headers.insert(name, value);
+
+        Ok(Self {
+            headers: Rc::new(headers),
+            ..self.clone()
+        })
+    }
+
+    /// Return a service identifying itself with this user agent, browsers may ignore it.
+    pub fn with_user_agent(&self, agent: &str) -> Result<Self> {
+        self.with_header(USER_AGENT.as_str(), agent)
+    }
+
+    /// Return a service recording the responses of its requests, shared with its clones.
+    ///
+    /// Pubsub subscriptions are not recorded.
+    #[cfg(feature = "testing")]
+    pub fn with_recording(&self) -> Self {
+        Self {
+            fixture: Some(FixtureMode::Record(Rc::default())),
+            ..self.clone()
+        }
+    }
+
+    /// Responses recorded so far, empty unless enabled with `with_recording`.
+    #[cfg(feature = "testing")]
+    pub fn recording(&self) -> Fixtures {
+        match &self.fixture {
+            Some(FixtureMode::Record(fixtures)) => fixtures.borrow().clone(),
+            _ => Fixtures::default(),
+        }
+    }
+
+    /// Return a service answering requests with these responses instead of asking the node.
+    ///
+    /// Requests without a recorded response fail.
+    #[cfg(feature = "testing")]
+    pub fn with_replay(&self, fixtures: Fixtures) -> Self {
+        Self {
+            fixture: Some(FixtureMode::Replay(Rc::new(fixtures))),
+            ..self.clone()
+        }
+    }
+
+    /// Return a service sharing this connection whose requests are aborted independently.
+    pub fn scoped(&self) -> Self {
+        Self {
+            in_flight: Rc::default(),
+            subscriptions: Rc::default(),
+            coalescer: Rc::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Abort every in-flight request made through this service or its clones.
+    ///
+    /// Pubsub subscriptions are cancelled with their own handle or by `shutdown`.
+    pub fn abort_all(&self) {
+        self.in_flight.abort_all();
+    }
+
+    /// Abort every in-flight request, stream and subscription made through this service or its clones.
+    ///
+    /// Requests made afterwards fail as aborted and subscriptions end at once.
+    pub fn shutdown(&self) {
+        self.in_flight.close();
+        self.subscriptions.close();
+    }
+
+    /// POST to an endpoint that may fetch data from the network, unless the service is offline.
+    fn post_fetch(&self, url: Url) -> RequestBuilder {
+        let request = self.client.post(url);
+
+        if self.offline {
+            request.query(&[("offline", "true")])
+        } else {
+            request
+        }
+    }
+
+    /// Send the request and buffer the response body.
+    ///
+    /// Retried as `send_idempotent` if its endpoint is idempotent, see `is_idempotent`.
+    async fn send(&self, request: RequestBuilder) -> Result<Bytes> {
+        let idempotent = request
+            .try_clone()
+            .and_then(|request| request.build().ok())
+            .is_some_and(|request| is_idempotent(endpoint(&self.base_url, request.url())));
+
+        let (_, bytes) = self.execute_retrying(request, idempotent).await?;
+
+        Ok(bytes)
+    }
+
+    /// Send the request, retrying according to the service retry policy.
+    ///
+    /// Only use for requests that can safely be sent more than once.
+    async fn send_idempotent(&self, request: RequestBuilder) -> Result<Bytes> {
+        let (_, bytes) = self.execute_idempotent(request).await?;
+
+        Ok(bytes)
+    }
+
+    /// Like `send_idempotent`, returning the HTTP status code of the last attempt.
+    async fn execute_idempotent(&self, request: RequestBuilder) -> Result<(u16, Bytes)> {
+        self.execute_retrying(request, true).await
+    }
+
+    /// Send the request, retrying according to the service retry policy.
+    ///
+    /// Requests not `idempotent` are only retried if unsent or allowed by the policy.
+    async fn execute_retrying(
+        &self,
+        mut request: RequestBuilder,
+        idempotent: bool,
+    ) -> Result<(u16, Bytes)> {
+        let policy = match &self.retry {
+            Some(policy) => policy.clone(),
+            None => return self.execute(request).await,
+        };
+
+        let mut attempt = 1;
+
+        loop {
+            let next = match request.try_clone() {
+                Some(next) if attempt < policy.max_attempts => next,
+                _ => return self.execute(request).await,
+            };
+
+            let retry_sent = idempotent || policy.retry_non_idempotent;
+
+            let delay = match self.execute(request).await {
+                Ok(res) => return Ok(res),
+                Err(e) if !policy.is_retryable_error(e.as_ref()) => return Err(e),
+                Err(e) if !retry_sent && !retry::is_unsent(e.as_ref()) => return Err(e),
+                Err(e) => policy.delay_after(attempt, e.as_ref()),
+            };
+
+            futures_timer::Delay::new(delay).await;
+
+            request = next;
+            attempt += 1;
+        }
+    }
+
+    /// Send an idempotent read, sharing the response with identical reads already in flight.
+    ///
+    /// Only reads of clones with the same offline mode, verification, size limit and timeout are shared.
+    /// Callers joining a read in flight are subject to the retries of the first one.
+    async fn execute_coalesced(
+        &self,
+        key: CacheKey,
+        request: RequestBuilder,
+    ) -> Result<(u16, Bytes)> {
+        let key = CoalesceKey {
+            read: key,
+            offline: self.offline,
+            verify: self.verify,
+            max_response_size: self.max_response_size,
+            timeout: self.timeout,
+        };
+
+        let ipfs = self.clone();
+
+        self.coalescer
+            .run(key, async move { ipfs.execute_idempotent(request).await })
+            .await
+    }
+
+    /// Cached response of an immutable path, verified by the caller.
+    ///
+    /// Responses over the size limit of this service are misses, for the request to fail as uncached.
+    fn cache_get(&self, key: &CacheKey) -> Option<Bytes> {
+        key.path.cid()?;
+
+        let bytes = self.cache.as_ref()?.borrow_mut().get(key)?;
+
+        match self.max_response_size {
+            Some(max) if bytes.len() > max => None,
+            _ => Some(bytes),
+        }
+    }
+
+    /// Cache the response if successful and its path immutable.
+    fn cache_put(&self, key: CacheKey, status: u16, bytes: Bytes) {
+        if let Some(cache) = &self.cache {
+            if (200..300).contains(&status) && key.path.cid().is_some() {
+                cache.borrow_mut().insert(key, bytes);
+            }
+        }
+    }
+
+    /// Send the request and buffer the response body, returning the HTTP status code.
+    ///
+    /// Failures and error statuses are returned as `Error::Request`, 429 and 503 as `Error::RateLimited`.
+    async fn execute(&self, request: RequestBuilder) -> Result<(u16, Bytes)> {
+        let request = self.with_default_headers(request).build()?;
+
+        let start = clock::now();
+
+        let endpoint = endpoint(&self.base_url, request.url()).to_owned();
+
+        #[cfg(feature = "tracing")]
+        let span = trace::request_span(&self.base_url, &request);
+
+        #[cfg(feature = "testing")]
+        let fixture_key = self
+            .fixture
+            .as_ref()
+            .map(|_| fixture::key(&self.base_url, &request));
+
+        #[cfg(feature = "testing")]
+        let result = self.dispatch(request, fixture_key.as_deref());
+
+        #[cfg(not(feature = "testing"))]
+        let result = self.dispatch(request);
+
+        #[cfg(feature = "tracing")]
+        let result = tracing::Instrument::instrument(result, span.clone());
+
+        let (result, retry_after) = match result.await {
+            Ok((status, bytes, retry_after)) => (Ok((status, bytes)), retry_after),
+            Err(e) => (Err(redact::error(e)), None),
+        };
+
+        #[cfg(feature = "testing")]
+        if let (Some(fixture), Some(key)) = (&self.fixture, fixture_key) {
+            fixture.record(key, &result);
+        }
+
+        #[cfg(feature = "tracing")]
+        trace::record(&span, &result, start);
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record(&endpoint, &result, clock::now().saturating_sub(start));
+        }
+
+        let error = match result {
+            Ok((status, bytes)) if (200..300).contains(&status) => return Ok((status, bytes)),
+            Ok((status, bytes)) => {
+                Error::from_status(self.url(), endpoint, status, &bytes, retry_after)
+            }
+            Err(source) => Error::Request {
+                node: redact::url(self.url()).into_owned(),
+                endpoint,
+                status: None,
+                source,
+            },
+        };
+
+        Err(error.into())
+    }
+
+    fn with_default_headers(&self, request: RequestBuilder) -> RequestBuilder {
+        if self.headers.is_empty() {
+            return request;
+        }
+
+        request.headers((*self.headers).clone())
+    }
+
+    /// Send the request subject to the abort handles, concurrency limit, timeout and size limit.
+    ///
+    /// Returns the status, body and `Retry-After` delay of the response.
+    /// When replaying, the recorded response under the fixture key is returned instead.
+    async fn dispatch(
+        &self,
+        request: Request,
+        #[cfg(feature = "testing")] fixture_key: Option<&str>,
+    ) -> Result<(u16, Bytes, Option<Duration>)> {
+        #[cfg(feature = "testing")]
+        if let (Some(fixture), Some(key)) = (&self.fixture, fixture_key) {
+            if let Some(res) = fixture.replay(key) {
+                return match (res, self.max_response_size) {
+                    (Ok((_, bytes)), Some(max)) if bytes.len() > max => Err(too_large(max)),
+                    (res, _) => res.map(|(status, bytes)| (status, bytes, None)),
+                };
+            }
+        }
+
+        let (regis, _guard) = self.in_flight.register();
+
+        let request = async {
+            let _permit = match &self.limiter {
+                Some(limiter) => Some(limiter.acquire().await),
+                None => None,
+            };
+
+            let response = runtime::compat(self.client.execute(request)).await?;
+            let status = response.status().as_u16();
+            let retry_after = retry_after(&response);
+
+            let bytes = match self.max_response_size {
+                Some(max) => read_limited(response, max).await?,
+                None => runtime::compat(response.bytes()).await?,
+            };
+
+            Ok((status, bytes, retry_after))
+        };
+
+        let response = async {
+            match Abortable::new(request, regis).await {
+                Ok(result) => result,
+                Err(aborted) => Err(aborted.into()),
+            }
+        };
+
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return response.await,
+        };
+
+        futures_util::pin_mut!(response);
+
+        let delay = futures_timer::Delay::new(timeout);
+
+        match future::select(response, delay).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => {
+                Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "Request timed out").into())
+            }
+        }
     }
 
     #[cfg(target_arch = "wasm32")]
     pub async fn add(&self, bytes: Bytes) -> Result<Cid> {
-        let url = self.base_url.join("add")?;
+        self.add_part(Part::stream(bytes)).await
+    }
+
+    /// Add the data of the stream, sent while it is read if the browser can stream request bodies.
+    ///
+    /// Browsers only stream uploads over HTTP/2, hence HTTPS,
+    /// otherwise the stream is read to the end before sending.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn add_stream<S>(&self, stream: S) -> Result<Cid>
+    where
+        S: Stream<Item = Result<Bytes>> + 'static,
+    {
+        if self.base_url.scheme() == "https" && upload::supports_request_streams() {
+            let body = reqwest::Body::wrap_stream(stream);
 
-        let part = Part::stream(bytes);
+            return self.add_part(Part::stream(body)).await;
+        }
+
+        let bytes = upload::collect(stream).await?;
+
+        self.add(bytes).await
+    }
+
+    /// Add the data of a browser stream of `Uint8Array`, see `add_stream`.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn add_readable_stream(&self, stream: web_sys::ReadableStream) -> Result<Cid> {
+        self.add_stream(upload::readable_stream(stream)).await
+    }
+
+    /// Add the content of a file or blob, see `add_stream`.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn add_blob(&self, blob: &web_sys::Blob) -> Result<Cid> {
+        self.add_readable_stream(blob.stream()).await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn add<S>(&self, stream: S) -> Result<Cid>
+    where
+        S: futures_util::stream::TryStream + Send + Sync + 'static,
+        S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        Bytes: From<S::Ok>,
+    {
+        let body = reqwest::Body::wrap_stream(stream);
 
+        self.add_part(Part::stream(body)).await
+    }
+
+    /// Add files under a wrapping directory, named by their path in it, e.g. `images/cat.png`.
+    ///
+    /// Returns every file and directory created, the root being the wrapping directory.
+    pub async fn add_files<I, N>(&self, files: I) -> Result<AddOutput>
+    where
+        I: IntoIterator<Item = (N, Bytes)>,
+        N: Into<String>,
+    {
+        let files = files
+            .into_iter()
+            .map(|(name, bytes)| (name, bytes, FileMetadata::default()));
+
+        self.add_files_with_metadata(files).await
+    }
+
+    /// Add files with their mode and modification time, see `add_files`.
+    ///
+    /// The metadata is stored if enabled with `with_preserve_mode` and `with_preserve_mtime`.
+    pub async fn add_files_with_metadata<I, N>(&self, files: I) -> Result<AddOutput>
+    where
+        I: IntoIterator<Item = (N, Bytes, FileMetadata)>,
+        N: Into<String>,
+    {
+        let mut files: Vec<(String, Bytes, FileMetadata)> = files
+            .into_iter()
+            .map(|(name, bytes, metadata)| (name.into(), bytes, metadata))
+            .collect();
+
+        // Files of a directory must follow it
+        files.sort_by(|(a, ..), (b, ..)| a.cmp(b));
+
+        let mut entries = Vec::new();
+        let mut directories = HashSet::new();
+
+        for (name, bytes, metadata) in files {
+            for (i, _) in name.match_indices('/') {
+                let directory = &name[..i];
+
+                if directories.insert(directory.to_owned()) {
+                    entries.push((directory.to_owned(), Bytes::new(), None));
+                }
+            }
+
+            entries.push((name, bytes, Some(metadata)));
+        }
+
+        let (content_type, body) = multipart::files_body(&entries);
+
+        let request = self
+            .add_request(true)?
+            .header(CONTENT_TYPE, content_type)
+            .body(body);
+
+        self.send_add(request).await
+    }
+
+    /// Add the data of the reader, sent while it is read.
+    ///
+    /// Tokio readers can be adapted with `tokio_util::compat`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn add_reader<R>(&self, reader: R) -> Result<Cid>
+    where
+        R: futures_util::AsyncRead + Send + Sync + Unpin + 'static,
+    {
+        // The reader is dropped after an error
+        let stream = stream::unfold(Some(reader), |reader| async move {
+            let mut reader = reader?;
+            let mut buf = vec![0; READ_CHUNK_SIZE];
+
+            match futures_util::AsyncReadExt::read(&mut reader, &mut buf).await {
+                Ok(0) => None,
+                Ok(read) => {
+                    buf.truncate(read);
+
+                    Some((Ok(Bytes::from(buf)), Some(reader)))
+                }
+                Err(e) => Some((Err(e), None)),
+            }
+        });
+
+        self.add(stream).await
+    }
+
+    async fn add_part(&self, part: Part) -> Result<Cid> {
         let form = Form::new().part("path", part);
 
-        let bytes = self
+        let output = self.add_form(form, false).await?;
+
+        Ok(output.root)
+    }
+
+    async fn add_form(&self, form: Form, wrap: bool) -> Result<AddOutput> {
+        let request = self.add_request(wrap)?.multipart(form);
+
+        self.send_add(request).await
+    }
+
+    /// Request to add data with the settings of this service, without a body.
+    fn add_request(&self, wrap: bool) -> Result<RequestBuilder> {
+        let url = self.base_url.join("add")?;
+
+        let request = self
             .client
             .post(url)
             .query(&[("pin", "false")])
-            .query(&[("cid-version", "1")])
-            .multipart(form)
-            .send()
-            .await?
-            .bytes()
-            .await?;
+            .query(&[("cid-version", u64::from(self.cid_version))])
+            .query(&[("wrap-with-directory", &wrap.to_string())]);
+
+        let request = match self.only_hash {
+            true => request.query(&[("only-hash", "true")]),
+            false => request,
+        };
+
+        let request = match self.inline_limit {
+            Some(limit) => request
+                .query(&[("inline", "true")])
+                .query(&[("inline-limit", &limit.to_string())]),
+            None => request,
+        };
+
+        let request = match self.chunker {
+            Some(chunker) => {
+                chunker.validate()?;
+
+                request.query(&[("chunker", &chunker.to_string())])
+            }
+            None => request,
+        };
+
+        let request = match self.hash {
+            Some(hash) => request.query(&[("hash", hash.as_str())]),
+            None => request,
+        };
+
+        let request = match self.mode {
+            Some(mode) => request.query(&[("mode", &mode.to_string())]),
+            None => request,
+        };
+
+        let request = match self.mtime {
+            Some(mtime) => request.query(&[("mtime", &mtime.to_string())]),
+            None => request,
+        };
+
+        let request = match self.preserve_mode {
+            true => request.query(&[("preserve-mode", "true")]),
+            false => request,
+        };
+
+        let request = match self.preserve_mtime {
+            true => request.query(&[("preserve-mtime", "true")]),
+            false => request,
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let request = match self.nocopy {
+            true => request.query(&[("nocopy", "true")]),
+            false => request,
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let request = match self.fscache {
+            true => request.query(&[("fscache", "true")]),
+            false => request,
+        };
+
+        Ok(request)
+    }
+
+    async fn send_add(&self, request: RequestBuilder) -> Result<AddOutput> {
+        let bytes = self.send(request).await?;
 
         //println!("{}", std::str::from_utf8(&bytes).unwrap());
 
-        if let Ok(res) = serde_json::from_slice::<AddResponse>(&bytes) {
-            return Ok(res.try_into()?);
+        let mut output = add_output(&bytes)?;
+
+        if self.cid_upgrade {
+            for entry in output.entries.iter_mut() {
+                entry.cid = to_cid_v1(&entry.cid);
+            }
+
+            output.root = to_cid_v1(&output.root);
+        }
+
+        Ok(output)
+    }
+
+    /// Entries of the directory at this path, with their mode and modification time if stored.
+    pub async fn ls<P>(&self, path: P) -> Result<Vec<LsEntry>>
+    where
+        P: Into<IpfsPath>,
+    {
+        let path = path.into();
+
+        let url = self.base_url.join("ls")?;
+
+        let request = self.client.post(url).query(&[("arg", &path.to_string())]);
+
+        let bytes = self.send_idempotent(request).await?;
+
+        if let Ok(res) = serde_json::from_slice::<LsResponse>(&bytes) {
+            let mut entries = Vec::new();
+
+            for link in res.objects.into_iter().flat_map(|object| object.links) {
+                entries.push(link.try_into()?);
+            }
+
+            return Ok(entries);
         }
 
         let error = serde_json::from_slice::<IPFSError>(&bytes)?;
@@ -83,35 +1144,189 @@ impl IpfsService {
         Err(error.into())
     }
 
+    /// Download the content at this path.
+    pub async fn cat<P>(&self, path: P) -> Result<Bytes>
+    where
+        P: Into<IpfsPath>,
+    {
+        let path = path.into();
+
+        let url = self.base_url.join("cat")?;
+
+        // Only the data of a single raw block hashes to its CID
+        let verified = match (self.verify, path.cid()) {
+            (false, _) => None,
+            (true, Some(cid)) if path.segments().is_empty() && cid.codec() == RAW_CODEC => {
+                Some(cid)
+            }
+            (true, _) => return Err("Only raw blocks can be verified, use block_get".into()),
+        };
+
+        let key = CacheKey {
+            path: path.clone(),
+            format: "cat",
+        };
+
+        if let Some(bytes) = self.cache_get(&key) {
+            if let Some(cid) = verified {
+                verify::verify(&cid, &bytes)?;
+            }
+
+            return Ok(bytes);
+        }
+
+        let request = self.post_fetch(url).query(&[("arg", &path.to_string())]);
+
+        let (status, bytes) = self.execute_coalesced(key.clone(), request).await?;
+
+        if let Some(cid) = verified {
+            verify::verify(&cid, &bytes)?;
+        }
+
+        self.cache_put(key, status, bytes.clone());
+
+        Ok(bytes)
+    }
+
+    /// Download the content at this path as it arrives, without buffering it.
+    ///
+    /// The stream is aborted by `abort_all` and `shutdown`,
+    /// it is not cached, verified, recorded nor subject to the timeout and concurrency limit.
+    /// When replaying, the recorded content is returned instead.
+    pub async fn cat_stream<P>(&self, path: P) -> Result<impl Stream<Item = Result<Bytes>>>
+    where
+        P: Into<IpfsPath>,
+    {
+        let path = path.into();
+
+        if self.verify {
+            return Err("Streamed content cannot be verified, use cat".into());
+        }
+
+        let url = self.base_url.join("cat")?;
+
+        let request = self.post_fetch(url).query(&[("arg", &path.to_string())]);
+
+        let request = self.with_default_headers(request).build()?;
+
+        let body = self.send_body_stream(request).await?;
+
+        Ok(body.err_into())
+    }
+
+    /// Write the content at this path to the writer as it arrives, see `cat_stream`.
+    ///
+    /// Returns the number of bytes written. The writer is flushed but not closed.
+    pub async fn cat_to_writer<P, W>(&self, path: P, writer: &mut W) -> Result<u64>
+    where
+        P: Into<IpfsPath>,
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let stream = self.cat_stream(path).await?;
+
+        futures_util::pin_mut!(stream);
+
+        let mut written = 0;
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+
+            writer.write_all(&chunk).await?;
+
+            written += chunk.len() as u64;
+        }
+
+        writer.flush().await?;
+
+        Ok(written)
+    }
+
+    /// Write the content at this path to a new file, replacing any existing one, see `cat_to_writer`.
     #[cfg(not(target_arch = "wasm32"))]
-    pub async fn add<S>(&self, stream: S) -> Result<Cid>
+    pub async fn cat_to_file<P, F>(&self, path: P, file: F) -> Result<u64>
     where
-        S: futures_util::stream::TryStream + Send + Sync + 'static,
-        S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
-        Bytes: From<S::Ok>,
+        P: Into<IpfsPath>,
+        F: AsRef<std::path::Path>,
     {
-        let url = self.base_url.join("add")?;
+        let file = std::fs::File::create(file)?;
+
+        let mut writer = futures_util::io::AllowStdIo::new(file);
+
+        self.cat_to_writer(path, &mut writer).await
+    }
+
+    /// Download the content at this path as a browser stream of `Uint8Array`, see `cat_stream`.
+    ///
+    /// Suited to `MediaSource` buffers or streamed downloads.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn cat_readable_stream<P>(&self, path: P) -> Result<web_sys::ReadableStream>
+    where
+        P: Into<IpfsPath>,
+    {
+        let stream = self.cat_stream(path).await?;
+
+        Ok(upload::into_readable_stream(stream))
+    }
+
+    /// Size of the block with this CID, the node fetches it if missing.
+    pub async fn block_stat(&self, cid: Cid) -> Result<BlockStat> {
+        let url = self.base_url.join("block/stat")?;
+
+        let request = self.post_fetch(url).query(&[("arg", &cid.to_string())]);
+
+        let bytes = self.send_idempotent(request).await?;
+
+        if let Ok(res) = serde_json::from_slice::<BlockStat>(&bytes) {
+            return Ok(res);
+        }
+
+        let error = serde_json::from_slice::<IPFSError>(&bytes)?;
+
+        Err(error.into())
+    }
+
+    /// Download the raw data of the block with this CID.
+    pub async fn block_get(&self, cid: Cid) -> Result<Bytes> {
+        let url = self.base_url.join("block/get")?;
+
+        let request = self.post_fetch(url).query(&[("arg", &cid.to_string())]);
+
+        let bytes = self.send_idempotent(request).await?;
+
+        if self.verify {
+            verify::verify(&cid, &bytes)?;
+        }
+
+        Ok(bytes)
+    }
 
-        let body = reqwest::Body::wrap_stream(stream);
-        let part = Part::stream(body);
+    /// Store the data as a block of this codec, e.g. `raw` or `dag-cbor`. Return a CID.
+    pub async fn block_put(&self, data: Bytes, codec: &str) -> Result<Cid> {
+        let form = Form::new().part("data", Part::stream(data.clone()));
 
-        let form = Form::new().part("path", part);
+        let url = self.base_url.join("block/put")?;
 
-        let bytes = self
+        let request = self
             .client
             .post(url)
-            .query(&[("pin", "false")])
-            .query(&[("cid-version", "1")])
-            .multipart(form)
-            .send()
-            .await?
-            .bytes()
-            .await?;
+            .query(&[("cid-codec", codec)])
+            .query(&[("pin", "false")]);
 
-        //println!("{}", std::str::from_utf8(&bytes).unwrap());
+        let request = match self.hash {
+            Some(hash) => request.query(&[("mhtype", hash.as_str())]),
+            None => request,
+        };
 
-        if let Ok(res) = serde_json::from_slice::<AddResponse>(&bytes) {
-            return Ok(res.try_into()?);
+        let bytes = self.send(request.multipart(form)).await?;
+
+        if let Ok(res) = serde_json::from_slice::<BlockStat>(&bytes) {
+            let cid = Cid::try_from(res.key)?;
+
+            if self.verify {
+                verify::verify(&cid, &data)?;
+            }
+
+            return Ok(cid);
         }
 
         let error = serde_json::from_slice::<IPFSError>(&bytes)?;
@@ -119,44 +1334,87 @@ impl IpfsService {
         Err(error.into())
     }
 
-    /// Download content from block with this CID.
-    pub async fn cat<U>(&self, cid: Cid, path: Option<U>) -> Result<Bytes>
-    where
-        U: Into<Cow<'static, str>>,
-    {
-        let url = self.base_url.join("cat")?;
+    /// CIDs of the blocks linked from this CID, each listed once.
+    ///
+    /// Links are followed at most `max_depth` levels deep, `None` to follow all of them.
+    pub async fn refs(&self, cid: Cid, max_depth: Option<usize>) -> Result<Vec<Cid>> {
+        let url = self.base_url.join("refs")?;
 
-        let mut origin = cid.to_string();
+        let max_depth = match max_depth {
+            Some(depth) => depth.to_string(),
+            None => "-1".to_owned(),
+        };
 
-        if let Some(path) = path {
-            origin.push_str(&path.into());
-        }
+        let request = self
+            .post_fetch(url)
+            .query(&[("arg", &cid.to_string())])
+            .query(&[("recursive", "true"), ("unique", "true")])
+            .query(&[("max-depth", &max_depth)]);
 
-        let bytes = self
-            .client
-            .post(url)
-            .query(&[("arg", &origin)])
-            .send()
-            .await?
-            .bytes()
-            .await?;
+        let bytes = self.send_idempotent(request).await?;
 
-        Ok(bytes)
+        let mut cids = Vec::new();
+
+        for line in bytes.split(|byte| *byte == b'\n') {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+
+            if let Ok(res) = serde_json::from_slice::<RefsResponse>(line) {
+                if !res.error.is_empty() {
+                    return Err(res.error.into());
+                }
+
+                cids.push(Cid::try_from(res.cid)?);
+
+                continue;
+            }
+
+            let error = serde_json::from_slice::<IPFSError>(line)?;
+
+            return Err(error.into());
+        }
+
+        Ok(cids)
     }
 
     /// Pin a CID recursively or not.
     pub async fn pin_add(&self, cid: Cid, recursive: bool) -> Result<PinAddResponse> {
+        self.pin_add_request(cid, recursive, None).await
+    }
+
+    /// Pin a CID under a name, to find it with `pin_ls_with` later.
+    pub async fn pin_add_named<U>(
+        &self,
+        cid: Cid,
+        recursive: bool,
+        name: U,
+    ) -> Result<PinAddResponse>
+    where
+        U: Into<Cow<'static, str>>,
+    {
+        self.pin_add_request(cid, recursive, Some(name.into()))
+            .await
+    }
+
+    async fn pin_add_request(
+        &self,
+        cid: Cid,
+        recursive: bool,
+        name: Option<Cow<'static, str>>,
+    ) -> Result<PinAddResponse> {
         let url = self.base_url.join("pin/add")?;
 
-        let bytes = self
-            .client
-            .post(url)
+        let mut request = self
+            .post_fetch(url)
             .query(&[("arg", &cid.to_string())])
-            .query(&[("recursive", &recursive.to_string())])
-            .send()
-            .await?
-            .bytes()
-            .await?;
+            .query(&[("recursive", &recursive.to_string())]);
+
+        if let Some(name) = name {
+            request = request.query(&[("name", &name)]);
+        }
+
+        let bytes = self.send(request).await?;
 
         //println!("{}", std::str::from_utf8(&bytes).unwrap());
 
@@ -173,15 +1431,13 @@ impl IpfsService {
     pub async fn pin_rm(&self, cid: Cid, recursive: bool) -> Result<PinRmResponse> {
         let url = self.base_url.join("pin/rm")?;
 
-        let bytes = self
+        let request = self
             .client
             .post(url)
             .query(&[("arg", &cid.to_string())])
-            .query(&[("recursive", &recursive.to_string())])
-            .send()
-            .await?
-            .bytes()
-            .await?;
+            .query(&[("recursive", &recursive.to_string())]);
+
+        let bytes = self.send(request).await?;
 
         //println!("pin_rm Raw => {}", std::str::from_utf8(&bytes).unwrap());
 
@@ -194,7 +1450,75 @@ impl IpfsService {
         Err(error.into())
     }
 
+    /// List pinned CIDs of this type, or of every type.
+    pub async fn pin_ls(&self, pin_type: Option<PinType>) -> Result<PinList> {
+        let url = self.base_url.join("pin/ls")?;
+
+        let pin_type = pin_type.map_or("all", |pin_type| pin_type.as_str());
+
+        let request = self.client.post(url).query(&[("type", pin_type)]);
+
+        let bytes = self.send_idempotent(request).await?;
+
+        //println!("{}", std::str::from_utf8(&bytes).unwrap());
+
+        if let Ok(res) = serde_json::from_slice::<PinLsResponse>(&bytes) {
+            let pins: PinList = res.try_into()?;
+
+            return Ok(self.upgrade_keys(pins));
+        }
+
+        let error = serde_json::from_slice::<IPFSError>(&bytes)?;
+
+        Err(error.into())
+    }
+
+    /// List the pins matching the filter, with their names.
+    pub async fn pin_ls_with(&self, filter: &PinFilter) -> Result<NamedPinList> {
+        let url = self.base_url.join("pin/ls")?;
+
+        let pin_type = filter.pin_type.map_or("all", |pin_type| pin_type.as_str());
+
+        let mut request = self
+            .client
+            .post(url)
+            .query(&[("type", pin_type), ("names", "true")]);
+
+        for cid in filter.cids.iter() {
+            request = request.query(&[("arg", &cid.to_string())]);
+        }
+
+        if let Some(name) = &filter.name {
+            request = request.query(&[("name", name)]);
+        }
+
+        let bytes = self.send_idempotent(request).await?;
+
+        if let Ok(res) = serde_json::from_slice::<PinLsResponse>(&bytes) {
+            let pins: NamedPinList = res.try_into()?;
+
+            return Ok(self.upgrade_keys(pins));
+        }
+
+        let error = serde_json::from_slice::<IPFSError>(&bytes)?;
+
+        Err(error.into())
+    }
+
+    /// Convert the CIDv0 keys to CIDv1 if enabled with `with_cid_upgrade`.
+    fn upgrade_keys<V>(&self, map: HashMap<Cid, V>) -> HashMap<Cid, V> {
+        match self.cid_upgrade {
+            true => map
+                .into_iter()
+                .map(|(cid, value)| (to_cid_v1(&cid), value))
+                .collect(),
+            false => map,
+        }
+    }
+
     /// Serialize then add dag node to IPFS. Return a CID.
+    ///
+    /// Byte fields must use `dag_json::bytes` to be stored as bytes rather than lists of numbers.
     pub async fn dag_put<T>(&self, node: &T) -> Result<Cid>
     where
         T: ?Sized + Serialize,
@@ -205,17 +1529,21 @@ impl IpfsService {
 
         let url = self.base_url.join("dag/put")?;
 
-        let bytes = self
+        let request = self
             .client
             .post(url)
             .query(&[("store-codec", "dag-cbor")])
             .query(&[("input-codec", "dag-json")])
-            .query(&[("pin", "false")])
-            .multipart(form)
-            .send()
-            .await?
-            .bytes()
-            .await?;
+            .query(&[("pin", "false")]);
+
+        let request = match self.hash {
+            Some(hash) => request.query(&[("hash", hash.as_str())]),
+            None => request,
+        };
+
+        let request = request.multipart(form);
+
+        let bytes = self.send(request).await?;
 
         //println!("{}", std::str::from_utf8(&bytes).unwrap());
 
@@ -228,33 +1556,37 @@ impl IpfsService {
         Err(error.into())
     }
 
-    /// Deserialize dag node from IPFS path. Return dag node.
-    pub async fn dag_get<U, T>(&self, cid: Cid, path: Option<U>) -> Result<T>
+    /// Deserialize dag node at this path. Return dag node.
+    pub async fn dag_get<P, T>(&self, path: P) -> Result<T>
     where
-        U: Into<Cow<'static, str>>,
+        P: Into<IpfsPath>,
         T: ?Sized + DeserializeOwned,
     {
-        let mut origin = cid.to_string();
+        let path = path.into();
 
-        if let Some(path) = path {
-            origin.push_str(&path.into());
+        let key = CacheKey {
+            path: path.clone(),
+            format: "dag-json",
+        };
+
+        if let Some(bytes) = self.cache_get(&key) {
+            return Ok(serde_json::from_slice::<T>(&bytes)?);
         }
 
         let url = self.base_url.join("dag/get")?;
 
-        let bytes = self
-            .client
-            .post(url)
-            .query(&[("arg", &origin)])
-            .query(&[("output-codec", "dag-json")])
-            .send()
-            .await?
-            .bytes()
-            .await?;
+        let request = self
+            .post_fetch(url)
+            .query(&[("arg", &path.to_string())])
+            .query(&[("output-codec", "dag-json")]);
+
+        let (status, bytes) = self.execute_coalesced(key.clone(), request).await?;
 
         //println!("{}", std::str::from_utf8(&bytes).unwrap());
 
         if let Ok(res) = serde_json::from_slice::<T>(&bytes) {
+            self.cache_put(key, status, bytes);
+
             return Ok(res);
         }
 
@@ -263,18 +1595,102 @@ impl IpfsService {
         Err(error.into())
     }
 
+    /// Deserialize the value at this path relative to the dag node, e.g. `link/field/3`.
+    ///
+    /// Fails with `Error::PathNotFound` if the node has nothing at this path.
+    pub async fn dag_get_path<T>(&self, cid: Cid, path: &str) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let path = IpfsPath::from(cid).join(path)?;
+
+        match self.dag_get(path.clone()).await {
+            Ok(value) => Ok(value),
+            Err(error) => match error.downcast_ref::<Error>() {
+                Some(e) if e.is_missing_link() => Err(Error::PathNotFound {
+                    node: redact::url(self.url()).into_owned(),
+                    path,
+                }
+                .into()),
+                _ => Err(error),
+            },
+        }
+    }
+
+    /// Download the block at this path as it is encoded, for nodes dag-json cannot represent.
+    pub async fn dag_get_raw<P>(&self, path: P) -> Result<Bytes>
+    where
+        P: Into<IpfsPath>,
+    {
+        let path = path.into();
+
+        // Only whole blocks hash to their CID
+        let verified = match (self.verify, path.cid()) {
+            (false, _) => None,
+            (true, Some(cid)) if path.segments().is_empty() => Some(cid),
+            (true, _) => return Err("Only whole blocks can be verified, use block_get".into()),
+        };
+
+        let key = CacheKey {
+            path: path.clone(),
+            format: "raw",
+        };
+
+        if let Some(bytes) = self.cache_get(&key) {
+            if let Some(cid) = verified {
+                verify::verify(&cid, &bytes)?;
+            }
+
+            return Ok(bytes);
+        }
+
+        let url = self.base_url.join("dag/get")?;
+
+        let request = self
+            .post_fetch(url)
+            .query(&[("arg", &path.to_string())])
+            .query(&[("output-codec", "raw")]);
+
+        let (status, bytes) = self.execute_coalesced(key.clone(), request).await?;
+
+        if let Some(cid) = verified {
+            verify::verify(&cid, &bytes)?;
+        }
+
+        self.cache_put(key, status, bytes.clone());
+
+        Ok(bytes)
+    }
+
     /// Returns all IPNS keys on this IPFS node.
-    pub async fn key_list(&self) -> Result<KeyList> {
+    ///
+    /// Served from memory once listed if enabled with `with_key_cache`.
+    pub async fn key_list(&self) -> Result<Vec<KeyInfo>> {
+        let cache = match &self.keys {
+            Some(cache) => cache,
+            None => return self.fetch_key_list().await,
+        };
+
+        if let Some(keys) = cache.borrow().as_ref() {
+            return Ok(keys.clone());
+        }
+
+        let keys = self.fetch_key_list().await?;
+
+        cache.borrow_mut().replace(keys.clone());
+
+        Ok(keys)
+    }
+
+    async fn fetch_key_list(&self) -> Result<Vec<KeyInfo>> {
         let url = self.base_url.join("key/list")?;
 
-        let bytes = self
+        let request = self
             .client
             .post(url)
-            .query(&[("l", "true"), ("ipns-base", "base32")])
-            .send()
-            .await?
-            .bytes()
-            .await?;
+            .query(&[("l", "true"), ("ipns-base", self.ipns_base.as_str())]);
+
+        let bytes = self.send_idempotent(request).await?;
 
         //println!("{}", std::str::from_utf8(&bytes).unwrap());
 
@@ -287,24 +1703,112 @@ impl IpfsService {
         Err(error.into())
     }
 
+    /// Returns the CID of each IPNS key by name.
+    pub async fn key_map(&self) -> Result<KeyList> {
+        Ok(self.key_list().await?.into_iter().collect())
+    }
+
+    /// Sign the data with this IPNS key, e.g. to prove control of its name.
+    pub async fn key_sign<U>(&self, key: U, data: Bytes) -> Result<KeySignature>
+    where
+        U: Into<Cow<'static, str>>,
+    {
+        let url = self.base_url.join("key/sign")?;
+
+        let form = Form::new().part("data", Part::stream(data));
+
+        let request = self
+            .client
+            .post(url)
+            .query(&[("key", &key.into())])
+            .query(&[("ipns-base", self.ipns_base.as_str())])
+            .multipart(form);
+
+        let bytes = self.send_idempotent(request).await?;
+
+        if let Ok(res) = serde_json::from_slice::<KeySignResponse>(&bytes) {
+            return Ok(res.try_into()?);
+        }
+
+        let error = serde_json::from_slice::<IPFSError>(&bytes)?;
+
+        Err(error.into())
+    }
+
+    /// Check a signature of the data made by `key_sign`.
+    ///
+    /// The key is the name of a key of this node or the IPNS name of an ed25519 key.
+    pub async fn key_verify<U>(&self, key: U, data: Bytes, signature: &[u8]) -> Result<bool>
+    where
+        U: Into<Cow<'static, str>>,
+    {
+        let url = self.base_url.join("key/verify")?;
+
+        let signature = multibase::encode(Base::Base64Url, signature);
+
+        let form = Form::new().part("data", Part::stream(data));
+
+        let request = self
+            .client
+            .post(url)
+            .query(&[("key", &key.into())])
+            .query(&[("signature", &signature)])
+            .query(&[("ipns-base", self.ipns_base.as_str())])
+            .multipart(form);
+
+        let bytes = self.send_idempotent(request).await?;
+
+        if let Ok(res) = serde_json::from_slice::<KeyVerifyResponse>(&bytes) {
+            return Ok(res.signature_valid);
+        }
+
+        let error = serde_json::from_slice::<IPFSError>(&bytes)?;
+
+        Err(error.into())
+    }
+
     /// Publish new IPNS record.
     pub async fn name_publish<U>(&self, cid: Cid, key: U) -> Result<NamePublishResponse>
+    where
+        U: Into<Cow<'static, str>>,
+    {
+        self.name_publish_with(cid, key, &NamePublishOptions::default())
+            .await
+    }
+
+    /// Publish new IPNS record with these options, e.g. signed for V2 only.
+    pub async fn name_publish_with<U>(
+        &self,
+        cid: Cid,
+        key: U,
+        options: &NamePublishOptions,
+    ) -> Result<NamePublishResponse>
     where
         U: Into<Cow<'static, str>>,
     {
         let url = self.base_url.join("name/publish")?;
 
-        let bytes = self
+        let mut request = self
             .client
             .post(url)
             .query(&[("arg", &cid.to_string())])
-            .query(&[("lifetime", "4320h")]) // 6 months
+            .query(&[("lifetime", &go_duration(options.lifetime))])
             .query(&[("key", &key.into())])
-            .query(&[("ipns-base", "base32")])
-            .send()
-            .await?
-            .bytes()
-            .await?;
+            .query(&[("ipns-base", self.ipns_base.as_str())]);
+
+        if let Some(ttl) = options.ttl {
+            request = request.query(&[("ttl", &go_duration(ttl))]);
+        }
+
+        if let Some(v1_compat) = options.v1_compat {
+            request = request.query(&[("v1compat", &v1_compat.to_string())]);
+        }
+
+        if options.allow_offline {
+            request = request.query(&[("allow-offline", "true")]);
+        }
+
+        let bytes = self.send(request).await?;
 
         //println!("{}", std::str::from_utf8(&bytes).unwrap());
 
@@ -317,18 +1821,13 @@ impl IpfsService {
         Err(error.into())
     }
 
-    /// Resolve IPNS name. Returns CID.
-    pub async fn name_resolve(&self, ipns: Cid) -> Result<Cid> {
+    /// Resolve IPNS name. Returns the path it points to, which may go past the root CID.
+    pub async fn name_resolve(&self, ipns: Cid) -> Result<IpfsPath> {
         let url = self.base_url.join("name/resolve")?;
 
-        let bytes = self
-            .client
-            .post(url)
-            .query(&[("arg", &ipns.to_string())])
-            .send()
-            .await?
-            .bytes()
-            .await?;
+        let request = self.post_fetch(url).query(&[("arg", &ipns.to_string())]);
+
+        let bytes = self.send_idempotent(request).await?;
 
         //println!("{}", std::str::from_utf8(&bytes).unwrap());
 
@@ -341,90 +1840,251 @@ impl IpfsService {
         Err(error.into())
     }
 
-    ///Return peer id as cid v1.
-    pub async fn peer_id(&self) -> Result<Cid> {
+    /// Resolve IPNS name all the way to the CID of the block it points to, see `dag_resolve`.
+    pub async fn name_resolve_cid(&self, ipns: Cid) -> Result<Cid> {
+        let path = self.name_resolve(ipns).await?;
+
+        match (path.cid(), path.segments().is_empty()) {
+            (Some(cid), true) => Ok(cid),
+            _ => self.dag_resolve(path).await,
+        }
+    }
+
+    /// CID of the block at the end of this path.
+    ///
+    /// Fails if the path ends inside a block, on a value that is not a link.
+    pub async fn dag_resolve<P>(&self, path: P) -> Result<Cid>
+    where
+        P: Into<IpfsPath>,
+    {
+        let path = path.into();
+
+        let url = self.base_url.join("dag/resolve")?;
+
+        let request = self.post_fetch(url).query(&[("arg", &path.to_string())]);
+
+        let bytes = self.send_idempotent(request).await?;
+
+        if let Ok(res) = serde_json::from_slice::<DagResolveResponse>(&bytes) {
+            let cid = Cid::try_from(res.cid.cid_string)?;
+
+            if !res.rem_path.is_empty() {
+                return Err(format!("{} ends inside {} at {}", path, cid, res.rem_path).into());
+            }
+
+            return Ok(cid);
+        }
+
+        let error = serde_json::from_slice::<IPFSError>(&bytes)?;
+
+        Err(error.into())
+    }
+
+    /// Return the peer id of this node.
+    pub async fn peer_id(&self) -> Result<PeerId> {
+        let res = self.id_info(None).await?;
+
+        Ok(res.try_into()?)
+    }
+
+    /// Statistics of the provider system, e.g. to watch the reprovide queue.
+    #[cfg(feature = "diagnostics")]
+    pub async fn stats_provide(&self) -> Result<ProvideStats> {
+        let url = self.base_url.join("stats/provide")?;
+
+        let request = self.client.post(url);
+
+        let bytes = self.send_idempotent(request).await?;
+
+        if let Ok(res) = serde_json::from_slice::<ProvideStatsResponse>(&bytes) {
+            return Ok(res.into());
+        }
+
+        let error = serde_json::from_slice::<IPFSError>(&bytes)?;
+
+        Err(error.into())
+    }
+
+    /// Routing tables of the DHTs of this node.
+    #[cfg(feature = "diagnostics")]
+    pub async fn stats_dht(&self) -> Result<Vec<DhtStats>> {
+        let url = self.base_url.join("stats/dht")?;
+
+        let request = self.client.post(url);
+
+        let bytes = self.send_idempotent(request).await?;
+
+        let mut stats = Vec::new();
+
+        for line in bytes.split(|byte| *byte == b'\n') {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+
+            if let Ok(res) = serde_json::from_slice::<DhtStats>(line) {
+                stats.push(res);
+
+                continue;
+            }
+
+            let error = serde_json::from_slice::<IPFSError>(line)?;
+
+            return Err(error.into());
+        }
+
+        Ok(stats)
+    }
+
+    /// Return identify information of this node or of another peer.
+    pub async fn id_info(&self, peer: Option<PeerId>) -> Result<IdResponse> {
         let url = self.base_url.join("id")?;
 
-        let bytes = self.client.post(url).send().await?.bytes().await?;
+        let mut request = self.client.post(url);
+
+        if let Some(peer) = peer {
+            request = request.query(&[("arg", &peer.to_string())]);
+        }
+
+        let bytes = self.send_idempotent(request).await?;
 
         //println!("{}", std::str::from_utf8(&bytes).unwrap());
 
         if let Ok(res) = serde_json::from_slice::<IdResponse>(&bytes) {
-            return Ok(res.try_into()?);
+            return Ok(res);
         }
 
         let error = serde_json::from_slice::<IPFSError>(&bytes)?;
 
         Err(error.into())
     }
+}
 
-    /// Send data on the specified topic.
-    pub async fn pubsub_pub<T, D>(&self, topic: T, data: D) -> Result<()>
-    where
-        T: AsRef<[u8]>,
-        D: Into<Cow<'static, [u8]>>,
-    {
-        let url = self.base_url.join("pubsub/pub")?;
+/// Parse the newline delimited objects of an add response, one per entry.
+fn add_output(bytes: &[u8]) -> Result<AddOutput> {
+    let mut entries: Vec<AddEntry> = Vec::new();
+
+    for line in bytes.split(|byte| *byte == b'\n') {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
 
-        let topic = encode(Base::Base64Url, topic);
+        if let Ok(res) = serde_json::from_slice::<AddResponse>(line) {
+            entries.push(res.try_into()?);
 
-        let part = Part::bytes(data);
-        let form = Form::new().part("data", part);
+            continue;
+        }
 
-        self.client
-            .post(url)
-            .query(&[("arg", &topic)])
-            .multipart(form)
-            .send()
-            .await?;
+        let error = serde_json::from_slice::<IPFSError>(line)?;
 
-        Ok(())
+        return Err(error.into());
     }
 
-    pub async fn pubsub_sub_response<T>(&self, topic: T) -> Result<Response>
-    where
-        T: AsRef<[u8]>,
-    {
-        let url = self.base_url.join("pubsub/sub")?;
+    let root = match entries.last() {
+        Some(entry) => entry.cid,
+        None => return Err("Add response has no entry".into()),
+    };
 
-        let topic = encode(Base::Base64Url, topic);
+    Ok(AddOutput { entries, root })
+}
 
-        let response = self
-            .client
-            .post(url)
-            .query(&[("arg", topic)])
-            .send()
-            .await?;
+/// Duration as parsed by Go, in the largest unit it is a whole number of.
+fn go_duration(duration: Duration) -> String {
+    match (duration.as_secs(), duration.subsec_nanos()) {
+        (secs, 0) if secs % 3600 == 0 => format!("{}h", secs / 3600),
+        (secs, 0) => format!("{}s", secs),
+        _ => format!("{}ms", duration.as_millis()),
+    }
+}
+
+/// Read the body, failing as soon as it is larger than `max` bytes.
+async fn read_limited(response: Response, max: usize) -> Result<Bytes> {
+    if matches!(response.content_length(), Some(len) if len > max as u64) {
+        return Err(too_large(max));
+    }
+
+    let stream = compat_stream(response.bytes_stream());
+
+    futures_util::pin_mut!(stream);
+
+    let mut body = Vec::new();
 
-        Ok(response)
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+
+        if body.len() + chunk.len() > max {
+            return Err(too_large(max));
+        }
+
+        body.extend_from_slice(&chunk);
     }
+
+    Ok(body.into())
+}
+
+/// Delay of the `Retry-After` header of the response, if any.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?;
+
+    retry::parse_retry_after(value.to_str().ok()?)
+}
+
+fn too_large(max: usize) -> Box<dyn std::error::Error> {
+    let message = format!("Response larger than {} bytes", max);
+
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message).into()
 }
 
-pub fn pubsub_sub_stream(
-    response: Response,
-    regis: AbortRegistration,
-) -> impl Stream<Item = Result<PubSubMsg>> {
-    let stream = response.bytes_stream();
+/// Path of the URL relative to the node API, e.g. `dag/get`.
+fn endpoint<'a>(base_url: &Url, url: &'a Url) -> &'a str {
+    url.path()
+        .strip_prefix(base_url.path())
+        .unwrap_or(url.path())
+}
 
-    let abortable_stream = Abortable::new(stream, regis);
+/// Body of a streaming response.
+///
+/// Kubo reports failures mid-stream in an `X-Stream-Error` trailer, which the HTTP client cannot read.
+/// A body shorter than the `X-Content-Length` announced by the node, e.g. for `cat`, ends with an error instead.
+/// Newline delimited JSON cut in the middle of an object is detected by `ndjson::json_stream`.
+pub(crate) fn body_stream(response: Response) -> impl Stream<Item = std::io::Result<Bytes>> {
+    let announced = response
+        .headers()
+        .get("X-Content-Length")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
 
     //TODO implement from reqwest error for std::io::Error
-    let line_stream = abortable_stream
+    let body = compat_stream(response.bytes_stream())
         //.err_into()
-        .map_err(|error| std::io::Error::new(std::io::ErrorKind::Other, error))
-        .into_async_read()
-        .lines();
-
-    line_stream.map(|item| match item {
-        Ok(line) => {
-            if let Ok(response) = serde_json::from_str::<PubsubSubResponse>(&line) {
-                return Ok(response.try_into()?);
-            }
+        .map_err(std::io::Error::other);
 
-            let ipfs_error = serde_json::from_str::<IPFSError>(&line)?;
+    let state = (Box::pin(body), 0u64, false);
 
-            return Err(ipfs_error.into());
+    stream::unfold(state, move |(mut body, received, done)| async move {
+        if done {
+            return None;
+        }
+
+        match body.next().await {
+            Some(Ok(chunk)) => {
+                let received = received + chunk.len() as u64;
+
+                Some((Ok(chunk), (body, received, false)))
+            }
+            Some(Err(e)) => Some((Err(e), (body, received, false))),
+            None => match announced {
+                Some(length) if received < length => {
+                    let message = format!(
+                        "Stream ended after {} of {} bytes, the node may have reported an X-Stream-Error",
+                        received, length
+                    );
+
+                    let error = std::io::Error::new(std::io::ErrorKind::UnexpectedEof, message);
+
+                    Some((Err(error), (body, received, true)))
+                }
+                _ => None,
+            },
         }
-        Err(e) => Err(e.into()),
     })
 }
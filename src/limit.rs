@@ -0,0 +1,55 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    task::{Poll, Waker},
+};
+
+use futures_util::future;
+
+/// Bound the number of requests in flight at once.
+pub(crate) struct Limiter {
+    max: usize,
+    active: Cell<usize>,
+    waiters: RefCell<Vec<Waker>>,
+}
+
+impl Limiter {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max: max.max(1),
+            active: Cell::default(),
+            waiters: RefCell::default(),
+        }
+    }
+
+    /// Wait for a free slot, held until the returned permit is dropped.
+    pub async fn acquire(self: &Rc<Self>) -> Permit {
+        future::poll_fn(|cx| {
+            if self.active.get() < self.max {
+                self.active.set(self.active.get() + 1);
+
+                return Poll::Ready(());
+            }
+
+            self.waiters.borrow_mut().push(cx.waker().clone());
+
+            Poll::Pending
+        })
+        .await;
+
+        Permit(self.clone())
+    }
+}
+
+pub(crate) struct Permit(Rc<Limiter>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.active.set(self.0.active.get() - 1);
+
+        // Waking every waiter, a single one may have been dropped since it registered
+        for waker in self.0.waiters.take() {
+            waker.wake();
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use bytes::Bytes;
+
+use cid::Cid;
+
+use futures_util::{
+    future::{self, LocalBoxFuture},
+    FutureExt,
+};
+
+#[cfg(feature = "pubsub")]
+use std::{collections::VecDeque, rc::Weak, task::Poll, task::Waker};
+
+#[cfg(feature = "pubsub")]
+use futures_util::{
+    future::{AbortHandle, Abortable},
+    stream, StreamExt,
+};
+
+use multihash::{Code, MultihashDigest};
+
+use crate::{
+    api::IpfsApi,
+    base::IpnsBase,
+    path::{IpfsPath, PathRoot},
+    peer_id::PeerId,
+    responses::{KeyInfo, NamePublishResponse, PinList, PinType},
+    Result, RAW_CODEC,
+};
+
+#[cfg(feature = "pubsub")]
+use crate::{api::MsgStream, pubsub::Subscription, responses::PubSubMsg};
+
+/// Multicodec of the DAG nodes stored by `MemoryIpfs`.
+pub const DAG_JSON_CODEC: u64 = 0x0129;
+
+/// In process IPFS node keeping everything in memory, for tests without a daemon.
+///
+/// Added data is stored as a single raw block and DAG nodes as dag-json,
+/// CIDs will not match the ones a Kubo node would return.
+/// The only key is `self` and pubsub messages only reach subscribers of this node.
+/// Clones share the same store.
+#[derive(Clone)]
+pub struct MemoryIpfs {
+    peer_id: PeerId,
+    store: Rc<RefCell<Store>>,
+}
+
+#[derive(Default)]
+struct Store {
+    blocks: HashMap<Cid, Bytes>,
+    pins: PinList,
+    names: HashMap<Cid, Cid>,
+    #[cfg(feature = "pubsub")]
+    topics: HashMap<Vec<u8>, Vec<Weak<RefCell<Inbox>>>>,
+    #[cfg(feature = "pubsub")]
+    seqno: u64,
+}
+
+/// Messages waiting to be read by a subscriber.
+#[cfg(feature = "pubsub")]
+#[derive(Default)]
+struct Inbox {
+    messages: VecDeque<PubSubMsg>,
+    waker: Option<Waker>,
+}
+
+impl Default for MemoryIpfs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryIpfs {
+    pub fn new() -> Self {
+        Self::with_seed(b"memory")
+    }
+
+    /// Nodes created with different seeds have different peer ids.
+    pub fn with_seed(seed: &[u8]) -> Self {
+        Self {
+            peer_id: PeerId::from_multihash(Code::Sha2_256.digest(seed)),
+            store: Rc::default(),
+        }
+    }
+
+    fn put(&self, codec: u64, data: Bytes) -> Cid {
+        let cid = Cid::new_v1(codec, Code::Sha2_256.digest(&data));
+
+        self.store.borrow_mut().blocks.insert(cid, data);
+
+        cid
+    }
+
+    fn block(&self, cid: &Cid) -> Result<Bytes> {
+        match self.store.borrow().blocks.get(cid) {
+            Some(data) => Ok(data.clone()),
+            None => Err(format!("Block {} not found", cid).into()),
+        }
+    }
+
+    /// CID the root of the path points to.
+    fn resolve_root(&self, path: &IpfsPath) -> Result<Cid> {
+        match path.root() {
+            PathRoot::Ipfs(cid) => Ok(*cid),
+            PathRoot::Ipns(name) => {
+                let name = Cid::try_from(name.as_str())?;
+
+                self.resolve_name(name)
+            }
+        }
+    }
+
+    fn resolve_name(&self, name: Cid) -> Result<Cid> {
+        match self.store.borrow().names.get(&name) {
+            Some(cid) => Ok(*cid),
+            None => Err(format!("Name {} not published", name).into()),
+        }
+    }
+
+    fn node(&self, cid: &Cid) -> Result<serde_json::Value> {
+        if cid.codec() != DAG_JSON_CODEC {
+            return Err(format!("{} is not a dag-json node", cid).into());
+        }
+
+        Ok(serde_json::from_slice(&self.block(cid)?)?)
+    }
+
+    /// Walk the path, following links from node to node.
+    fn walk(&self, path: &IpfsPath) -> Result<serde_json::Value> {
+        let mut node = self.node(&self.resolve_root(path)?)?;
+
+        for segment in path.segments() {
+            node = self.follow(node)?;
+
+            let next = match &node {
+                serde_json::Value::Object(map) => map.get(segment),
+                serde_json::Value::Array(list) => segment
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| list.get(index)),
+                _ => None,
+            };
+
+            node = match next {
+                Some(next) => next.clone(),
+                None => return Err(format!("No link named {} in {}", segment, path).into()),
+            };
+        }
+
+        self.follow(node)
+    }
+
+    /// The linked node if the value is a link, the value otherwise.
+    fn follow(&self, value: serde_json::Value) -> Result<serde_json::Value> {
+        let link = match &value {
+            serde_json::Value::Object(map) if map.len() == 1 => {
+                map.get("/").and_then(|v| v.as_str())
+            }
+            _ => None,
+        };
+
+        match link {
+            Some(link) => self.node(&Cid::try_from(link)?),
+            None => Ok(value),
+        }
+    }
+
+    /// Data of a raw block, directories are not supported.
+    fn file(&self, path: &IpfsPath) -> Result<Bytes> {
+        if !path.segments().is_empty() {
+            return Err(format!("No link named {} in {}", path.sub_path(), path).into());
+        }
+
+        let cid = self.resolve_root(path)?;
+
+        if cid.codec() != RAW_CODEC {
+            return Err(format!("{} is not a file", cid).into());
+        }
+
+        self.block(&cid)
+    }
+
+    fn self_key(&self) -> Cid {
+        self.peer_id.to_cid()
+    }
+}
+
+impl IpfsApi for MemoryIpfs {
+    fn add(&self, bytes: Bytes) -> LocalBoxFuture<'_, Result<Cid>> {
+        future::ready(Ok(self.put(RAW_CODEC, bytes))).boxed_local()
+    }
+
+    fn cat(&self, path: IpfsPath) -> LocalBoxFuture<'_, Result<Bytes>> {
+        future::ready(self.file(&path)).boxed_local()
+    }
+
+    fn block_get(&self, cid: Cid) -> LocalBoxFuture<'_, Result<Bytes>> {
+        future::ready(self.block(&cid)).boxed_local()
+    }
+
+    fn dag_put(&self, node: serde_json::Value) -> LocalBoxFuture<'_, Result<Cid>> {
+        let res = serde_json::to_vec(&node)
+            .map(|data| self.put(DAG_JSON_CODEC, data.into()))
+            .map_err(Into::into);
+
+        future::ready(res).boxed_local()
+    }
+
+    fn dag_get(&self, path: IpfsPath) -> LocalBoxFuture<'_, Result<serde_json::Value>> {
+        future::ready(self.walk(&path)).boxed_local()
+    }
+
+    fn pin_add(&self, cid: Cid, recursive: bool) -> LocalBoxFuture<'_, Result<()>> {
+        let res = self.block(&cid).map(|_| {
+            let pin_type = match recursive {
+                true => PinType::Recursive,
+                false => PinType::Direct,
+            };
+
+            self.store.borrow_mut().pins.insert(cid, pin_type);
+        });
+
+        future::ready(res).boxed_local()
+    }
+
+    fn pin_rm(&self, cid: Cid, _recursive: bool) -> LocalBoxFuture<'_, Result<()>> {
+        let res = match self.store.borrow_mut().pins.remove(&cid) {
+            Some(_) => Ok(()),
+            None => Err(format!("{} is not pinned", cid).into()),
+        };
+
+        future::ready(res).boxed_local()
+    }
+
+    fn pin_ls(&self, pin_type: Option<PinType>) -> LocalBoxFuture<'_, Result<PinList>> {
+        let pins = self
+            .store
+            .borrow()
+            .pins
+            .iter()
+            .filter(|(_, pin)| pin_type.is_none() || pin_type == Some(**pin))
+            .map(|(cid, pin)| (*cid, *pin))
+            .collect();
+
+        future::ready(Ok(pins)).boxed_local()
+    }
+
+    fn key_list(&self) -> LocalBoxFuture<'_, Result<Vec<KeyInfo>>> {
+        let keys = vec![KeyInfo {
+            name: "self".to_owned(),
+            ipns_name: IpnsBase::Base36.encode(&self.self_key()),
+            peer_id: self.peer_id,
+        }];
+
+        future::ready(Ok(keys)).boxed_local()
+    }
+
+    fn name_publish(
+        &self,
+        cid: Cid,
+        key: String,
+    ) -> LocalBoxFuture<'_, Result<NamePublishResponse>> {
+        let res = match key.as_str() {
+            "self" => {
+                let name = self.self_key();
+
+                self.store.borrow_mut().names.insert(name, cid);
+
+                Ok(NamePublishResponse {
+                    name: name.to_string(),
+                    value: format!("/ipfs/{}", cid),
+                })
+            }
+            _ => Err(format!("No key named {}", key).into()),
+        };
+
+        future::ready(res).boxed_local()
+    }
+
+    fn name_resolve(&self, ipns: Cid) -> LocalBoxFuture<'_, Result<IpfsPath>> {
+        future::ready(self.resolve_name(ipns).map(IpfsPath::from)).boxed_local()
+    }
+
+    fn peer_id(&self) -> LocalBoxFuture<'_, Result<PeerId>> {
+        future::ready(Ok(self.peer_id)).boxed_local()
+    }
+
+    #[cfg(feature = "pubsub")]
+    fn pubsub_pub(&self, topic: Vec<u8>, data: Vec<u8>) -> LocalBoxFuture<'_, Result<()>> {
+        let mut store = self.store.borrow_mut();
+
+        store.seqno += 1;
+
+        let seqno = store.seqno.to_be_bytes().to_vec();
+
+        if let Some(inboxes) = store.topics.get_mut(&topic) {
+            inboxes.retain(|inbox| inbox.strong_count() > 0);
+
+            for inbox in inboxes.iter().filter_map(Weak::upgrade) {
+                let mut inbox = inbox.borrow_mut();
+
+                inbox.messages.push_back(PubSubMsg {
+                    from: self.peer_id,
+                    data: data.clone(),
+                    seqno: seqno.clone(),
+                });
+
+                if let Some(waker) = inbox.waker.take() {
+                    waker.wake();
+                }
+            }
+        }
+
+        future::ready(Ok(())).boxed_local()
+    }
+
+    #[cfg(feature = "pubsub")]
+    fn pubsub_sub(&self, topic: Vec<u8>) -> LocalBoxFuture<'_, Result<(MsgStream, Subscription)>> {
+        let inbox = Rc::new(RefCell::new(Inbox::default()));
+
+        self.store
+            .borrow_mut()
+            .topics
+            .entry(topic)
+            .or_default()
+            .push(Rc::downgrade(&inbox));
+
+        let messages = stream::poll_fn(move |cx| {
+            let mut inbox = inbox.borrow_mut();
+
+            match inbox.messages.pop_front() {
+                Some(msg) => Poll::Ready(Some(Ok(msg))),
+                None => {
+                    inbox.waker = Some(cx.waker().clone());
+
+                    Poll::Pending
+                }
+            }
+        });
+
+        let (handle, regis) = AbortHandle::new_pair();
+
+        let subscription = Subscription::new(handle);
+
+        let stream = subscription
+            .track(Abortable::new(messages, regis))
+            .boxed_local();
+
+        future::ready(Ok((stream, subscription))).boxed_local()
+    }
+}
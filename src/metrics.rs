@@ -0,0 +1,77 @@
+use std::{cell::RefCell, collections::HashMap, time::Duration};
+
+use bytes::Bytes;
+
+use crate::Result;
+
+/// Upper bounds of the latency histogram buckets, slower requests go in a last bucket.
+pub const LATENCY_BUCKETS: [Duration; 10] = [
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+];
+
+/// Requests made to one endpoint of the node API.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EndpointMetrics {
+    pub requests: u64,
+
+    /// Requests that failed or were answered with an error status.
+    pub errors: u64,
+
+    /// Size of the response bodies.
+    pub bytes_received: u64,
+
+    /// Request count per `LATENCY_BUCKETS` entry, then the count of slower requests.
+    pub latency: [u64; LATENCY_BUCKETS.len() + 1],
+
+    pub total_latency: Duration,
+}
+
+/// Metrics keyed by endpoint, e.g. `dag/get`.
+pub type MetricsSnapshot = HashMap<String, EndpointMetrics>;
+
+#[derive(Default)]
+pub(crate) struct Metrics {
+    endpoints: RefCell<MetricsSnapshot>,
+}
+
+impl Metrics {
+    pub fn record(&self, endpoint: &str, result: &Result<(u16, Bytes)>, latency: Duration) {
+        let mut endpoints = self.endpoints.borrow_mut();
+
+        let metrics = match endpoints.get_mut(endpoint) {
+            Some(metrics) => metrics,
+            None => endpoints.entry(endpoint.to_owned()).or_default(),
+        };
+
+        metrics.requests += 1;
+
+        match result {
+            Ok((status, bytes)) => {
+                if !(200..300).contains(status) {
+                    metrics.errors += 1;
+                }
+
+                metrics.bytes_received += bytes.len() as u64;
+            }
+            Err(_) => metrics.errors += 1,
+        }
+
+        let bucket = LATENCY_BUCKETS.partition_point(|bound| *bound < latency);
+
+        metrics.latency[bucket] += 1;
+        metrics.total_latency += latency;
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.endpoints.borrow().clone()
+    }
+}
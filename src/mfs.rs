@@ -0,0 +1,87 @@
+use bytes::Bytes;
+
+use reqwest::{
+    multipart::{Form, Part},
+    RequestBuilder,
+};
+
+use crate::{
+    responses::{FilesStat, FilesStatResponse, IPFSError},
+    IpfsService, Result,
+};
+
+impl IpfsService {
+    /// Write the data to the file at this MFS path, e.g. `/docs/a.txt`, replacing its content.
+    ///
+    /// The file and its parent directories are created if missing,
+    /// with the mode and modification time of this service, see `with_mode` and `with_mtime`.
+    pub async fn files_write(&self, path: &str, data: Bytes) -> Result<()> {
+        let url = self.base_url.join("files/write")?;
+
+        let part = Part::stream(data);
+        let form = Form::new().part("data", part);
+
+        let request = self
+            .client
+            .post(url)
+            .query(&[("arg", path)])
+            .query(&[("create", "true")])
+            .query(&[("parents", "true")])
+            .query(&[("truncate", "true")]);
+
+        let request = self.metadata_query(request).multipart(form);
+
+        self.send(request).await?;
+
+        Ok(())
+    }
+
+    /// Create a directory at this MFS path, and its parents if missing.
+    ///
+    /// Created with the mode and modification time of this service, see `with_mode` and `with_mtime`.
+    pub async fn files_mkdir(&self, path: &str) -> Result<()> {
+        let url = self.base_url.join("files/mkdir")?;
+
+        let request = self
+            .client
+            .post(url)
+            .query(&[("arg", path)])
+            .query(&[("parents", "true")]);
+
+        let request = self.metadata_query(request);
+
+        self.send(request).await?;
+
+        Ok(())
+    }
+
+    /// Status of the file or directory at this MFS path, or e.g. `/ipfs/<cid>`.
+    pub async fn files_stat(&self, path: &str) -> Result<FilesStat> {
+        let url = self.base_url.join("files/stat")?;
+
+        let request = self.client.post(url).query(&[("arg", path)]);
+
+        let bytes = self.send_idempotent(request).await?;
+
+        if let Ok(res) = serde_json::from_slice::<FilesStatResponse>(&bytes) {
+            return Ok(res.try_into()?);
+        }
+
+        let error = serde_json::from_slice::<IPFSError>(&bytes)?;
+
+        Err(error.into())
+    }
+
+    /// Query the mode and modification time of this service.
+    fn metadata_query(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = match self.mode {
+            Some(mode) => request.query(&[("mode", &mode.to_string())]),
+            None => request,
+        };
+
+        match self.mtime {
+            Some(mtime) => request.query(&[("mtime", &mtime.to_string())]),
+            None => request,
+        }
+    }
+}
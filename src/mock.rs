@@ -0,0 +1,351 @@
+use std::{cell::RefCell, rc::Rc};
+
+use bytes::Bytes;
+
+use cid::Cid;
+
+use futures_util::{
+    future::{self, LocalBoxFuture},
+    FutureExt,
+};
+
+#[cfg(feature = "pubsub")]
+use futures_util::{future::AbortHandle, stream, StreamExt};
+
+use crate::{
+    api::IpfsApi,
+    path::IpfsPath,
+    peer_id::PeerId,
+    responses::{KeyInfo, NamePublishResponse, PinList, PinType},
+    Result,
+};
+
+#[cfg(feature = "pubsub")]
+use crate::{api::MsgStream, pubsub::Subscription, responses::PubSubMsg};
+
+type Handler<A, T> = Option<Rc<dyn Fn(A) -> Result<T>>>;
+
+/// A call received by a `MockIpfs`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockCall {
+    Add(Bytes),
+    Cat(IpfsPath),
+    BlockGet(Cid),
+    DagPut(serde_json::Value),
+    DagGet(IpfsPath),
+    PinAdd { cid: Cid, recursive: bool },
+    PinRm { cid: Cid, recursive: bool },
+    PinLs(Option<PinType>),
+    KeyList,
+    NamePublish { cid: Cid, key: String },
+    NameResolve(Cid),
+    PeerId,
+    PubsubPub { topic: Vec<u8>, data: Vec<u8> },
+    PubsubSub(Vec<u8>),
+}
+
+/// `IpfsApi` answering with programmed responses and recording every call.
+///
+/// Calls without a programmed response fail. Clones share the recorded calls.
+#[derive(Clone, Default)]
+pub struct MockIpfs {
+    calls: Rc<RefCell<Vec<MockCall>>>,
+
+    add: Handler<Bytes, Cid>,
+    cat: Handler<IpfsPath, Bytes>,
+    block_get: Handler<Cid, Bytes>,
+    dag_put: Handler<serde_json::Value, Cid>,
+    dag_get: Handler<IpfsPath, serde_json::Value>,
+    pin_add: Handler<(Cid, bool), ()>,
+    pin_rm: Handler<(Cid, bool), ()>,
+    pin_ls: Handler<Option<PinType>, PinList>,
+    key_list: Handler<(), Vec<KeyInfo>>,
+    name_publish: Handler<(Cid, String), NamePublishResponse>,
+    name_resolve: Handler<Cid, IpfsPath>,
+    peer_id: Handler<(), PeerId>,
+    #[cfg(feature = "pubsub")]
+    pubsub_pub: Handler<(Vec<u8>, Vec<u8>), ()>,
+    #[cfg(feature = "pubsub")]
+    pubsub_sub: Handler<Vec<u8>, Vec<PubSubMsg>>,
+}
+
+impl MockIpfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls received so far, oldest first.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.borrow().clone()
+    }
+
+    pub fn on_add<F>(&self, f: F) -> Self
+    where
+        F: Fn(Bytes) -> Result<Cid> + 'static,
+    {
+        Self {
+            add: Some(Rc::new(f)),
+            ..self.clone()
+        }
+    }
+
+    pub fn on_cat<F>(&self, f: F) -> Self
+    where
+        F: Fn(IpfsPath) -> Result<Bytes> + 'static,
+    {
+        Self {
+            cat: Some(Rc::new(f)),
+            ..self.clone()
+        }
+    }
+
+    pub fn on_block_get<F>(&self, f: F) -> Self
+    where
+        F: Fn(Cid) -> Result<Bytes> + 'static,
+    {
+        Self {
+            block_get: Some(Rc::new(f)),
+            ..self.clone()
+        }
+    }
+
+    pub fn on_dag_put<F>(&self, f: F) -> Self
+    where
+        F: Fn(serde_json::Value) -> Result<Cid> + 'static,
+    {
+        Self {
+            dag_put: Some(Rc::new(f)),
+            ..self.clone()
+        }
+    }
+
+    pub fn on_dag_get<F>(&self, f: F) -> Self
+    where
+        F: Fn(IpfsPath) -> Result<serde_json::Value> + 'static,
+    {
+        Self {
+            dag_get: Some(Rc::new(f)),
+            ..self.clone()
+        }
+    }
+
+    pub fn on_pin_add<F>(&self, f: F) -> Self
+    where
+        F: Fn((Cid, bool)) -> Result<()> + 'static,
+    {
+        Self {
+            pin_add: Some(Rc::new(f)),
+            ..self.clone()
+        }
+    }
+
+    pub fn on_pin_rm<F>(&self, f: F) -> Self
+    where
+        F: Fn((Cid, bool)) -> Result<()> + 'static,
+    {
+        Self {
+            pin_rm: Some(Rc::new(f)),
+            ..self.clone()
+        }
+    }
+
+    pub fn on_pin_ls<F>(&self, f: F) -> Self
+    where
+        F: Fn(Option<PinType>) -> Result<PinList> + 'static,
+    {
+        Self {
+            pin_ls: Some(Rc::new(f)),
+            ..self.clone()
+        }
+    }
+
+    pub fn on_key_list<F>(&self, f: F) -> Self
+    where
+        F: Fn(()) -> Result<Vec<KeyInfo>> + 'static,
+    {
+        Self {
+            key_list: Some(Rc::new(f)),
+            ..self.clone()
+        }
+    }
+
+    pub fn on_name_publish<F>(&self, f: F) -> Self
+    where
+        F: Fn((Cid, String)) -> Result<NamePublishResponse> + 'static,
+    {
+        Self {
+            name_publish: Some(Rc::new(f)),
+            ..self.clone()
+        }
+    }
+
+    pub fn on_name_resolve<F>(&self, f: F) -> Self
+    where
+        F: Fn(Cid) -> Result<IpfsPath> + 'static,
+    {
+        Self {
+            name_resolve: Some(Rc::new(f)),
+            ..self.clone()
+        }
+    }
+
+    pub fn on_peer_id<F>(&self, f: F) -> Self
+    where
+        F: Fn(()) -> Result<PeerId> + 'static,
+    {
+        Self {
+            peer_id: Some(Rc::new(f)),
+            ..self.clone()
+        }
+    }
+
+    #[cfg(feature = "pubsub")]
+    pub fn on_pubsub_pub<F>(&self, f: F) -> Self
+    where
+        F: Fn((Vec<u8>, Vec<u8>)) -> Result<()> + 'static,
+    {
+        Self {
+            pubsub_pub: Some(Rc::new(f)),
+            ..self.clone()
+        }
+    }
+
+    /// The subscription yields the messages returned then ends.
+    #[cfg(feature = "pubsub")]
+    pub fn on_pubsub_sub<F>(&self, f: F) -> Self
+    where
+        F: Fn(Vec<u8>) -> Result<Vec<PubSubMsg>> + 'static,
+    {
+        Self {
+            pubsub_sub: Some(Rc::new(f)),
+            ..self.clone()
+        }
+    }
+
+    /// Record the call then answer with the handler, if any.
+    fn answer<A, T>(
+        &self,
+        call: MockCall,
+        name: &str,
+        handler: &Handler<A, T>,
+        args: A,
+    ) -> LocalBoxFuture<'static, Result<T>>
+    where
+        T: 'static,
+    {
+        self.calls.borrow_mut().push(call);
+
+        let res = match handler {
+            Some(handler) => handler(args),
+            None => Err(format!("MockIpfs has no response for {}", name).into()),
+        };
+
+        future::ready(res).boxed_local()
+    }
+}
+
+impl IpfsApi for MockIpfs {
+    fn add(&self, bytes: Bytes) -> LocalBoxFuture<'_, Result<Cid>> {
+        self.answer(MockCall::Add(bytes.clone()), "add", &self.add, bytes)
+    }
+
+    fn cat(&self, path: IpfsPath) -> LocalBoxFuture<'_, Result<Bytes>> {
+        self.answer(MockCall::Cat(path.clone()), "cat", &self.cat, path)
+    }
+
+    fn block_get(&self, cid: Cid) -> LocalBoxFuture<'_, Result<Bytes>> {
+        self.answer(MockCall::BlockGet(cid), "block_get", &self.block_get, cid)
+    }
+
+    fn dag_put(&self, node: serde_json::Value) -> LocalBoxFuture<'_, Result<Cid>> {
+        self.answer(
+            MockCall::DagPut(node.clone()),
+            "dag_put",
+            &self.dag_put,
+            node,
+        )
+    }
+
+    fn dag_get(&self, path: IpfsPath) -> LocalBoxFuture<'_, Result<serde_json::Value>> {
+        self.answer(
+            MockCall::DagGet(path.clone()),
+            "dag_get",
+            &self.dag_get,
+            path,
+        )
+    }
+
+    fn pin_add(&self, cid: Cid, recursive: bool) -> LocalBoxFuture<'_, Result<()>> {
+        let call = MockCall::PinAdd { cid, recursive };
+
+        self.answer(call, "pin_add", &self.pin_add, (cid, recursive))
+    }
+
+    fn pin_rm(&self, cid: Cid, recursive: bool) -> LocalBoxFuture<'_, Result<()>> {
+        let call = MockCall::PinRm { cid, recursive };
+
+        self.answer(call, "pin_rm", &self.pin_rm, (cid, recursive))
+    }
+
+    fn pin_ls(&self, pin_type: Option<PinType>) -> LocalBoxFuture<'_, Result<PinList>> {
+        self.answer(MockCall::PinLs(pin_type), "pin_ls", &self.pin_ls, pin_type)
+    }
+
+    fn key_list(&self) -> LocalBoxFuture<'_, Result<Vec<KeyInfo>>> {
+        self.answer(MockCall::KeyList, "key_list", &self.key_list, ())
+    }
+
+    fn name_publish(
+        &self,
+        cid: Cid,
+        key: String,
+    ) -> LocalBoxFuture<'_, Result<NamePublishResponse>> {
+        let call = MockCall::NamePublish {
+            cid,
+            key: key.clone(),
+        };
+
+        self.answer(call, "name_publish", &self.name_publish, (cid, key))
+    }
+
+    fn name_resolve(&self, ipns: Cid) -> LocalBoxFuture<'_, Result<IpfsPath>> {
+        let call = MockCall::NameResolve(ipns);
+
+        self.answer(call, "name_resolve", &self.name_resolve, ipns)
+    }
+
+    fn peer_id(&self) -> LocalBoxFuture<'_, Result<PeerId>> {
+        self.answer(MockCall::PeerId, "peer_id", &self.peer_id, ())
+    }
+
+    #[cfg(feature = "pubsub")]
+    fn pubsub_pub(&self, topic: Vec<u8>, data: Vec<u8>) -> LocalBoxFuture<'_, Result<()>> {
+        let call = MockCall::PubsubPub {
+            topic: topic.clone(),
+            data: data.clone(),
+        };
+
+        self.answer(call, "pubsub_pub", &self.pubsub_pub, (topic, data))
+    }
+
+    #[cfg(feature = "pubsub")]
+    fn pubsub_sub(&self, topic: Vec<u8>) -> LocalBoxFuture<'_, Result<(MsgStream, Subscription)>> {
+        let call = MockCall::PubsubSub(topic.clone());
+
+        self.answer(call, "pubsub_sub", &self.pubsub_sub, topic)
+            .map(|res| {
+                let msgs = res?;
+
+                let (handle, _) = AbortHandle::new_pair();
+
+                let subscription = Subscription::new(handle);
+
+                let stream = subscription
+                    .track(stream::iter(msgs.into_iter().map(Ok)))
+                    .boxed_local();
+
+                Ok((stream, subscription))
+            })
+            .boxed_local()
+    }
+}
@@ -0,0 +1,95 @@
+use bytes::{BufMut, Bytes, BytesMut};
+
+use cid::multibase::{self, Base};
+
+use multihash::{Code, MultihashDigest};
+
+/// UnixFS metadata of a file added with `add_files_with_metadata`.
+///
+/// Only stored by services with `with_preserve_mode` and `with_preserve_mtime`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// POSIX mode, e.g. `0o644`.
+    pub mode: Option<u32>,
+
+    /// Modification time in seconds since the Unix epoch.
+    pub mtime: Option<i64>,
+
+    /// Nanoseconds of the modification time.
+    pub mtime_nsecs: Option<u32>,
+}
+
+/// Multipart body of files to add, returned with its content type.
+///
+/// The HTTP client cannot add parameters to the `Content-Disposition` of a part,
+/// which is where Kubo reads the mode and mtime of each file.
+/// Entries without metadata are directories, which must precede their files.
+pub(crate) fn files_body(files: &[(String, Bytes, Option<FileMetadata>)]) -> (String, Bytes) {
+    // Derived from the content so that it cannot appear in it
+    let mut hashed = Vec::new();
+
+    for (name, bytes, _) in files {
+        hashed.extend_from_slice(name.as_bytes());
+        hashed.extend_from_slice(&Code::Sha2_256.digest(bytes).to_bytes());
+    }
+
+    let digest = Code::Sha2_256.digest(&hashed);
+    let boundary = multibase::encode(Base::Base32Lower, digest.digest());
+
+    let mut body = BytesMut::new();
+
+    for (name, bytes, metadata) in files {
+        let mut disposition = format!("form-data; name=\"file\"; filename=\"{}\"", escape(name));
+
+        let content_type = match metadata {
+            Some(metadata) => {
+                if let Some(mode) = metadata.mode {
+                    disposition.push_str(&format!("; mode={:o}", mode));
+                }
+
+                if let Some(mtime) = metadata.mtime {
+                    disposition.push_str(&format!("; mtime={}", mtime));
+                }
+
+                if let Some(nsecs) = metadata.mtime_nsecs {
+                    disposition.push_str(&format!("; mtime-nsecs={}", nsecs));
+                }
+
+                "application/octet-stream"
+            }
+            None => "application/x-directory",
+        };
+
+        body.put_slice(
+            format!(
+                "--{}\r\nContent-Disposition: {}\r\nContent-Type: {}\r\n\r\n",
+                boundary, disposition, content_type
+            )
+            .as_bytes(),
+        );
+        body.put_slice(bytes);
+        body.put_slice(b"\r\n");
+    }
+
+    body.put_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    let content_type = format!("multipart/form-data; boundary={}", boundary);
+
+    (content_type, body.freeze())
+}
+
+/// Percent encode a file name, unescaped by the node like a query.
+fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+
+    for byte in name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                escaped.push(byte as char)
+            }
+            byte => escaped.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    escaped
+}
@@ -0,0 +1,327 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use cid::Cid;
+
+use futures_util::{
+    future::{self, AbortHandle, AbortRegistration, Abortable},
+    stream::{self, LocalBoxStream},
+    Stream, StreamExt,
+};
+
+use reqwest::{Request, RequestBuilder, Response};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    body_stream, peer_id::PeerId, redact, responses::*, retry_after, runtime, Error, IpfsService,
+    Result,
+};
+
+#[cfg(feature = "dht")]
+use crate::capability::CompatMode;
+
+#[cfg(feature = "testing")]
+use crate::fixture;
+
+/// Decode a streaming response of newline delimited JSON objects, until it ends or is aborted.
+///
+/// Error objects sent by the node are returned as `IPFSError`. Failures reported in the
+/// `X-Stream-Error` trailer cannot be read, a body cut in the middle of an object is returned as an error.
+pub fn response_to_json_stream<T>(
+    response: Response,
+    regis: AbortRegistration,
+) -> LocalBoxStream<'static, Result<T>>
+where
+    T: DeserializeOwned + 'static,
+{
+    json_stream(Abortable::new(body_stream(response), regis))
+}
+
+pub(crate) fn json_stream<T, S>(stream: S) -> LocalBoxStream<'static, Result<T>>
+where
+    T: DeserializeOwned + 'static,
+    S: Stream<Item = std::io::Result<Bytes>> + 'static,
+{
+    let state = (Box::pin(stream), Vec::new(), false);
+
+    stream::unfold(state, |(mut stream, mut buffer, mut done)| async move {
+        loop {
+            if let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+
+                if is_blank(&line) {
+                    continue;
+                }
+
+                return Some((decode_line(&line), (stream, buffer, done)));
+            }
+
+            if done {
+                if is_blank(&buffer) {
+                    return None;
+                }
+
+                let line = std::mem::take(&mut buffer);
+
+                return Some((decode_line(&line), (stream, buffer, done)));
+            }
+
+            match stream.next().await {
+                Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+                Some(Err(e)) => {
+                    buffer.clear();
+
+                    return Some((Err(e.into()), (stream, buffer, done)));
+                }
+                None => done = true,
+            }
+        }
+    })
+    .boxed_local()
+}
+
+fn is_blank(line: &[u8]) -> bool {
+    line.iter().all(u8::is_ascii_whitespace)
+}
+
+fn decode_line<T>(line: &[u8]) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    if let Ok(res) = serde_json::from_slice::<T>(line) {
+        return Ok(res);
+    }
+
+    let error = serde_json::from_slice::<IPFSError>(line)?;
+
+    Err(error.into())
+}
+
+impl IpfsService {
+    /// Send the request, failing if the node does not respond with a success status.
+    ///
+    /// The body is left unread, for the caller to stream with `tracked_body`.
+    pub(crate) async fn send_streaming(&self, request: Request) -> Result<Response> {
+        let endpoint = crate::endpoint(&self.base_url, request.url()).to_owned();
+
+        let (regis, _guard) = self.in_flight.register();
+
+        let response = Abortable::new(runtime::compat(self.client.execute(request)), regis)
+            .await?
+            .map_err(|e| redact::error(e.into()))?;
+
+        let status = response.status().as_u16();
+
+        if !(200..300).contains(&status) {
+            let retry_after = retry_after(&response);
+            let bytes = runtime::compat(response.bytes()).await?;
+
+            return Err(self
+                .status_error(endpoint, status, &bytes, retry_after)
+                .into());
+        }
+
+        Ok(response)
+    }
+
+    /// Body of the response, aborted with the in-flight requests.
+    ///
+    /// An aborted body ends with an error instead of looking complete.
+    pub(crate) fn tracked_body(
+        &self,
+        response: Response,
+    ) -> impl Stream<Item = std::io::Result<Bytes>> {
+        let (handle, regis) = AbortHandle::new_pair();
+
+        let guard = self.in_flight.track(handle.clone());
+
+        let end = stream::once(async move {
+            let _tracked = guard;
+
+            handle.is_aborted().then(|| {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::Interrupted,
+                    "Request aborted",
+                ))
+            })
+        })
+        .filter_map(future::ready);
+
+        Abortable::new(body_stream(response), regis).chain(end)
+    }
+
+    fn status_error(
+        &self,
+        endpoint: String,
+        status: u16,
+        bytes: &Bytes,
+        retry_after: Option<Duration>,
+    ) -> Error {
+        Error::from_status(self.url(), endpoint, status, bytes, retry_after)
+    }
+
+    /// Send the request and decode the response as it arrives, see `response_to_json_stream`.
+    ///
+    /// The stream is aborted by `abort_all` and `shutdown` but is not recorded nor subject to the timeout.
+    /// When replaying, the recorded response is decoded instead.
+    async fn send_json_stream<T>(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<LocalBoxStream<'static, Result<T>>>
+    where
+        T: DeserializeOwned + 'static,
+    {
+        let request = self.with_default_headers(request).build()?;
+
+        let body = self.send_body_stream(request).await?;
+
+        Ok(json_stream(body))
+    }
+
+    /// Send the request and return the body as it arrives, see `tracked_body`.
+    ///
+    /// When replaying, the recorded body is returned instead.
+    pub(crate) async fn send_body_stream(
+        &self,
+        request: Request,
+    ) -> Result<LocalBoxStream<'static, std::io::Result<Bytes>>> {
+        #[cfg(feature = "testing")]
+        if let Some(fixture) = &self.fixture {
+            if let Some(res) = fixture.replay(&fixture::key(&self.base_url, &request)) {
+                let (status, bytes) = res?;
+
+                if !(200..300).contains(&status) {
+                    let endpoint = crate::endpoint(&self.base_url, request.url()).to_owned();
+
+                    return Err(self.status_error(endpoint, status, &bytes, None).into());
+                }
+
+                return Ok(stream::once(future::ready(Ok(bytes))).boxed_local());
+            }
+        }
+
+        let response = self.send_streaming(request).await?;
+
+        Ok(self.tracked_body(response).boxed_local())
+    }
+
+    /// CIDs linked from this CID as the node finds them, see `refs`.
+    pub async fn refs_stream(
+        &self,
+        cid: Cid,
+        max_depth: Option<usize>,
+    ) -> Result<impl Stream<Item = Result<Cid>>> {
+        let url = self.base_url.join("refs")?;
+
+        let max_depth = match max_depth {
+            Some(depth) => depth.to_string(),
+            None => "-1".to_owned(),
+        };
+
+        let request = self
+            .post_fetch(url)
+            .query(&[("arg", &cid.to_string())])
+            .query(&[("recursive", "true"), ("unique", "true")])
+            .query(&[("max-depth", &max_depth)]);
+
+        let stream = self.send_json_stream::<RefsResponse>(request).await?;
+
+        Ok(stream.map(|item| {
+            let res = item?;
+
+            if !res.error.is_empty() {
+                return Err(res.error.into());
+            }
+
+            Ok(Cid::try_from(res.cid)?)
+        }))
+    }
+
+    /// Ping a peer `count` times, replies arriving as they are received.
+    pub async fn ping(
+        &self,
+        peer: PeerId,
+        count: u32,
+    ) -> Result<impl Stream<Item = Result<PingReply>>> {
+        let url = self.base_url.join("ping")?;
+
+        let request = self
+            .client
+            .post(url)
+            .query(&[("arg", &peer.to_string())])
+            .query(&[("count", count)]);
+
+        let stream = self.send_json_stream::<PingResponse>(request).await?;
+
+        Ok(stream.map(|item| Ok(item?.into())))
+    }
+
+    /// Peers providing this CID, at most `num_providers` of them, as the DHT finds them.
+    #[cfg(feature = "dht")]
+    pub async fn routing_findprovs(
+        &self,
+        cid: Cid,
+        num_providers: u32,
+    ) -> Result<impl Stream<Item = Result<Provider>>> {
+        let endpoint = match self.compat_mode().await {
+            CompatMode::Legacy => "dht/findprovs",
+            CompatMode::Current => "routing/findprovs",
+        };
+
+        let url = self.base_url.join(endpoint)?;
+
+        let request = self
+            .client
+            .post(url)
+            .query(&[("arg", &cid.to_string())])
+            .query(&[("num-providers", num_providers)]);
+
+        let stream = self
+            .send_json_stream::<RoutingQueryResponse>(request)
+            .await?;
+
+        let providers = stream.flat_map(|item| {
+            let providers: Vec<Result<Provider>> = match item {
+                Ok(res) if res.event_type == RoutingQueryResponse::PROVIDER => res
+                    .responses
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|peer| Ok(peer.try_into()?))
+                    .collect(),
+                Ok(_) => Vec::new(),
+                Err(e) => vec![Err(e)],
+            };
+
+            stream::iter(providers)
+        });
+
+        Ok(providers)
+    }
+
+    /// Garbage collect the repository, returning the CIDs of the removed blocks as they are.
+    #[cfg(feature = "diagnostics")]
+    pub async fn repo_gc(&self) -> Result<impl Stream<Item = Result<Cid>>> {
+        let url = self.base_url.join("repo/gc")?;
+
+        let request = self.client.post(url).query(&[("stream-errors", "true")]);
+
+        let stream = self.send_json_stream::<RepoGcResponse>(request).await?;
+
+        Ok(stream.filter_map(|item| async move {
+            let res = match item {
+                Ok(res) => res,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if !res.error.is_empty() {
+                return Some(Err(res.error.into()));
+            }
+
+            let key = res.key?;
+
+            Some(Cid::try_from(key.cid_string).map_err(Into::into))
+        }))
+    }
+}
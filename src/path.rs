@@ -0,0 +1,168 @@
+use core::fmt;
+
+use std::str::FromStr;
+
+use cid::Cid;
+
+/// Content a path starts from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathRoot {
+    /// Immutable content.
+    Ipfs(Cid),
+
+    /// IPNS name, a key CID or a DNSLink domain.
+    Ipns(String),
+}
+
+/// Path to content, e.g. `/ipfs/<cid>/a/b` or `/ipns/<name>/a`.
+///
+/// Parsed from `/ipfs/...`, `/ipns/...` or `<cid>/...` strings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IpfsPath {
+    root: PathRoot,
+    segments: Vec<String>,
+}
+
+/// Returned when a string is not a valid path.
+pub struct PathError {
+    pub path: String,
+    pub reason: &'static str,
+}
+
+impl std::error::Error for PathError {}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid path {}: {}", self.path, self.reason)
+    }
+}
+
+impl fmt::Debug for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl IpfsPath {
+    /// Path to the content with this CID.
+    pub fn ipfs(cid: Cid) -> Self {
+        Self {
+            root: PathRoot::Ipfs(cid),
+            segments: Vec::new(),
+        }
+    }
+
+    /// Path to the content an IPNS name points to.
+    pub fn ipns<T>(name: T) -> Result<Self, PathError>
+    where
+        T: Into<String>,
+    {
+        let name = name.into();
+
+        if name.is_empty() || name.contains('/') {
+            return Err(PathError {
+                path: name,
+                reason: "IPNS name must be a single segment",
+            });
+        }
+
+        Ok(Self {
+            root: PathRoot::Ipns(name),
+            segments: Vec::new(),
+        })
+    }
+
+    /// Append the segments of a relative path, a leading `/` is optional.
+    pub fn join(mut self, path: &str) -> Result<Self, PathError> {
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            if segment == "." || segment == ".." {
+                return Err(PathError {
+                    path: path.to_owned(),
+                    reason: "relative segments are not supported",
+                });
+            }
+
+            self.segments.push(segment.to_owned());
+        }
+
+        Ok(self)
+    }
+
+    pub fn root(&self) -> &PathRoot {
+        &self.root
+    }
+
+    /// Root CID, if the path points to immutable content.
+    pub fn cid(&self) -> Option<Cid> {
+        match &self.root {
+            PathRoot::Ipfs(cid) => Some(*cid),
+            PathRoot::Ipns(_) => None,
+        }
+    }
+
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    /// Segments after the root, each prefixed with `/`, empty for a root path.
+    pub fn sub_path(&self) -> String {
+        self.segments
+            .iter()
+            .fold(String::new(), |mut path, segment| {
+                path.push('/');
+                path.push_str(segment);
+                path
+            })
+    }
+}
+
+impl From<Cid> for IpfsPath {
+    fn from(cid: Cid) -> Self {
+        Self::ipfs(cid)
+    }
+}
+
+impl fmt::Display for IpfsPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.root {
+            PathRoot::Ipfs(cid) => write!(f, "/ipfs/{}{}", cid, self.sub_path()),
+            PathRoot::Ipns(name) => write!(f, "/ipns/{}{}", name, self.sub_path()),
+        }
+    }
+}
+
+impl FromStr for IpfsPath {
+    type Err = PathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |reason| PathError {
+            path: s.to_owned(),
+            reason,
+        };
+
+        let (namespace, rest) = match s.strip_prefix('/') {
+            Some(rest) => match rest.split_once('/') {
+                Some((namespace, rest)) => (Some(namespace), rest),
+                None => return Err(error("missing /ipfs/ or /ipns/ root")),
+            },
+            None => (None, s),
+        };
+
+        let (root, rest) = match rest.split_once('/') {
+            Some((root, rest)) => (root, rest),
+            None => (rest, ""),
+        };
+
+        let path = match namespace {
+            Some("ipfs") | None => {
+                let cid = Cid::try_from(root).map_err(|_| error("invalid CID"))?;
+
+                Self::ipfs(cid)
+            }
+            Some("ipns") => Self::ipns(root).map_err(|e| error(e.reason))?,
+            Some(_) => return Err(error("namespace must be ipfs or ipns")),
+        };
+
+        path.join(rest).map_err(|e| error(e.reason))
+    }
+}
@@ -0,0 +1,85 @@
+use core::fmt;
+
+use std::str::FromStr;
+
+use cid::{multibase::Base, multihash::MultihashGeneric, Cid};
+
+use crate::base::IpnsBase;
+
+/// Multicodec of CIDs encoding a libp2p public key.
+pub const LIBP2P_KEY_CODEC: u64 = 0x72;
+
+/// Libp2p peer identity, the multihash of the peer public key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PeerId(MultihashGeneric<64>);
+
+impl PeerId {
+    pub fn from_multihash(multihash: MultihashGeneric<64>) -> Self {
+        Self(multihash)
+    }
+
+    pub fn multihash(&self) -> &MultihashGeneric<64> {
+        &self.0
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes()
+    }
+
+    /// Multihash bytes as base 58 btc, the legacy text representation.
+    pub fn to_base58(&self) -> String {
+        Base::Base58Btc.encode(self.0.to_bytes())
+    }
+
+    /// CIDv1 with the libp2p-key codec.
+    pub fn to_cid(&self) -> Cid {
+        Cid::new_v1(LIBP2P_KEY_CODEC, self.0)
+    }
+
+    /// IPNS name of the key in this base, e.g. `k51...` in base36.
+    pub fn to_ipns_name(&self, base: IpnsBase) -> String {
+        base.encode(&self.to_cid())
+    }
+}
+
+impl fmt::Display for PeerId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_base58())
+    }
+}
+
+impl FromStr for PeerId {
+    type Err = cid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // https://github.com/libp2p/specs/blob/master/peer-ids/peer-ids.md#string-representation
+        if s.starts_with('1') || s.starts_with("Qm") {
+            let decoded = Base::Base58Btc.decode(s)?;
+            let multihash = MultihashGeneric::from_bytes(&decoded)?;
+
+            return Ok(Self(multihash));
+        }
+
+        let cid = Cid::try_from(s)?;
+
+        Self::try_from(cid)
+    }
+}
+
+impl TryFrom<Cid> for PeerId {
+    type Error = cid::Error;
+
+    fn try_from(cid: Cid) -> Result<Self, Self::Error> {
+        if cid.codec() != LIBP2P_KEY_CODEC {
+            return Err(cid::Error::UnknownCodec);
+        }
+
+        Ok(Self(*cid.hash()))
+    }
+}
+
+impl From<PeerId> for Cid {
+    fn from(peer_id: PeerId) -> Self {
+        peer_id.to_cid()
+    }
+}
@@ -0,0 +1,890 @@
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    future::Future,
+    rc::Rc,
+    task::{Poll, Waker},
+    time::Duration,
+};
+
+use bytes::Bytes;
+
+use futures_util::{
+    future::{self, Either},
+    stream::FuturesUnordered,
+    StreamExt,
+};
+
+use cid::Cid;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use reqwest::Url;
+
+use crate::{
+    breaker::{CircuitBreakerConfig, CircuitState},
+    clock, coalesce,
+    gateway::Gateways,
+    health::{HealthCheck, NodeHealth, NodeStats, NodeStatus},
+    path::IpfsPath,
+    repair::{ReadRepair, RepairQueue},
+    replication::ReplicationStatus,
+    responses::{IdResponse, KeyInfo, NamePublishResponse},
+    retry::is_unsent,
+    ring::HashRing,
+    Error, IpfsService, Result,
+};
+
+/// How the pool picks the node serving a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Routing {
+    /// Rotate through the nodes.
+    #[default]
+    RoundRobin,
+
+    /// Requests keyed by CID always go to the same node, others rotate.
+    ConsistentHash { virtual_nodes: usize },
+
+    /// Prefer the nodes answering fastest and failing least, see `NodeStats`.
+    ///
+    /// Load shifts away from a node as it degrades, nodes with equal stats rotate.
+    Fastest,
+}
+
+/// How many nodes a hedged read is sent to and how long to wait before each extra request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hedging {
+    pub fanout: usize,
+    pub delay: Duration,
+}
+
+impl Default for Hedging {
+    fn default() -> Self {
+        Self {
+            fanout: 2,
+            delay: Duration::from_millis(50),
+        }
+    }
+}
+
+/// Outcome of an operation on one pool node.
+#[derive(Debug)]
+pub struct NodeOutcome<T = ()> {
+    pub url: Url,
+    pub result: Result<T>,
+}
+
+/// A set of IPFS nodes used together.
+///
+/// Clones share the same nodes, membership changes are seen by all of them.
+#[derive(Clone)]
+pub struct IpfsPool {
+    members: Rc<RefCell<Members>>,
+    cursor: Rc<Cell<usize>>,
+    routing: Routing,
+    /// Ring built for a membership generation.
+    ring: RefCell<Option<(usize, Rc<HashRing>)>>,
+    gateways: Option<Rc<Gateways>>,
+    /// Node stream-oriented operations are sent to, by key.
+    affinity: Rc<RefCell<HashMap<Vec<u8>, Url>>>,
+    /// Background replication of writes, by CID.
+    pub(crate) replication: Rc<RefCell<HashMap<Cid, ReplicationStatus>>>,
+    pub(crate) read_repair: Option<ReadRepair>,
+    pub(crate) repairs: Rc<RepairQueue>,
+    pub(crate) breaker: CircuitBreakerConfig,
+}
+
+struct Members {
+    nodes: Vec<Rc<Node>>,
+    /// Incremented on every membership change.
+    generation: usize,
+}
+
+pub(crate) struct Node {
+    pub(crate) service: IpfsService,
+    circuit: RefCell<CircuitState>,
+    health: Cell<NodeHealth>,
+    stats: Cell<NodeStats>,
+    active: Cell<usize>,
+    drained: RefCell<Option<Waker>>,
+}
+
+/// Marks a request in flight on a node until dropped.
+pub(crate) struct Active {
+    node: Rc<Node>,
+    started_at: Duration,
+}
+
+impl Active {
+    /// Record the outcome and latency of the request then end it.
+    pub(crate) fn finish<T>(self, res: &Result<T>, breaker: &CircuitBreakerConfig) {
+        let answered = match res {
+            Ok(_) => true,
+            Err(e) => !is_node_failure(e.as_ref()),
+        };
+
+        if answered {
+            let mut stats = self.node.stats.get();
+            stats.record_latency(clock::now().saturating_sub(self.started_at));
+            self.node.stats.set(stats);
+        }
+
+        self.node.observe(res, breaker);
+    }
+}
+
+impl Drop for Active {
+    fn drop(&mut self) {
+        let active = self.node.active.get() - 1;
+
+        self.node.active.set(active);
+
+        if active == 0 {
+            if let Some(waker) = self.node.drained.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Node {
+    fn new(service: IpfsService) -> Self {
+        Self {
+            service,
+            circuit: RefCell::default(),
+            health: Cell::default(),
+            stats: Cell::default(),
+            active: Cell::default(),
+            drained: RefCell::default(),
+        }
+    }
+
+    /// Count a request in flight on this node until the returned guard is dropped.
+    pub(crate) fn begin(self: &Rc<Self>) -> Active {
+        self.active.set(self.active.get() + 1);
+
+        Active {
+            node: self.clone(),
+            started_at: clock::now(),
+        }
+    }
+
+    /// Resolve once no request is in flight on this node.
+    async fn drain(&self) {
+        future::poll_fn(|cx| {
+            if self.active.get() == 0 {
+                return Poll::Ready(());
+            }
+
+            *self.drained.borrow_mut() = Some(cx.waker().clone());
+
+            Poll::Pending
+        })
+        .await
+    }
+
+    async fn probe(&self, timeout: Duration) {
+        let start = clock::now();
+
+        let res = self.service.with_timeout(timeout).peer_id().await;
+
+        let now = clock::now();
+
+        let health = NodeHealth {
+            up: Some(res.is_ok()),
+            latency: res.ok().map(|_| now.saturating_sub(start)),
+            checked_at: Some(clock::since_epoch()),
+        };
+
+        self.health.set(health);
+    }
+
+    /// Returns true if a request may be routed to this node.
+    pub(crate) fn allow(&self, breaker: &CircuitBreakerConfig) -> bool {
+        !self.health.get().is_down() && self.circuit.borrow_mut().allow(breaker)
+    }
+
+    /// Update the circuit breaker and error rate with the outcome of a request.
+    pub(crate) fn observe<T>(&self, res: &Result<T>, breaker: &CircuitBreakerConfig) {
+        let failed = match res {
+            Ok(_) => {
+                self.circuit.borrow_mut().on_success();
+
+                false
+            }
+            Err(e) if is_node_failure(e.as_ref()) => {
+                self.circuit.borrow_mut().on_failure(breaker);
+
+                true
+            }
+            Err(_) => false,
+        };
+
+        let mut stats = self.stats.get();
+        stats.record_outcome(failed);
+        self.stats.set(stats);
+    }
+}
+
+impl IpfsPool {
+    pub fn new<I>(urls: I) -> Self
+    where
+        I: IntoIterator<Item = Url>,
+    {
+        Self::from_services(urls.into_iter().map(IpfsService::new))
+    }
+
+    /// Pool of nodes reached through these services, each keeping its own configuration.
+    pub fn from_services<I>(services: I) -> Self
+    where
+        I: IntoIterator<Item = IpfsService>,
+    {
+        let nodes = services
+            .into_iter()
+            .map(|service| Rc::new(Node::new(service)))
+            .collect();
+
+        let members = Members {
+            nodes,
+            generation: 0,
+        };
+
+        Self {
+            members: Rc::new(RefCell::new(members)),
+            cursor: Rc::default(),
+            routing: Routing::default(),
+            ring: RefCell::default(),
+            gateways: None,
+            affinity: Rc::default(),
+            replication: Rc::default(),
+            read_repair: None,
+            repairs: Rc::default(),
+            breaker: CircuitBreakerConfig::default(),
+        }
+    }
+
+    /// Add a node to the pool, returns false if a node with this URL is already a member.
+    pub fn add_node(&self, url: Url) -> bool {
+        self.add_service(IpfsService::new(url))
+    }
+
+    /// Add a node reached through this service, returns false if a node with its URL is already a member.
+    pub fn add_service(&self, service: IpfsService) -> bool {
+        let mut members = self.members.borrow_mut();
+
+        if members
+            .nodes
+            .iter()
+            .any(|node| node.service.url() == service.url())
+        {
+            return false;
+        }
+
+        members.nodes.push(Rc::new(Node::new(service)));
+        members.generation += 1;
+
+        true
+    }
+
+    /// Remove a node from the pool, no new request is routed to it.
+    ///
+    /// The returned future resolves once requests already in flight on the node completed,
+    /// with false if no node had this URL. Pubsub subscriptions are not waited for.
+    pub fn remove_node(&self, url: &Url) -> impl Future<Output = bool> {
+        let removed = {
+            let mut members = self.members.borrow_mut();
+
+            let position = members
+                .nodes
+                .iter()
+                .position(|node| node.service.url() == url);
+
+            position.map(|i| {
+                members.generation += 1;
+                members.nodes.remove(i)
+            })
+        };
+
+        async move {
+            match removed {
+                Some(node) => {
+                    node.drain().await;
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+
+    /// Return a pool using this configuration for its node circuit breakers.
+    pub fn with_circuit_breaker(&self, config: CircuitBreakerConfig) -> Self {
+        Self {
+            breaker: config,
+            ..self.clone()
+        }
+    }
+
+    /// Return a pool selecting nodes according to this routing mode.
+    pub fn with_routing(&self, routing: Routing) -> Self {
+        Self {
+            routing,
+            ring: RefCell::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Return a pool reading from these HTTP gateways when every node failed.
+    ///
+    /// Gateway URLs are the origin only, e.g. `https://ipfs.io/`.
+    pub fn with_gateways<I>(&self, urls: I) -> Self
+    where
+        I: IntoIterator<Item = Url>,
+    {
+        let urls: Vec<Url> = urls.into_iter().collect();
+
+        let gateways = if urls.is_empty() {
+            None
+        } else {
+            Some(Rc::new(Gateways::new(urls)))
+        };
+
+        Self {
+            gateways,
+            ..self.clone()
+        }
+    }
+
+    /// Queue the CIDs read with `cat` and `dag_get` for repair, see `read_repair`.
+    pub fn with_read_repair(&self, config: ReadRepair) -> Self {
+        Self {
+            read_repair: Some(config),
+            ..self.clone()
+        }
+    }
+
+    /// Send the stream-oriented operations keyed by `key`, e.g. a pubsub topic, to this node.
+    ///
+    /// Returns false if no node has this URL. Keys are otherwise assigned a node on first use.
+    pub fn set_affinity(&self, key: &[u8], url: &Url) -> bool {
+        if !self.snapshot().iter().any(|node| node.service.url() == url) {
+            return false;
+        }
+
+        self.affinity.borrow_mut().insert(key.to_vec(), url.clone());
+
+        true
+    }
+
+    /// Let the next operation keyed by `key` pick a node again.
+    pub fn clear_affinity(&self, key: &[u8]) {
+        self.affinity.borrow_mut().remove(key);
+    }
+
+    /// Node of the operations keyed by `key`.
+    ///
+    /// A new node is picked, and kept, if the key has none or if its node left or is unavailable.
+    #[cfg(feature = "pubsub")]
+    pub(crate) fn sticky_node(&self, key: &[u8]) -> Option<Rc<Node>> {
+        let current = self.affinity.borrow().get(key).cloned();
+
+        if let Some(url) = current {
+            let node = self
+                .snapshot()
+                .into_iter()
+                .find(|node| node.service.url() == &url && node.allow(&self.breaker));
+
+            if node.is_some() {
+                return node;
+            }
+        }
+
+        let node = self
+            .route()
+            .into_iter()
+            .find(|node| node.allow(&self.breaker))?;
+
+        self.affinity
+            .borrow_mut()
+            .insert(key.to_vec(), node.service.url().clone());
+
+        Some(node)
+    }
+
+    /// Services of every node in the pool.
+    pub fn nodes(&self) -> Vec<IpfsService> {
+        self.snapshot()
+            .iter()
+            .map(|node| node.service.clone())
+            .collect()
+    }
+
+    /// Current members of the pool.
+    pub(crate) fn snapshot(&self) -> Vec<Rc<Node>> {
+        self.members.borrow().nodes.clone()
+    }
+
+    /// Health and circuit breaker state of every node.
+    pub fn pool_status(&self) -> Vec<NodeStatus> {
+        self.snapshot()
+            .iter()
+            .map(|node| NodeStatus {
+                url: node.service.url().clone(),
+                health: node.health.get(),
+                circuit: *node.circuit.borrow(),
+                stats: node.stats.get(),
+            })
+            .collect()
+    }
+
+    /// Run the operation on every node concurrently, regardless of health or circuit state.
+    pub async fn call_all<F, Fut, T>(&self, operation: F) -> Vec<NodeOutcome<T>>
+    where
+        F: Fn(IpfsService) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.call_each(self.snapshot(), operation).await
+    }
+
+    async fn call_each<F, Fut, T>(&self, nodes: Vec<Rc<Node>>, operation: F) -> Vec<NodeOutcome<T>>
+    where
+        F: Fn(IpfsService) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let results = future::join_all(nodes.iter().map(|node| {
+            let active = node.begin();
+            let fut = operation(node.service.clone());
+
+            async move {
+                let res = fut.await;
+                active.finish(&res, &self.breaker);
+
+                res
+            }
+        }))
+        .await;
+
+        nodes
+            .iter()
+            .zip(results)
+            .map(|(node, result)| NodeOutcome {
+                url: node.service.url().clone(),
+                result,
+            })
+            .collect()
+    }
+
+    /// Keys held by every node.
+    pub async fn key_list_all(&self) -> Vec<NodeOutcome<Vec<KeyInfo>>> {
+        self.call_all(|ipfs| async move { ipfs.key_list().await })
+            .await
+    }
+
+    /// Identity of every node.
+    pub async fn id_all(&self) -> Vec<NodeOutcome<IdResponse>> {
+        self.call_all(|ipfs| async move { ipfs.id_info(None).await })
+            .await
+    }
+
+    /// Publish an IPNS record on every node holding the key.
+    ///
+    /// Fails only if no node holds the key, per node outcomes are returned otherwise.
+    pub async fn name_publish_all<U>(
+        &self,
+        cid: Cid,
+        key: U,
+    ) -> Result<Vec<NodeOutcome<NamePublishResponse>>>
+    where
+        U: Into<Cow<'static, str>>,
+    {
+        let key = key.into();
+
+        let holders: Vec<Url> = self
+            .key_list_all()
+            .await
+            .into_iter()
+            .filter(|outcome| match &outcome.result {
+                Ok(list) => list.iter().any(|info| info.name == key),
+                Err(_) => false,
+            })
+            .map(|outcome| outcome.url)
+            .collect();
+
+        if holders.is_empty() {
+            return Err(format!("No pool node holds key {}", key).into());
+        }
+
+        let nodes = self
+            .snapshot()
+            .into_iter()
+            .filter(|node| holders.contains(node.service.url()))
+            .collect();
+
+        let outcomes = self
+            .call_each(nodes, |ipfs| {
+                let key = key.clone();
+
+                async move { ipfs.name_publish(cid, key).await }
+            })
+            .await;
+
+        Ok(outcomes)
+    }
+
+    /// Probe every node periodically, nodes found down are skipped when routing.
+    ///
+    /// The returned future never completes, spawn it on a local executor and drop it to stop.
+    pub fn health_check(&self, config: HealthCheck) -> impl Future<Output = ()> {
+        let pool = self.clone();
+
+        async move {
+            loop {
+                let nodes = pool.snapshot();
+
+                future::join_all(nodes.iter().map(|node| node.probe(config.timeout))).await;
+
+                futures_timer::Delay::new(config.interval).await;
+            }
+        }
+    }
+
+    /// Run the operation on the next available node, failing over to the others if the node fails.
+    ///
+    /// Errors returned by the IPFS API itself are not node failures and are returned as is.
+    pub async fn call<F, Fut, T>(&self, operation: F) -> Result<T>
+    where
+        F: Fn(IpfsService) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.call_routed(self.route(), operation).await
+    }
+
+    async fn call_routed<F, Fut, T>(&self, nodes: Vec<Rc<Node>>, operation: F) -> Result<T>
+    where
+        F: Fn(IpfsService) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let (_, res) = self.call_routed_node(nodes, true, operation).await?;
+
+        Ok(res)
+    }
+
+    /// Like `call_routed` but also return the node that answered.
+    ///
+    /// Operations not `idempotent` only fail over if the request never reached the node.
+    pub(crate) async fn call_routed_node<F, Fut, T>(
+        &self,
+        nodes: Vec<Rc<Node>>,
+        idempotent: bool,
+        operation: F,
+    ) -> Result<(Rc<Node>, T)>
+    where
+        F: Fn(IpfsService) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut last_error = None;
+
+        for node in nodes {
+            if !node.allow(&self.breaker) {
+                continue;
+            }
+
+            let active = node.begin();
+
+            let res = operation(node.service.clone()).await;
+
+            active.finish(&res, &self.breaker);
+
+            match res {
+                Ok(res) => return Ok((node, res)),
+                Err(e) if is_node_failure(e.as_ref()) && (idempotent || is_unsent(e.as_ref())) => {
+                    last_error = Some(e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(no_node_available))
+    }
+
+    /// Like `call` but, with consistent hash routing, always start with the node owning this CID.
+    pub async fn call_for<F, Fut, T>(&self, cid: &Cid, operation: F) -> Result<T>
+    where
+        F: Fn(IpfsService) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.call_routed(self.route_for(Some(cid)), operation).await
+    }
+
+    /// Send a read to several nodes, staggered by the hedging delay, and return the first success.
+    ///
+    /// The next node is asked right away when a request fails.
+    /// Requests still in flight are cancelled. Only use with operations safe to run on many nodes.
+    pub async fn call_hedged<F, Fut, T>(&self, hedging: Hedging, operation: F) -> Result<T>
+    where
+        F: Fn(IpfsService) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let (_, res) = self
+            .call_hedged_node(self.route(), hedging, operation)
+            .await?;
+
+        Ok(res)
+    }
+
+    /// Like `call_hedged` but also return the node that answered.
+    async fn call_hedged_node<F, Fut, T>(
+        &self,
+        nodes: Vec<Rc<Node>>,
+        hedging: Hedging,
+        operation: F,
+    ) -> Result<(Rc<Node>, T)>
+    where
+        F: Fn(IpfsService) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut nodes = nodes
+            .into_iter()
+            .filter(|node| node.allow(&self.breaker))
+            .take(hedging.fanout.max(1));
+
+        let attempt = |node: Rc<Node>| {
+            let fut = operation(node.service.clone());
+
+            async move {
+                let active = node.begin();
+                let res = fut.await;
+                active.finish(&res, &self.breaker);
+
+                (node, res)
+            }
+        };
+
+        let mut pending = FuturesUnordered::new();
+        let mut next = nodes.next();
+        let mut last_error = None;
+
+        loop {
+            let node = match next.take() {
+                Some(node) if pending.is_empty() => Some(node),
+                Some(node) => {
+                    let delay = futures_timer::Delay::new(hedging.delay);
+
+                    match future::select(pending.next(), delay).await {
+                        Either::Left((Some((node, Ok(res))), _)) => return Ok((node, res)),
+                        Either::Left((Some((_, Err(e))), _)) => last_error = Some(e),
+                        Either::Left((None, _)) | Either::Right(_) => {}
+                    }
+
+                    Some(node)
+                }
+                None => match pending.next().await {
+                    Some((node, Ok(res))) => return Ok((node, res)),
+                    Some((_, Err(e))) => {
+                        last_error = Some(e);
+
+                        None
+                    }
+                    None => break,
+                },
+            };
+
+            if let Some(node) = node {
+                pending.push(attempt(node));
+                next = nodes.next();
+            }
+        }
+
+        Err(last_error.unwrap_or_else(no_node_available))
+    }
+
+    /// Download the content at this path, falling back to the gateways.
+    pub async fn cat<P>(&self, path: P) -> Result<Bytes>
+    where
+        P: Into<IpfsPath>,
+    {
+        self.cat_with(path.into(), None).await
+    }
+
+    /// Like `cat` but sent to several nodes, see `call_hedged`.
+    pub async fn cat_hedged<P>(&self, path: P, hedging: Hedging) -> Result<Bytes>
+    where
+        P: Into<IpfsPath>,
+    {
+        self.cat_with(path.into(), Some(hedging)).await
+    }
+
+    async fn cat_with(&self, path: IpfsPath, hedging: Option<Hedging>) -> Result<Bytes> {
+        let res = self
+            .call_read(&path, hedging, |ipfs| {
+                let path = path.clone();
+
+                async move { ipfs.cat(path).await }
+            })
+            .await;
+
+        match (res, &self.gateways) {
+            (Err(e), Some(gateways)) if is_node_failure(e.as_ref()) => {
+                gateways.get(&path, None).await
+            }
+            (res, _) => res,
+        }
+    }
+
+    /// Deserialize dag node at this path, falling back to the gateways as dag-json.
+    pub async fn dag_get<P, T>(&self, path: P) -> Result<T>
+    where
+        P: Into<IpfsPath>,
+        T: DeserializeOwned,
+    {
+        self.dag_get_with(path.into(), None).await
+    }
+
+    /// Like `dag_get` but sent to several nodes, see `call_hedged`.
+    pub async fn dag_get_hedged<P, T>(&self, path: P, hedging: Hedging) -> Result<T>
+    where
+        P: Into<IpfsPath>,
+        T: DeserializeOwned,
+    {
+        self.dag_get_with(path.into(), Some(hedging)).await
+    }
+
+    async fn dag_get_with<T>(&self, path: IpfsPath, hedging: Option<Hedging>) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let res = self
+            .call_read(&path, hedging, |ipfs| {
+                let path = path.clone();
+
+                async move { ipfs.dag_get(path).await }
+            })
+            .await;
+
+        match (res, &self.gateways) {
+            (Err(e), Some(gateways)) if is_node_failure(e.as_ref()) => {
+                let bytes = gateways.get(&path, Some("dag-json")).await?;
+
+                Ok(serde_json::from_slice::<T>(&bytes)?)
+            }
+            (res, _) => res,
+        }
+    }
+
+    /// Read the path, hedged if enabled, queueing its CID for repair if it has no segments.
+    async fn call_read<F, Fut, T>(
+        &self,
+        path: &IpfsPath,
+        hedging: Option<Hedging>,
+        operation: F,
+    ) -> Result<T>
+    where
+        F: Fn(IpfsService) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let cid = path.cid();
+        let nodes = self.route_for(cid.as_ref());
+
+        let (node, res) = match hedging {
+            Some(hedging) => self.call_hedged_node(nodes, hedging, operation).await?,
+            None => self.call_routed_node(nodes, true, operation).await?,
+        };
+
+        if let Some(cid) = cid.filter(|_| path.segments().is_empty()) {
+            self.queue_repair(cid, &node);
+        }
+
+        Ok(res)
+    }
+
+    /// Nodes in the order they should be tried, rotating on each call.
+    pub(crate) fn route(&self) -> Vec<Rc<Node>> {
+        self.route_for(None)
+    }
+
+    /// Nodes in the order they should be tried for requests about this CID.
+    pub(crate) fn route_for(&self, cid: Option<&Cid>) -> Vec<Rc<Node>> {
+        let members = self.members.borrow();
+
+        let mut order = match (self.ring(&members), cid) {
+            (Some(ring), Some(cid)) => ring.lookup(&cid.to_bytes()),
+            _ => {
+                let start = self.cursor.get();
+                self.cursor.set(start.wrapping_add(1));
+
+                let len = members.nodes.len();
+
+                (0..len).map(|i| (start + i) % len).collect()
+            }
+        };
+
+        if self.routing == Routing::Fastest {
+            // Stable, nodes of equal score keep their rotation
+            order.sort_by(|a, b| {
+                let a = members.nodes[*a].stats.get().score();
+                let b = members.nodes[*b].stats.get().score();
+
+                a.total_cmp(&b)
+            });
+        }
+
+        order
+            .into_iter()
+            .map(|i| members.nodes[i].clone())
+            .collect()
+    }
+
+    /// Hash ring of the current members, rebuilt after membership changes.
+    fn ring(&self, members: &Members) -> Option<Rc<HashRing>> {
+        let virtual_nodes = match self.routing {
+            Routing::RoundRobin | Routing::Fastest => return None,
+            Routing::ConsistentHash { virtual_nodes } => virtual_nodes,
+        };
+
+        let mut cache = self.ring.borrow_mut();
+
+        if let Some((generation, ring)) = cache.as_ref() {
+            if *generation == members.generation {
+                return Some(ring.clone());
+            }
+        }
+
+        let names = members.nodes.iter().map(|node| node.service.url().as_str());
+        let ring = Rc::new(HashRing::new(names, virtual_nodes));
+
+        *cache = Some((members.generation, ring.clone()));
+
+        Some(ring)
+    }
+}
+
+/// Returns true if the error means the node could not be reached, did not answer in time or is rate limiting.
+pub(crate) fn is_node_failure(error: &(dyn std::error::Error + 'static)) -> bool {
+    let error = match coalesce::original(error).downcast_ref::<Error>() {
+        Some(Error::RateLimited { .. }) => return true,
+        // The node answered
+        Some(Error::Request {
+            status: Some(_), ..
+        })
+        | Some(Error::PathNotFound { .. }) => return false,
+        Some(Error::Request { source, .. }) => source.as_ref(),
+        None => coalesce::original(error),
+    };
+
+    if error.downcast_ref::<reqwest::Error>().is_some() {
+        return true;
+    }
+
+    match error.downcast_ref::<std::io::Error>() {
+        Some(error) => matches!(
+            error.kind(),
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::NotConnected
+        ),
+        None => false,
+    }
+}
+
+pub(crate) fn no_node_available() -> Box<dyn std::error::Error> {
+    std::io::Error::new(std::io::ErrorKind::NotConnected, "No pool node available").into()
+}
@@ -0,0 +1,174 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+
+use futures_util::{stream, Stream, StreamExt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock, codec::Codec, peer_id::PeerId, pubsub::Subscription, responses::PubSubMsg, IpfsService,
+    Result,
+};
+
+/// How often to announce presence and how many heartbeats a peer may miss.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PresenceConfig {
+    pub interval: Duration,
+
+    /// Peers are gone after missing this many of their heartbeats in a row.
+    pub missed: u32,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            missed: 3,
+        }
+    }
+}
+
+/// Change of the peers present on a topic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresenceEvent {
+    Joined(PeerId),
+    Left(PeerId),
+}
+
+/// Payload published on the topic.
+#[derive(Serialize, Deserialize)]
+struct Heartbeat {
+    /// Interval of the sender in milliseconds.
+    interval: u64,
+}
+
+/// Peers recently seen on a topic, shared by its clones.
+#[derive(Clone)]
+pub struct Presence {
+    /// When the presence of each peer expires, since the unix epoch.
+    peers: Rc<RefCell<HashMap<PeerId, Duration>>>,
+    subscription: Subscription,
+}
+
+impl Presence {
+    /// Peers seen on the topic and not expired, this node included.
+    pub fn peers(&self) -> Vec<PeerId> {
+        self.peers.borrow().keys().copied().collect()
+    }
+
+    pub fn is_present(&self, peer: &PeerId) -> bool {
+        self.peers.borrow().contains_key(peer)
+    }
+
+    /// Stop announcing and watching, ending the stream of events.
+    pub fn cancel(&self) {
+        self.subscription.cancel()
+    }
+}
+
+enum Input {
+    Message(Result<PubSubMsg>),
+    Tick,
+}
+
+impl IpfsService {
+    /// Announce this node on the topic every interval and watch the other peers doing so.
+    ///
+    /// Heartbeats are signed by the nodes publishing them, their sender is the verified `from` of the message.
+    /// The stream of events drives both, poll it until the presence is cancelled.
+    pub async fn presence<T>(
+        &self,
+        topic: T,
+        config: PresenceConfig,
+    ) -> Result<(Presence, impl Stream<Item = Result<PresenceEvent>>)>
+    where
+        T: AsRef<[u8]>,
+    {
+        let topic = topic.as_ref().to_vec();
+
+        let (messages, subscription) = self.pubsub_sub(topic.clone()).await?;
+
+        let presence = Presence {
+            peers: Rc::default(),
+            subscription,
+        };
+
+        let heartbeat = Codec::Json.encode(&Heartbeat {
+            interval: config.interval.as_millis() as u64,
+        })?;
+
+        let ticks = {
+            let subscription = presence.subscription.clone();
+
+            // First heartbeat at once
+            stream::unfold(true, move |first| {
+                let active = subscription.is_active();
+
+                async move {
+                    if !first {
+                        futures_timer::Delay::new(config.interval).await;
+                    }
+
+                    active.then_some((Input::Tick, false))
+                }
+            })
+        };
+
+        let inputs = stream::select(messages.map(Input::Message), ticks);
+
+        let ipfs = self.clone();
+        let peers = presence.peers.clone();
+
+        let events = inputs
+            .then(move |input| {
+                let ipfs = ipfs.clone();
+                let peers = peers.clone();
+                let topic = topic.clone();
+                let heartbeat = heartbeat.clone();
+
+                async move {
+                    match input {
+                        Input::Message(Ok(msg)) => {
+                            let expires_at = match Codec::Json.decode::<Heartbeat>(&msg.data) {
+                                Ok(beat) => {
+                                    clock::now()
+                                        + Duration::from_millis(beat.interval) * config.missed
+                                }
+                                // Not a heartbeat
+                                Err(_) => return Vec::new(),
+                            };
+
+                            match peers.borrow_mut().insert(msg.from, expires_at) {
+                                Some(_) => Vec::new(),
+                                None => vec![Ok(PresenceEvent::Joined(msg.from))],
+                            }
+                        }
+                        Input::Message(Err(e)) => vec![Err(e)],
+                        Input::Tick => {
+                            let mut events = Vec::new();
+
+                            if let Err(e) = ipfs.pubsub_pub(&topic, heartbeat).await {
+                                events.push(Err(e));
+                            }
+
+                            let now = clock::now();
+
+                            peers.borrow_mut().retain(|peer, expires_at| {
+                                if *expires_at > now {
+                                    return true;
+                                }
+
+                                events.push(Ok(PresenceEvent::Left(*peer)));
+
+                                false
+                            });
+
+                            events
+                        }
+                    }
+                }
+            })
+            .flat_map(stream::iter);
+
+        Ok((presence, events))
+    }
+}
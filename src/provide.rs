@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use cid::Cid;
+
+use futures_util::{
+    future::{self, Either},
+    pin_mut, StreamExt,
+};
+
+use crate::{responses::Provider, IpfsService, Result};
+
+/// Providers asked of the DHT at most.
+const MAX_PROVIDERS: u32 = 20;
+
+/// Providers of a CID found on the DHT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvidersReport {
+    pub cid: Cid,
+
+    pub providers: Vec<Provider>,
+
+    /// The query was still running when the timeout elapsed.
+    pub timed_out: bool,
+}
+
+impl ProvidersReport {
+    /// Returns true if at least one peer provides the CID.
+    pub fn is_provided(&self) -> bool {
+        !self.providers.is_empty()
+    }
+}
+
+impl IpfsService {
+    /// Look for providers of this CID on the DHT for at most `timeout`, to verify the content can be found.
+    ///
+    /// Providers found before the timeout are reported, the timeout itself is not an error.
+    pub async fn is_provided(&self, cid: Cid, timeout: Duration) -> Result<ProvidersReport> {
+        let mut providers = Vec::new();
+
+        let timed_out = {
+            let query = async {
+                let stream = self.routing_findprovs(cid, MAX_PROVIDERS).await?;
+
+                pin_mut!(stream);
+
+                while let Some(provider) = stream.next().await {
+                    providers.push(provider?);
+                }
+
+                Ok::<_, Box<dyn std::error::Error>>(())
+            };
+
+            pin_mut!(query);
+
+            match future::select(query, futures_timer::Delay::new(timeout)).await {
+                Either::Left((res, _)) => {
+                    res?;
+
+                    false
+                }
+                Either::Right(_) => true,
+            }
+        };
+
+        let report = ProvidersReport {
+            cid,
+            providers,
+            timed_out,
+        };
+
+        Ok(report)
+    }
+}
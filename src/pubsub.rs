@@ -0,0 +1,521 @@
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    collections::{HashSet, VecDeque},
+    rc::Rc,
+    task::Poll,
+    time::Duration,
+};
+
+use bytes::Bytes;
+
+use cid::multibase::{encode, Base};
+
+use futures_util::{
+    future::{self, AbortHandle, AbortRegistration, Abortable},
+    stream::{self, LocalBoxStream},
+    Sink, Stream, StreamExt,
+};
+
+use reqwest::{
+    multipart::{Form, Part},
+    Response,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    abort::{InFlight, InFlightGuard},
+    backoff::Backoff,
+    body_stream,
+    capability::CompatMode,
+    codec::Codec,
+    ndjson::{json_stream, response_to_json_stream},
+    peer_id::PeerId,
+    pool::{no_node_available, IpfsPool, Node},
+    responses::{PubSubMsg, PubsubSubResponse},
+    runtime::compat,
+    IpfsService, Result,
+};
+
+/// Number of recent messages remembered when deduplicating pool subscriptions.
+const DEDUP_CAPACITY: usize = 1024;
+
+/// Subscription to a topic, shared by its clones.
+///
+/// Cancelling it or dropping its last clone aborts the request and ends the stream of messages.
+#[derive(Clone)]
+pub struct Subscription {
+    state: Rc<SubscriptionState>,
+}
+
+/// Former name of `Subscription`.
+pub type SubscriptionHandle = Subscription;
+
+struct SubscriptionState {
+    handle: AbortHandle,
+    ended: Cell<bool>,
+    _tracked: Vec<InFlightGuard>,
+}
+
+impl Drop for SubscriptionState {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+impl Subscription {
+    pub(crate) fn new(handle: AbortHandle) -> Self {
+        Self::tracked(handle, [])
+    }
+
+    /// Subscription also aborted when one of these services shuts down.
+    fn tracked<'a, I>(handle: AbortHandle, subscriptions: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Rc<InFlight>>,
+    {
+        let guards = subscriptions
+            .into_iter()
+            .map(|subscriptions| subscriptions.track(handle.clone()))
+            .collect();
+
+        let state = SubscriptionState {
+            handle,
+            ended: Cell::new(false),
+            _tracked: guards,
+        };
+
+        Self {
+            state: Rc::new(state),
+        }
+    }
+
+    /// Return the stream, marking the subscription inactive when it ends.
+    pub(crate) fn track<S>(&self, stream: S) -> impl Stream<Item = S::Item>
+    where
+        S: Stream,
+    {
+        let state = Rc::downgrade(&self.state);
+
+        let end = stream::poll_fn(move |_| {
+            if let Some(state) = state.upgrade() {
+                state.ended.set(true);
+            }
+
+            Poll::Ready(None)
+        });
+
+        stream.chain(end)
+    }
+
+    /// End the subscription stream.
+    pub fn cancel(&self) {
+        self.state.handle.abort();
+    }
+
+    /// False once cancelled or once the stream ended, e.g. if the node closed it.
+    pub fn is_active(&self) -> bool {
+        !self.state.handle.is_aborted() && !self.state.ended.get()
+    }
+}
+
+/// Item of a resilient pubsub subscription.
+pub enum PubsubEvent {
+    /// A message received on the topic.
+    Message(PubSubMsg),
+
+    /// The subscription was lost, a new one will be attempted after `delay`.
+    Reconnecting {
+        attempt: u32,
+        delay: Duration,
+
+        /// Cause of the disconnection, `None` if the node closed the stream.
+        error: Option<Box<dyn std::error::Error>>,
+    },
+
+    /// The subscription was re-established.
+    Reconnected,
+
+    /// An item could not be decoded or the node sent an error, the subscription goes on.
+    ///
+    /// Also the last item if the topic cannot be encoded for the node, see `CompatMode::Legacy`.
+    Error(Box<dyn std::error::Error>),
+}
+
+impl IpfsService {
+    /// Send data on the specified topic.
+    ///
+    /// In `CompatMode::Legacy` the data is sent as a text argument and must be UTF-8.
+    pub async fn pubsub_pub<T, D>(&self, topic: T, data: D) -> Result<()>
+    where
+        T: AsRef<[u8]>,
+        D: Into<Cow<'static, [u8]>>,
+    {
+        let url = self.base_url.join("pubsub/pub")?;
+
+        let mode = self.compat_mode().await;
+
+        let topic = encode_topic(mode, topic.as_ref())?;
+
+        let request = self.client.post(url).query(&[("arg", &topic)]);
+
+        let request = match mode {
+            CompatMode::Current => {
+                let part = Part::bytes(data);
+                let form = Form::new().part("data", part);
+
+                request.multipart(form)
+            }
+            CompatMode::Legacy => {
+                let data = String::from_utf8(data.into().into_owned())
+                    .map_err(|_| "Data must be UTF-8 for nodes older than go-ipfs 0.11")?;
+
+                request.query(&[("arg", &data)])
+            }
+        };
+
+        self.send(request).await?;
+
+        Ok(())
+    }
+
+    pub async fn pubsub_sub_response<T>(&self, topic: T) -> Result<Response>
+    where
+        T: AsRef<[u8]>,
+    {
+        let url = self.base_url.join("pubsub/sub")?;
+
+        let topic = encode_topic(self.compat_mode().await, topic.as_ref())?;
+
+        let request = self.client.post(url).query(&[("arg", topic)]);
+
+        let request = self.with_default_headers(request).send();
+
+        let (regis, _guard) = self.subscriptions.register();
+
+        let response = Abortable::new(compat(request), regis).await??;
+
+        Ok(response)
+    }
+
+    /// Subscribe to a topic until the subscription is cancelled or dropped.
+    pub async fn pubsub_sub<T>(
+        &self,
+        topic: T,
+    ) -> Result<(impl Stream<Item = Result<PubSubMsg>>, Subscription)>
+    where
+        T: AsRef<[u8]>,
+    {
+        let response = self.pubsub_sub_response(topic).await?;
+
+        let (handle, regis) = AbortHandle::new_pair();
+
+        let subscription = Subscription::tracked(handle, [&self.subscriptions]);
+
+        let stream = subscription.track(pubsub_sub_stream(response, regis));
+
+        Ok((stream, subscription))
+    }
+
+    /// Subscribe to a topic, re-subscribing with exponential backoff whenever the connection fails or ends.
+    pub fn pubsub_sub_resilient<T>(
+        &self,
+        topic: T,
+        backoff: Backoff,
+    ) -> impl Stream<Item = PubsubEvent>
+    where
+        T: AsRef<[u8]>,
+    {
+        let (regis, guard) = self.subscriptions.register();
+
+        let stream = resilient_stream(self.clone(), topic.as_ref().to_vec(), backoff);
+
+        Abortable::new(stream, regis).map(move |event| {
+            let _tracked = &guard;
+
+            event
+        })
+    }
+
+    /// Subscribe to a topic and return a typed sink and stream of messages encoded with codec.
+    ///
+    /// The stream ends when the subscription is cancelled or dropped, see `pubsub_sub`.
+    pub async fn pubsub_channel<T, U>(
+        &self,
+        topic: U,
+        codec: Codec,
+    ) -> Result<(
+        impl Sink<T, Error = Box<dyn std::error::Error>>,
+        impl Stream<Item = Result<T>>,
+        Subscription,
+    )>
+    where
+        T: Serialize + DeserializeOwned,
+        U: AsRef<[u8]>,
+    {
+        let topic = topic.as_ref().to_vec();
+
+        let response = self.pubsub_sub_response(&topic).await?;
+
+        let (handle, regis) = AbortHandle::new_pair();
+
+        let subscription = Subscription::tracked(handle, [&self.subscriptions]);
+
+        let stream = subscription
+            .track(pubsub_sub_stream(response, regis))
+            .map(move |item| item.and_then(|msg| codec.decode(&msg.data)));
+
+        let sink = futures_util::sink::unfold(
+            (self.clone(), topic),
+            move |(ipfs, topic), item: T| async move {
+                let data = codec.encode(&item)?;
+
+                ipfs.pubsub_pub(&topic, data).await?;
+
+                Ok((ipfs, topic))
+            },
+        );
+
+        Ok((sink, stream, subscription))
+    }
+}
+
+/// Topic as the node expects it, plain text before go-ipfs 0.11.
+fn encode_topic(mode: CompatMode, topic: &[u8]) -> Result<String> {
+    match mode {
+        CompatMode::Current => Ok(encode(Base::Base64Url, topic)),
+        CompatMode::Legacy => match std::str::from_utf8(topic) {
+            Ok(topic) => Ok(topic.to_owned()),
+            Err(_) => Err("Topics must be UTF-8 for nodes older than go-ipfs 0.11".into()),
+        },
+    }
+}
+
+pub fn pubsub_sub_stream(
+    response: Response,
+    regis: AbortRegistration,
+) -> impl Stream<Item = Result<PubSubMsg>> {
+    response_to_json_stream::<PubsubSubResponse>(response, regis).map(|item| Ok(item?.try_into()?))
+}
+
+pub(crate) fn pubsub_msg_stream<S>(stream: S) -> impl Stream<Item = Result<PubSubMsg>>
+where
+    S: Stream<Item = std::io::Result<Bytes>> + 'static,
+{
+    json_stream::<PubsubSubResponse, _>(stream).map(|item| Ok(item?.try_into()?))
+}
+
+impl IpfsPool {
+    /// Publish on the node of the topic, the one its sticky subscriptions use.
+    ///
+    /// The topic is assigned a node on first use, see `set_affinity`.
+    pub async fn pubsub_pub<T, D>(&self, topic: T, data: D) -> Result<()>
+    where
+        T: AsRef<[u8]>,
+        D: Into<Cow<'static, [u8]>>,
+    {
+        let node = self
+            .sticky_node(topic.as_ref())
+            .ok_or_else(no_node_available)?;
+
+        let active = node.begin();
+
+        let res = node.service.pubsub_pub(topic, data).await;
+
+        active.finish(&res, &self.breaker);
+
+        res
+    }
+
+    /// Subscribe to a topic on its node only, so that messages published through the pool round-trip.
+    pub async fn pubsub_sub_sticky<T>(
+        &self,
+        topic: T,
+    ) -> Result<(impl Stream<Item = Result<PubSubMsg>>, Subscription)>
+    where
+        T: AsRef<[u8]>,
+    {
+        let node = self
+            .sticky_node(topic.as_ref())
+            .ok_or_else(no_node_available)?;
+
+        let res = node.service.pubsub_sub(topic).await;
+
+        node.observe(&res, &self.breaker);
+
+        res
+    }
+
+    /// Subscribe to a topic on every reachable node and merge the messages, dropping duplicates.
+    pub async fn pubsub_sub<T>(
+        &self,
+        topic: T,
+    ) -> Result<(impl Stream<Item = Result<PubSubMsg>>, Subscription)>
+    where
+        T: AsRef<[u8]>,
+    {
+        let topic = topic.as_ref();
+
+        let nodes: Vec<Rc<Node>> = self
+            .snapshot()
+            .into_iter()
+            .filter(|node| node.allow(&self.breaker))
+            .collect();
+
+        let responses = future::join_all(
+            nodes
+                .iter()
+                .map(|node| node.service.pubsub_sub_response(topic)),
+        )
+        .await;
+
+        let mut streams = Vec::with_capacity(responses.len());
+        let mut subscribed = Vec::with_capacity(responses.len());
+        let mut last_error = None;
+
+        for (node, response) in nodes.into_iter().zip(responses) {
+            node.observe(&response, &self.breaker);
+
+            match response {
+                Ok(response) => {
+                    streams.push(pubsub_msg_stream(body_stream(response)).boxed_local());
+                    subscribed.push(node);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        if streams.is_empty() {
+            return Err(last_error.unwrap_or_else(no_node_available));
+        }
+
+        let mut seen = Seen::default();
+
+        let merged = stream::select_all(streams).filter(move |item| {
+            let keep = match item {
+                Ok(msg) => seen.insert(msg.from, msg.seqno.clone()),
+                Err(_) => true,
+            };
+
+            future::ready(keep)
+        });
+
+        let (handle, regis) = AbortHandle::new_pair();
+
+        let subscription = Subscription::tracked(
+            handle,
+            subscribed.iter().map(|node| &node.service.subscriptions),
+        );
+
+        let stream = subscription.track(Abortable::new(merged, regis));
+
+        Ok((stream, subscription))
+    }
+}
+
+enum State {
+    Start,
+    Connect(u32),
+    Streaming(LocalBoxStream<'static, Result<PubSubMsg>>),
+    Wait(u32, Duration),
+    Done,
+}
+
+pub(crate) fn resilient_stream(
+    ipfs: IpfsService,
+    topic: Vec<u8>,
+    backoff: Backoff,
+) -> impl Stream<Item = PubsubEvent> {
+    stream::unfold(State::Start, move |mut state| {
+        let ipfs = ipfs.clone();
+        let topic = topic.clone();
+
+        async move {
+            loop {
+                state = match state {
+                    // Subscribing again would fail the same way
+                    State::Start => match encode_topic(ipfs.compat_mode().await, &topic) {
+                        Ok(_) => State::Connect(0),
+                        Err(e) => return Some((PubsubEvent::Error(e), State::Done)),
+                    },
+                    State::Connect(attempt) => match ipfs.pubsub_sub_response(&topic).await {
+                        Ok(response) => {
+                            let stream = pubsub_msg_stream(body_stream(response)).boxed_local();
+
+                            if attempt > 0 {
+                                return Some((PubsubEvent::Reconnected, State::Streaming(stream)));
+                            }
+
+                            State::Streaming(stream)
+                        }
+                        Err(e) => return Some(reconnect(attempt, Some(e), &backoff)),
+                    },
+                    State::Streaming(mut stream) => match stream.next().await {
+                        Some(Ok(msg)) => {
+                            return Some((PubsubEvent::Message(msg), State::Streaming(stream)))
+                        }
+                        // Failures of the connection, the others are about one item
+                        Some(Err(e)) if e.is::<std::io::Error>() => {
+                            return Some(reconnect(0, Some(e), &backoff))
+                        }
+                        Some(Err(e)) => {
+                            return Some((PubsubEvent::Error(e), State::Streaming(stream)))
+                        }
+                        None => return Some(reconnect(0, None, &backoff)),
+                    },
+                    State::Wait(attempt, delay) => {
+                        futures_timer::Delay::new(delay).await;
+
+                        State::Connect(attempt)
+                    }
+                    State::Done => return None,
+                }
+            }
+        }
+    })
+}
+
+fn reconnect(
+    attempt: u32,
+    error: Option<Box<dyn std::error::Error>>,
+    backoff: &Backoff,
+) -> (PubsubEvent, State) {
+    let attempt = attempt + 1;
+    let delay = backoff.delay(attempt);
+
+    let event = PubsubEvent::Reconnecting {
+        attempt,
+        delay,
+        error,
+    };
+
+    (event, State::Wait(attempt, delay))
+}
+
+/// Bounded set of recently seen `(from, seqno)` pairs.
+#[derive(Default)]
+struct Seen {
+    set: HashSet<(PeerId, Vec<u8>)>,
+    order: VecDeque<(PeerId, Vec<u8>)>,
+}
+
+impl Seen {
+    /// Returns false if the message was already seen.
+    fn insert(&mut self, from: PeerId, seqno: Vec<u8>) -> bool {
+        let key = (from, seqno);
+
+        if !self.set.insert(key.clone()) {
+            return false;
+        }
+
+        self.order.push_back(key);
+
+        if self.order.len() > DEDUP_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.set.remove(&oldest);
+            }
+        }
+
+        true
+    }
+}
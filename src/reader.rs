@@ -0,0 +1,236 @@
+use std::{
+    io::{self, SeekFrom},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+
+use futures_util::{future::LocalBoxFuture, AsyncRead, AsyncSeek, FutureExt};
+
+use crate::{
+    path::IpfsPath,
+    responses::{FilesStatResponse, IPFSError},
+    IpfsService, Result,
+};
+
+/// Bytes requested at once when reading.
+const READ_AHEAD: u64 = 1024 * 1024;
+
+enum State {
+    Idle,
+    /// Range starting at this offset.
+    Reading(u64, LocalBoxFuture<'static, Result<Bytes>>),
+    /// Size of the file, to seek from its end by this much.
+    Sizing(i64, LocalBoxFuture<'static, Result<u64>>),
+}
+
+/// File at a path, read with ranged `cat` requests as it is read and seeked.
+///
+/// Data is requested in ranges of 1 MiB and the last range kept to serve small reads.
+pub struct UnixFsReader {
+    ipfs: IpfsService,
+    path: IpfsPath,
+    position: u64,
+    size: Option<u64>,
+    /// Last range read, with its offset.
+    buffer: (u64, Bytes),
+    state: State,
+}
+
+impl UnixFsReader {
+    pub fn new<P>(ipfs: IpfsService, path: P) -> Self
+    where
+        P: Into<IpfsPath>,
+    {
+        Self {
+            ipfs,
+            path: path.into(),
+            position: 0,
+            size: None,
+            buffer: (0, Bytes::new()),
+            state: State::Idle,
+        }
+    }
+
+    /// Copy what the buffer holds at the current position, if anything.
+    fn read_buffered(&mut self, buf: &mut [u8]) -> Option<usize> {
+        let (start, bytes) = &self.buffer;
+
+        let skip = self.position.checked_sub(*start)?;
+
+        if skip >= bytes.len() as u64 {
+            return None;
+        }
+
+        let available = &bytes[skip as usize..];
+        let len = available.len().min(buf.len());
+
+        buf[..len].copy_from_slice(&available[..len]);
+        self.position += len as u64;
+
+        Some(len)
+    }
+}
+
+impl AsyncRead for UnixFsReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        loop {
+            if let Some(len) = this.read_buffered(buf) {
+                return Poll::Ready(Ok(len));
+            }
+
+            if this.size.is_some_and(|size| this.position >= size) {
+                return Poll::Ready(Ok(0));
+            }
+
+            match &mut this.state {
+                State::Reading(offset, read) if *offset == this.position => {
+                    let bytes = match read.poll_unpin(cx) {
+                        Poll::Ready(res) => res.map_err(to_io)?,
+                        Poll::Pending => return Poll::Pending,
+                    };
+
+                    this.state = State::Idle;
+
+                    // A short range ends the file
+                    if (bytes.len() as u64) < READ_AHEAD {
+                        this.size = Some(this.position + bytes.len() as u64);
+                    }
+
+                    this.buffer = (this.position, bytes);
+                }
+                // Nothing requested at this position
+                _ => {
+                    let ipfs = this.ipfs.clone();
+                    let path = this.path.clone();
+                    let offset = this.position;
+
+                    let read = async move { ipfs.cat_range(path, offset, Some(READ_AHEAD)).await };
+
+                    this.state = State::Reading(offset, read.boxed_local());
+                }
+            }
+        }
+    }
+}
+
+impl AsyncSeek for UnixFsReader {
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        let this = self.get_mut();
+
+        let (base, delta) = match pos {
+            SeekFrom::Start(offset) => (0, offset as i64),
+            SeekFrom::Current(delta) => (this.position, delta),
+            SeekFrom::End(delta) => match (this.size, &mut this.state) {
+                (Some(size), _) => (size, delta),
+                (None, State::Sizing(pending, size)) if *pending == delta => {
+                    let size = match size.poll_unpin(cx) {
+                        Poll::Ready(res) => res.map_err(to_io)?,
+                        Poll::Pending => return Poll::Pending,
+                    };
+
+                    this.state = State::Idle;
+                    this.size = Some(size);
+
+                    (size, delta)
+                }
+                (None, _) => {
+                    let ipfs = this.ipfs.clone();
+                    let path = this.path.clone();
+
+                    let size = async move { ipfs.file_size(path).await };
+
+                    this.state = State::Sizing(delta, size.boxed_local());
+
+                    return Pin::new(this).poll_seek(cx, pos);
+                }
+            },
+        };
+
+        let position = match base.checked_add_signed(delta) {
+            Some(position) => position,
+            None => {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Seek before the start of the file",
+                )))
+            }
+        };
+
+        this.position = position;
+
+        Poll::Ready(Ok(position))
+    }
+}
+
+fn to_io(error: Box<dyn std::error::Error>) -> io::Error {
+    io::Error::other(error.to_string())
+}
+
+impl IpfsService {
+    /// Download `length` bytes of the content at this path from `offset`, or up to the end if `None`.
+    pub async fn cat_range<P>(&self, path: P, offset: u64, length: Option<u64>) -> Result<Bytes>
+    where
+        P: Into<IpfsPath>,
+    {
+        let path = path.into();
+
+        let url = self.base_url.join("cat")?;
+
+        let mut request = self
+            .post_fetch(url)
+            .query(&[("arg", &path.to_string())])
+            .query(&[("offset", offset)]);
+
+        if let Some(length) = length {
+            request = request.query(&[("length", length)]);
+        }
+
+        self.send_idempotent(request).await
+    }
+
+    /// Size in bytes of the file at this path.
+    pub async fn file_size<P>(&self, path: P) -> Result<u64>
+    where
+        P: Into<IpfsPath>,
+    {
+        let path = path.into();
+
+        let url = self.base_url.join("files/stat")?;
+
+        let request = self.client.post(url).query(&[("arg", &path.to_string())]);
+
+        let bytes = self.send_idempotent(request).await?;
+
+        if let Ok(res) = serde_json::from_slice::<FilesStatResponse>(&bytes) {
+            return Ok(res.size);
+        }
+
+        let error = serde_json::from_slice::<IPFSError>(&bytes)?;
+
+        Err(error.into())
+    }
+
+    /// Read the file at this path with `AsyncRead` and `AsyncSeek`, see `UnixFsReader`.
+    pub fn unixfs_reader<P>(&self, path: P) -> UnixFsReader
+    where
+        P: Into<IpfsPath>,
+    {
+        UnixFsReader::new(self.clone(), path)
+    }
+}
@@ -0,0 +1,47 @@
+use std::borrow::Cow;
+
+use reqwest::{header::HeaderName, Url};
+
+/// Replaces secrets in debug output, errors and traces.
+pub(crate) const REDACTED: &str = "redacted";
+
+/// Headers carrying credentials.
+const SENSITIVE_HEADERS: [&str; 4] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-api-key",
+];
+
+/// Returns true if the value of this header is a secret.
+pub(crate) fn is_sensitive(name: &HeaderName) -> bool {
+    SENSITIVE_HEADERS.contains(&name.as_str())
+}
+
+/// The URL with its password, if any, redacted.
+pub(crate) fn url(url: &Url) -> Cow<'_, Url> {
+    if url.password().is_none() {
+        return Cow::Borrowed(url);
+    }
+
+    let mut url = url.clone();
+    let _ = url.set_password(Some(REDACTED));
+
+    Cow::Owned(url)
+}
+
+/// The error with the password of its URL redacted, if it has one.
+pub(crate) fn error(error: Box<dyn std::error::Error>) -> Box<dyn std::error::Error> {
+    let mut error = match error.downcast::<reqwest::Error>() {
+        Ok(error) => error,
+        Err(error) => return error,
+    };
+
+    if let Some(url) = error.url_mut() {
+        if let Cow::Owned(redacted) = self::url(url) {
+            *url = redacted;
+        }
+    }
+
+    error
+}
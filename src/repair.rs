@@ -0,0 +1,134 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    rc::Rc,
+    task::{Poll, Waker},
+};
+
+use cid::Cid;
+
+use futures_util::{stream, Stream, StreamExt};
+
+use reqwest::Url;
+
+use crate::{
+    pool::{IpfsPool, Node, NodeOutcome},
+    replication::holds,
+};
+
+/// Pin content read from one pool node on the nodes missing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadRepair {
+    /// Reads repaired at once.
+    pub concurrency: usize,
+
+    /// Reads waiting for repair at most, further reads are not repaired.
+    pub max_pending: usize,
+}
+
+impl Default for ReadRepair {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            max_pending: 1024,
+        }
+    }
+}
+
+/// Reads waiting for repair, with the node that served them.
+#[derive(Default)]
+pub(crate) struct RepairQueue {
+    pending: RefCell<VecDeque<(Cid, Url)>>,
+    waker: RefCell<Option<Waker>>,
+}
+
+impl IpfsPool {
+    /// Queue the CID read from this node for repair, if enabled.
+    pub(crate) fn queue_repair(&self, cid: Cid, source: &Node) {
+        let config = match self.read_repair {
+            Some(config) => config,
+            None => return,
+        };
+
+        let mut pending = self.repairs.pending.borrow_mut();
+
+        if pending.len() >= config.max_pending || pending.iter().any(|(queued, _)| *queued == cid) {
+            return;
+        }
+
+        pending.push_back((cid, source.service.url().clone()));
+
+        if let Some(waker) = self.repairs.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Number of reads waiting for repair.
+    pub fn pending_repairs(&self) -> usize {
+        self.repairs.pending.borrow().len()
+    }
+
+    /// Repair the reads of `cat` and `dag_get`, once `with_read_repair` is set.
+    ///
+    /// Nodes not holding the block read are found with an offline `block_stat` and asked to pin it.
+    /// Yields the outcome of every pin. The stream never ends, spawn it on a local executor and drop it to stop.
+    pub fn read_repair(&self) -> impl Stream<Item = (Cid, NodeOutcome)> {
+        let pool = self.clone();
+        let queue = self.repairs.clone();
+
+        let concurrency = self
+            .read_repair
+            .map(|config| config.concurrency)
+            .unwrap_or(1)
+            .max(1);
+
+        stream::poll_fn(move |cx| match queue.pending.borrow_mut().pop_front() {
+            Some(read) => Poll::Ready(Some(read)),
+            None => {
+                *queue.waker.borrow_mut() = Some(cx.waker().clone());
+
+                Poll::Pending
+            }
+        })
+        .map(move |(cid, source)| {
+            let pool = pool.clone();
+
+            async move { pool.repair(cid, &source).await }
+        })
+        .buffer_unordered(concurrency)
+        .flat_map(stream::iter)
+    }
+
+    /// Pin the CID on the available nodes other than the source that do not hold it.
+    async fn repair(&self, cid: Cid, source: &Url) -> Vec<(Cid, NodeOutcome)> {
+        let nodes = self
+            .snapshot()
+            .into_iter()
+            .filter(|node| node.service.url() != source && node.allow(&self.breaker));
+
+        let outcomes = futures_util::future::join_all(nodes.map(|node| self.repair_on(node, cid)));
+
+        outcomes.await.into_iter().flatten().collect()
+    }
+
+    async fn repair_on(&self, node: Rc<Node>, cid: Cid) -> Option<(Cid, NodeOutcome)> {
+        let active = node.begin();
+        let res = holds(&node.service, cid).await;
+        active.finish(&res, &self.breaker);
+
+        if !matches!(res, Ok(false)) {
+            return None;
+        }
+
+        let active = node.begin();
+        let res = node.service.pin_add(cid, true).await;
+        active.finish(&res, &self.breaker);
+
+        let outcome = NodeOutcome {
+            url: node.service.url().clone(),
+            result: res.map(|_| ()),
+        };
+
+        Some((cid, outcome))
+    }
+}
@@ -0,0 +1,414 @@
+use core::fmt;
+
+use std::{collections::HashMap, future::Future, rc::Rc};
+
+use bytes::Bytes;
+
+use cid::Cid;
+
+use futures_util::{
+    future,
+    stream::{self, FuturesUnordered},
+    StreamExt,
+};
+
+use reqwest::Url;
+
+use serde::Serialize;
+
+use crate::{
+    api::IpfsApi,
+    breaker::CircuitBreakerConfig,
+    pool::{is_node_failure, IpfsPool, Node, NodeOutcome},
+    responses::{PinAddResponse, PinList, PinType},
+    IpfsService, Result,
+};
+
+/// Per node outcome of a replicated pin.
+#[derive(Debug)]
+pub struct PinReplication {
+    pub cid: Cid,
+
+    /// Nodes asked to pin, in order of completion.
+    pub outcomes: Vec<NodeOutcome>,
+}
+
+impl PinReplication {
+    /// Number of nodes that confirmed the pin.
+    pub fn replicas(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.result.is_ok())
+            .count()
+    }
+}
+
+/// Returned when fewer nodes than requested confirmed a replicated pin.
+pub struct ReplicationError {
+    pub required: usize,
+    pub report: PinReplication,
+}
+
+impl std::error::Error for ReplicationError {}
+
+impl fmt::Display for ReplicationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Pinned {} on {} of {} required nodes",
+            self.report.cid,
+            self.report.replicas(),
+            self.required
+        )
+    }
+}
+
+impl fmt::Debug for ReplicationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Summary of a pinset synchronization.
+#[derive(Default)]
+pub struct PinSyncReport {
+    /// Direct and recursive pins found on the source.
+    pub source_pins: usize,
+
+    /// Pins the target already had.
+    pub already_pinned: usize,
+
+    /// Pins added to the target.
+    pub pinned: Vec<Cid>,
+
+    pub failed: Vec<(Cid, Box<dyn std::error::Error>)>,
+}
+
+/// Pin on target every direct and recursive pin of source it is missing.
+///
+/// At most `concurrency` pin requests are in flight at once.
+pub async fn sync_pins(
+    source: &IpfsService,
+    target: &IpfsService,
+    concurrency: usize,
+) -> Result<PinSyncReport> {
+    let (source_pins, target_pins) =
+        future::try_join(source.pin_ls(None), target.pin_ls(None)).await?;
+
+    let mut report = PinSyncReport::default();
+    let mut missing = Vec::new();
+
+    for (cid, pin_type) in source_pins {
+        if pin_type == PinType::Indirect {
+            continue;
+        }
+
+        report.source_pins += 1;
+
+        match target_pins.get(&cid) {
+            Some(PinType::Recursive) => report.already_pinned += 1,
+            Some(target_type) if *target_type == pin_type => report.already_pinned += 1,
+            _ => missing.push((cid, pin_type)),
+        }
+    }
+
+    let mut results = stream::iter(missing)
+        .map(|(cid, pin_type)| async move {
+            let recursive = pin_type == PinType::Recursive;
+
+            (cid, target.pin_add(cid, recursive).await)
+        })
+        .buffer_unordered(concurrency.max(1));
+
+    while let Some((cid, res)) = results.next().await {
+        match res {
+            Ok(_) => report.pinned.push(cid),
+            Err(e) => report.failed.push((cid, e)),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Replication state of recursive pins across the pool.
+#[derive(Debug, Default)]
+pub struct PinDiffReport {
+    /// Nodes holding each recursive pin.
+    pub holders: HashMap<Cid, Vec<Url>>,
+
+    /// Pinned on fewer nodes than the replication factor.
+    pub under_replicated: Vec<Cid>,
+
+    /// Pinned on more nodes than the replication factor.
+    pub over_replicated: Vec<Cid>,
+
+    /// Expected CIDs pinned nowhere.
+    pub missing: Vec<Cid>,
+
+    /// Nodes whose pins could not be listed.
+    pub failed: Vec<NodeOutcome>,
+}
+
+/// Progress of the background replication of a write.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplicationStatus {
+    /// Nodes still pinning.
+    pub pending: Vec<Url>,
+
+    /// Nodes that pinned.
+    pub replicated: Vec<Url>,
+
+    /// Nodes that failed to pin.
+    pub failed: Vec<Url>,
+}
+
+impl ReplicationStatus {
+    /// Returns true once every node answered.
+    pub fn is_complete(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+impl IpfsPool {
+    /// Add the data on the first available node, returning once it confirmed.
+    ///
+    /// Another node is only tried if the previous one could not be connected to, not to add the data twice.
+    /// The returned future pins the CID on this node, then on every other available node at once,
+    /// these fetching the blocks from it. Spawn it on a local executor, progress is reported by `replication_status`.
+    pub async fn add_write_through(
+        &self,
+        bytes: Bytes,
+    ) -> Result<(Cid, impl Future<Output = PinReplication>)> {
+        let (primary, cid) = self
+            .call_routed_node(self.route(), false, |ipfs| {
+                let bytes = bytes.clone();
+
+                async move { IpfsApi::add(&ipfs, bytes).await }
+            })
+            .await?;
+
+        Ok((cid, self.replicate(primary, cid)))
+    }
+
+    /// Put the dag node on the first available node, see `add_write_through`.
+    pub async fn dag_put_write_through<T>(
+        &self,
+        node: &T,
+    ) -> Result<(Cid, impl Future<Output = PinReplication>)>
+    where
+        T: ?Sized + Serialize,
+    {
+        let (primary, cid) = self
+            .call_routed_node(self.route(), false, |ipfs| async move {
+                ipfs.dag_put(node).await
+            })
+            .await?;
+
+        Ok((cid, self.replicate(primary, cid)))
+    }
+
+    /// Whether each node holds the block, without fetching it.
+    ///
+    /// Every node is asked, regardless of health or circuit state. Unreachable nodes report an error.
+    pub async fn availability(&self, cid: Cid) -> Vec<NodeOutcome<bool>> {
+        self.call_all(|ipfs| async move { holds(&ipfs, cid).await })
+            .await
+    }
+
+    /// Progress of the last write-through replication of this CID.
+    pub fn replication_status(&self, cid: &Cid) -> Option<ReplicationStatus> {
+        self.replication.borrow().get(cid).cloned()
+    }
+
+    /// Stop reporting the replication of this CID.
+    pub fn forget_replication(&self, cid: &Cid) {
+        self.replication.borrow_mut().remove(cid);
+    }
+
+    fn replicate(&self, primary: Rc<Node>, cid: Cid) -> impl Future<Output = PinReplication> {
+        let others: Vec<Rc<Node>> = self
+            .snapshot()
+            .into_iter()
+            .filter(|node| !Rc::ptr_eq(node, &primary) && node.allow(&self.breaker))
+            .collect();
+
+        let status = ReplicationStatus {
+            pending: std::iter::once(&primary)
+                .chain(others.iter())
+                .map(|node| node.service.url().clone())
+                .collect(),
+            ..Default::default()
+        };
+
+        self.replication.borrow_mut().insert(cid, status);
+
+        let pool = self.clone();
+
+        async move {
+            let record = |node: Rc<Node>, res: Result<_>| {
+                let url = node.service.url().clone();
+
+                if let Some(status) = pool.replication.borrow_mut().get_mut(&cid) {
+                    status.pending.retain(|pending| *pending != url);
+
+                    match res {
+                        Ok(_) => status.replicated.push(url.clone()),
+                        Err(_) => status.failed.push(url.clone()),
+                    }
+                }
+
+                NodeOutcome {
+                    url,
+                    result: res.map(|_| ()),
+                }
+            };
+
+            let mut outcomes = Vec::with_capacity(others.len() + 1);
+
+            // The others fetch the blocks from the primary, once it holds them all
+            let (node, res) = pin_on(primary, cid, &pool.breaker).await;
+
+            outcomes.push(record(node, res));
+
+            let mut pending: FuturesUnordered<_> = others
+                .into_iter()
+                .map(|node| pin_on(node, cid, &pool.breaker))
+                .collect();
+
+            while let Some((node, res)) = pending.next().await {
+                outcomes.push(record(node, res));
+            }
+
+            PinReplication { cid, outcomes }
+        }
+    }
+
+    /// Recursively pin a CID on `replication_factor` nodes in parallel.
+    ///
+    /// Nodes failing to pin are replaced by the next available ones.
+    /// Fails with a `ReplicationError` if not enough nodes confirmed.
+    pub async fn pin_add_replicated(
+        &self,
+        cid: Cid,
+        replication_factor: usize,
+    ) -> Result<PinReplication> {
+        let mut candidates = self
+            .route_for(Some(&cid))
+            .into_iter()
+            .filter(|node| node.allow(&self.breaker));
+
+        let mut pending = FuturesUnordered::new();
+
+        for node in candidates.by_ref().take(replication_factor) {
+            pending.push(pin_on(node, cid, &self.breaker));
+        }
+
+        let mut outcomes = Vec::with_capacity(replication_factor);
+
+        while let Some((node, res)) = pending.next().await {
+            if res.is_err() {
+                if let Some(next) = candidates.next() {
+                    pending.push(pin_on(next, cid, &self.breaker));
+                }
+            }
+
+            outcomes.push(NodeOutcome {
+                url: node.service.url().clone(),
+                result: res.map(|_| ()),
+            });
+        }
+
+        let report = PinReplication { cid, outcomes };
+
+        if report.replicas() < replication_factor {
+            let error = ReplicationError {
+                required: replication_factor,
+                report,
+            };
+
+            return Err(error.into());
+        }
+
+        Ok(report)
+    }
+
+    /// Compare recursive pins of every node against the replication factor.
+    ///
+    /// CIDs in `expected` that no node pins are reported as missing.
+    /// Nodes that are down or whose circuit is open are not asked, and reported as failed.
+    pub async fn pin_diff<I>(&self, replication_factor: usize, expected: I) -> PinDiffReport
+    where
+        I: IntoIterator<Item = Cid>,
+    {
+        let nodes = self.snapshot();
+
+        let lists = future::join_all(nodes.iter().map(|node| pin_ls_on(node, &self.breaker))).await;
+
+        let mut report = PinDiffReport::default();
+
+        for (node, list) in nodes.iter().zip(lists) {
+            let url = node.service.url();
+
+            match list {
+                Ok(list) => {
+                    for cid in list.into_keys() {
+                        report.holders.entry(cid).or_default().push(url.clone());
+                    }
+                }
+                Err(e) => report.failed.push(NodeOutcome {
+                    url: url.clone(),
+                    result: Err(e),
+                }),
+            }
+        }
+
+        for (cid, holders) in report.holders.iter() {
+            if holders.len() < replication_factor {
+                report.under_replicated.push(*cid);
+            } else if holders.len() > replication_factor {
+                report.over_replicated.push(*cid);
+            }
+        }
+
+        report.missing = expected
+            .into_iter()
+            .filter(|cid| !report.holders.contains_key(cid))
+            .collect();
+
+        report
+    }
+}
+
+async fn pin_on(
+    node: Rc<Node>,
+    cid: Cid,
+    breaker: &CircuitBreakerConfig,
+) -> (Rc<Node>, Result<PinAddResponse>) {
+    let active = node.begin();
+    let res = node.service.pin_add(cid, true).await;
+    active.finish(&res, breaker);
+
+    (node, res)
+}
+
+async fn pin_ls_on(node: &Rc<Node>, breaker: &CircuitBreakerConfig) -> Result<PinList> {
+    if !node.allow(breaker) {
+        return Err("Node is down or its circuit is open".into());
+    }
+
+    let active = node.begin();
+    let res = node.service.pin_ls(Some(PinType::Recursive)).await;
+    active.finish(&res, breaker);
+
+    res
+}
+
+/// Returns true if the node has the block locally.
+pub(crate) async fn holds(ipfs: &IpfsService, cid: Cid) -> Result<bool> {
+    match ipfs.with_offline(true).block_stat(cid).await {
+        Ok(_) => Ok(true),
+        Err(e) if is_node_failure(e.as_ref()) => Err(e),
+        // Not found locally
+        Err(_) => Ok(false),
+    }
+}
@@ -0,0 +1,133 @@
+use std::{future::Future, rc::Rc, time::Duration};
+
+use cid::Cid;
+
+use futures_util::{
+    future::{self, LocalBoxFuture},
+    stream, FutureExt, Stream, StreamExt,
+};
+
+use crate::{responses::NamePublishResponse, IpfsService, Result};
+
+type Resolver = Rc<dyn Fn() -> LocalBoxFuture<'static, Result<Cid>>>;
+
+/// What a name is republished with.
+#[derive(Clone)]
+enum Target {
+    /// The value the name currently resolves to.
+    Current,
+    Fixed(Cid),
+    Resolver(Resolver),
+}
+
+/// Republish IPNS names periodically, before their records lapse.
+///
+/// Records are published with a lifetime of 6 months but dropped by DHT peers after a day or two.
+#[derive(Clone)]
+pub struct Republisher {
+    ipfs: IpfsService,
+    interval: Duration,
+    names: Vec<(String, Target)>,
+}
+
+impl Republisher {
+    /// Republish every `interval`, a few hours is enough to stay resolvable.
+    pub fn new(ipfs: IpfsService, interval: Duration) -> Self {
+        Self {
+            ipfs,
+            interval,
+            names: Vec::new(),
+        }
+    }
+
+    /// Republish the name of this key with the value it currently resolves to.
+    pub fn with_name<U>(&self, key: U) -> Self
+    where
+        U: Into<String>,
+    {
+        self.with_target(key.into(), Target::Current)
+    }
+
+    /// Republish the name of this key pointing to this CID.
+    pub fn with_name_cid<U>(&self, key: U, cid: Cid) -> Self
+    where
+        U: Into<String>,
+    {
+        self.with_target(key.into(), Target::Fixed(cid))
+    }
+
+    /// Republish the name of this key pointing to the CID returned by `resolver`, called every time.
+    pub fn with_name_resolver<U, F, Fut>(&self, key: U, resolver: F) -> Self
+    where
+        U: Into<String>,
+        F: Fn() -> Fut + 'static,
+        Fut: Future<Output = Result<Cid>> + 'static,
+    {
+        let resolver: Resolver = Rc::new(move || resolver().boxed_local());
+
+        self.with_target(key.into(), Target::Resolver(resolver))
+    }
+
+    fn with_target(&self, key: String, target: Target) -> Self {
+        let mut names = self.names.clone();
+        names.retain(|(name, _)| *name != key);
+        names.push((key, target));
+
+        Self {
+            names,
+            ..self.clone()
+        }
+    }
+
+    /// Republish every name now then after each interval, yielding the outcome per key.
+    ///
+    /// The stream never ends, spawn it on a local executor and drop it to stop.
+    pub fn run(self) -> impl Stream<Item = (String, Result<NamePublishResponse>)> {
+        let republisher = Rc::new(self);
+
+        stream::unfold((republisher, true), |(republisher, first)| async move {
+            if !first {
+                futures_timer::Delay::new(republisher.interval).await;
+            }
+
+            let outcomes = future::join_all(
+                republisher
+                    .names
+                    .iter()
+                    .map(|(key, target)| republisher.republish(key, target)),
+            )
+            .await;
+
+            Some((stream::iter(outcomes), (republisher, false)))
+        })
+        .flatten()
+    }
+
+    async fn republish(&self, key: &str, target: &Target) -> (String, Result<NamePublishResponse>) {
+        let res = async {
+            let cid = match target {
+                Target::Current => self.current(key).await?,
+                Target::Fixed(cid) => *cid,
+                Target::Resolver(resolver) => resolver().await?,
+            };
+
+            self.ipfs.name_publish(cid, key.to_owned()).await
+        };
+
+        (key.to_owned(), res.await)
+    }
+
+    /// CID the name of this key resolves to.
+    async fn current(&self, key: &str) -> Result<Cid> {
+        let keys = self.ipfs.key_list().await?;
+
+        let info = keys
+            .into_iter()
+            .find(|info| info.name == key)
+            .ok_or_else(|| format!("No key named {}", key))?;
+
+        let name = Cid::try_from(info.ipns_name.as_str())?;
+
+        self.ipfs.name_resolve_cid(name).await
+    }
+}
@@ -1,19 +1,41 @@
 use core::fmt;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, str::FromStr, time::Duration};
 
+use cid::Cid;
+
+#[cfg(feature = "pubsub")]
 use cid::{
     multibase::{decode, Base},
     multihash::MultihashGeneric,
-    Cid,
 };
 
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    base::IpnsBase,
+    path::{IpfsPath, PathError},
+    peer_id::PeerId,
+};
+
 #[derive(Debug, Deserialize)]
 pub struct AddResponse {
+    #[serde(rename = "Name", default)]
+    pub name: String,
+
     #[serde(rename = "Hash")]
     pub hash: String,
+
+    /// Cumulative size as a decimal string.
+    #[serde(rename = "Size", default)]
+    pub size: String,
+
+    /// POSIX mode as an octal string, if stored.
+    #[serde(rename = "Mode", default)]
+    pub mode: String,
+
+    #[serde(rename = "Mtime", default)]
+    pub mtime: Option<i64>,
 }
 
 impl TryFrom<AddResponse> for Cid {
@@ -24,32 +46,108 @@ impl TryFrom<AddResponse> for Cid {
     }
 }
 
+/// File or directory created by an add.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddEntry {
+    /// Path relative to the added root, empty for a wrapping directory.
+    pub name: String,
+
+    pub cid: Cid,
+
+    /// Cumulative size of the DAG in bytes.
+    pub size: u64,
+
+    /// POSIX mode, if stored.
+    pub mode: Option<u32>,
+
+    /// Modification time in seconds since the Unix epoch, if stored.
+    pub mtime: Option<i64>,
+}
+
+impl TryFrom<AddResponse> for AddEntry {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(response: AddResponse) -> Result<Self, Self::Error> {
+        let AddResponse {
+            name,
+            hash,
+            size,
+            mode,
+            mtime,
+        } = response;
+
+        let size = match size.as_str() {
+            "" => 0,
+            size => size.parse()?,
+        };
+
+        let mode = match mode.as_str() {
+            "" => None,
+            mode => Some(u32::from_str_radix(mode, 8)?),
+        };
+
+        Ok(Self {
+            name,
+            cid: Cid::try_from(hash)?,
+            size,
+            mode,
+            mtime,
+        })
+    }
+}
+
+/// Every entry of an add, in the order the node reported them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddOutput {
+    pub entries: Vec<AddEntry>,
+
+    /// CID of the last entry, the wrapping directory if any.
+    pub root: Cid,
+}
+
+#[cfg(feature = "pubsub")]
 #[derive(Deserialize)]
 pub struct PubsubSubResponse {
     pub from: String,
     pub data: String,
+    pub seqno: String,
 }
 
+#[cfg(feature = "pubsub")]
 pub struct PubSubMsg {
-    pub from: Cid,
+    pub from: PeerId,
     pub data: Vec<u8>,
+    pub seqno: Vec<u8>,
 }
 
+#[cfg(feature = "pubsub")]
 impl TryFrom<PubsubSubResponse> for PubSubMsg {
     type Error = cid::Error;
 
     fn try_from(response: PubsubSubResponse) -> Result<Self, Self::Error> {
-        let PubsubSubResponse { from, data } = response;
-
-        //Use Peer ID as CID v1 instead of multihash btc58 encoded
-        // https://github.com/libp2p/specs/blob/master/peer-ids/peer-ids.md#string-representation
-        let decoded = Base::Base58Btc.decode(from)?;
-        let multihash = MultihashGeneric::from_bytes(&decoded)?;
-        let cid = Cid::new_v1(0x70, multihash);
+        let PubsubSubResponse { from, data, seqno } = response;
+
+        // Before go-ipfs 0.11, the peer id bytes and the data are plain base64.
+        let from = match from.parse() {
+            Ok(from) => from,
+            Err(_) => {
+                let bytes = Base::Base64Pad.decode(from)?;
+
+                let data = Base::Base64Pad.decode(data)?;
+                let seqno = Base::Base64Pad.decode(seqno)?;
+
+                return Ok(Self {
+                    from: PeerId::from_multihash(MultihashGeneric::from_bytes(&bytes)?),
+                    data,
+                    seqno,
+                });
+            }
+        };
 
         let (_, data) = decode(data)?;
+        let (_, seqno) = decode(seqno)?;
 
-        Ok(Self { from: cid, data })
+        Ok(Self { from, data, seqno })
     }
 }
 
@@ -59,7 +157,7 @@ pub struct DagPutResponse {
     pub cid: CidString,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct CidString {
     #[serde(rename = "/")]
     pub cid_string: String,
@@ -73,6 +171,30 @@ impl TryFrom<DagPutResponse> for Cid {
     }
 }
 
+#[derive(Deserialize)]
+pub struct DagImportResponse {
+    #[serde(rename = "Root")]
+    pub root: DagImportRoot,
+}
+
+#[derive(Deserialize)]
+pub struct DagImportRoot {
+    #[serde(rename = "Cid")]
+    pub cid: CidString,
+
+    #[serde(rename = "PinErrorMsg", default)]
+    pub pin_error_msg: String,
+}
+
+#[derive(Deserialize)]
+pub struct RefsResponse {
+    #[serde(rename = "Ref")]
+    pub cid: String,
+
+    #[serde(rename = "Err", default)]
+    pub error: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct NamePublishResponse {
     ///IPNS Name
@@ -84,20 +206,64 @@ pub struct NamePublishResponse {
     pub value: String,
 }
 
+/// Record published by `name_publish_with`, as `name_publish` does by default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamePublishOptions {
+    /// How long the record is valid, 6 months by default.
+    pub lifetime: Duration,
+
+    /// How long resolvers may cache the record, the node default if `None`.
+    pub ttl: Option<Duration>,
+
+    /// Also sign the V1 fields read by older nodes, or only sign V2 if `false`. The node default if `None`.
+    pub v1_compat: Option<bool>,
+
+    /// Publish with no connection to the network, only storing the record locally.
+    pub allow_offline: bool,
+}
+
+impl Default for NamePublishOptions {
+    fn default() -> Self {
+        Self {
+            lifetime: Duration::from_secs(4320 * 3600),
+            ttl: None,
+            v1_compat: None,
+            allow_offline: false,
+        }
+    }
+}
+
+impl NamePublishResponse {
+    /// Key of the name, whatever its base.
+    pub fn peer_id(&self) -> Result<PeerId, cid::Error> {
+        PeerId::from_str(&self.name)
+    }
+}
+
 #[derive(Deserialize)]
 pub struct NameResolveResponse {
     #[serde(rename = "Path")]
     pub path: String,
 }
 
-impl TryFrom<NameResolveResponse> for Cid {
-    type Error = cid::Error;
+impl TryFrom<NameResolveResponse> for IpfsPath {
+    type Error = PathError;
 
     fn try_from(response: NameResolveResponse) -> Result<Self, Self::Error> {
-        Cid::try_from(response.path)
+        response.path.parse()
     }
 }
 
+#[derive(Deserialize)]
+pub struct DagResolveResponse {
+    #[serde(rename = "Cid")]
+    pub cid: CidString,
+
+    /// Path left to resolve inside the block.
+    #[serde(rename = "RemPath", default)]
+    pub rem_path: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct KeyListResponse {
     #[serde(rename = "Keys")]
@@ -113,43 +279,509 @@ pub struct KeyPair {
     pub name: String,
 }
 
-pub type KeyList = HashMap<String, Cid>;
+/// IPNS key of a node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyInfo {
+    pub name: String,
+
+    /// IPNS name in base36, the `k51...` form shown by browsers, see `ipns_name_in` for other bases.
+    pub ipns_name: String,
 
-impl TryFrom<KeyListResponse> for KeyList {
+    pub peer_id: PeerId,
+}
+
+impl KeyInfo {
+    /// IPNS name as a libp2p-key CID.
+    pub fn cid(&self) -> Cid {
+        self.peer_id.to_cid()
+    }
+
+    /// IPNS name in this base, e.g. base32 as in subdomain gateway URLs.
+    pub fn ipns_name_in(&self, base: IpnsBase) -> String {
+        self.peer_id.to_ipns_name(base)
+    }
+}
+
+impl TryFrom<KeyPair> for KeyInfo {
+    type Error = cid::Error;
+
+    fn try_from(keypair: KeyPair) -> Result<Self, Self::Error> {
+        let KeyPair { id, name } = keypair;
+
+        let peer_id = PeerId::from_str(&id)?;
+
+        Ok(Self {
+            name,
+            ipns_name: peer_id.to_ipns_name(IpnsBase::Base36),
+            peer_id,
+        })
+    }
+}
+
+impl TryFrom<KeyListResponse> for Vec<KeyInfo> {
     type Error = cid::Error;
 
     fn try_from(response: KeyListResponse) -> Result<Self, Self::Error> {
-        let map = response.keys.into_iter().filter_map(|keypair| {
-            let KeyPair { id, name } = keypair;
+        response.keys.into_iter().map(KeyInfo::try_from).collect()
+    }
+}
 
-            match Cid::try_from(id) {
-                Ok(cid) => Some((name, cid)),
-                Err(_) => None,
-            }
-        });
+/// IPNS key CIDs by key name.
+pub type KeyList = HashMap<String, Cid>;
 
-        Ok(HashMap::from_iter(map))
+impl FromIterator<KeyInfo> for KeyList {
+    fn from_iter<I: IntoIterator<Item = KeyInfo>>(iter: I) -> Self {
+        iter.into_iter()
+            .map(|key| (key.name, key.peer_id.to_cid()))
+            .collect()
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Deserialize)]
+pub struct KeySignResponse {
+    #[serde(rename = "Key")]
+    pub key: KeyPair,
+
+    /// Multibase encoded signature.
+    #[serde(rename = "Signature")]
+    pub signature: String,
+}
+
+/// Signature of some data by an IPNS key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySignature {
+    pub key: KeyInfo,
+    pub signature: Vec<u8>,
+}
+
+impl TryFrom<KeySignResponse> for KeySignature {
+    type Error = cid::Error;
+
+    fn try_from(response: KeySignResponse) -> Result<Self, Self::Error> {
+        let (_, signature) = cid::multibase::decode(response.signature)?;
+
+        Ok(Self {
+            key: response.key.try_into()?,
+            signature,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KeyVerifyResponse {
+    #[serde(rename = "SignatureValid")]
+    pub signature_valid: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockStat {
+    #[serde(rename = "Key")]
+    pub key: String,
+
+    /// Size of the block data in bytes.
+    #[serde(rename = "Size")]
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FilesStatResponse {
+    #[cfg(feature = "mfs")]
+    #[serde(rename = "Hash", default)]
+    pub hash: String,
+
+    /// Size of the file data in bytes, 0 for directories.
+    #[serde(rename = "Size")]
+    pub size: u64,
+
+    #[cfg(feature = "mfs")]
+    #[serde(rename = "CumulativeSize", default)]
+    pub cumulative_size: u64,
+
+    /// `file` or `directory`.
+    #[cfg(feature = "mfs")]
+    #[serde(rename = "Type", default)]
+    pub file_type: String,
+
+    /// 0 if not stored.
+    #[cfg(feature = "mfs")]
+    #[serde(rename = "Mode", default)]
+    pub mode: u32,
+
+    /// 0 if not stored.
+    #[cfg(feature = "mfs")]
+    #[serde(rename = "Mtime", default)]
+    pub mtime: i64,
+}
+
+/// Status of a file or directory, see `files_stat`.
+#[cfg(feature = "mfs")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilesStat {
+    pub cid: Cid,
+
+    /// Size of the file data in bytes, 0 for directories.
+    pub size: u64,
+
+    /// Size of the DAG in bytes.
+    pub cumulative_size: u64,
+
+    pub is_dir: bool,
+
+    /// POSIX permissions, if stored.
+    pub mode: Option<u32>,
+
+    /// Modification time in seconds since the Unix epoch, if stored.
+    pub mtime: Option<i64>,
+}
+
+#[cfg(feature = "mfs")]
+impl TryFrom<FilesStatResponse> for FilesStat {
+    type Error = cid::Error;
+
+    fn try_from(response: FilesStatResponse) -> Result<Self, Self::Error> {
+        Ok(Self {
+            cid: Cid::try_from(response.hash)?,
+            size: response.size,
+            cumulative_size: response.cumulative_size,
+            is_dir: response.file_type == "directory",
+            mode: Some(response.mode & PERMISSIONS).filter(|mode| *mode != 0),
+            mtime: Some(response.mtime).filter(|mtime| *mtime != 0),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LsResponse {
+    #[serde(rename = "Objects")]
+    pub objects: Vec<LsObject>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LsObject {
+    #[serde(rename = "Links", default)]
+    pub links: Vec<LsLink>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LsLink {
+    #[serde(rename = "Name", default)]
+    pub name: String,
+
+    #[serde(rename = "Hash")]
+    pub hash: String,
+
+    #[serde(rename = "Size", default)]
+    pub size: u64,
+
+    /// UnixFS data type.
+    #[serde(rename = "Type", default)]
+    pub link_type: i32,
+
+    /// 0 if not stored.
+    #[serde(rename = "Mode", default)]
+    pub mode: u32,
+
+    /// Time as RFC 3339, the zero time of Go if not stored.
+    #[serde(rename = "ModTime", default)]
+    pub mod_time: String,
+}
+
+impl LsLink {
+    /// UnixFS data types of directories, plain and sharded.
+    const DIRECTORIES: [i32; 2] = [1, 5];
+}
+
+/// Entry of a directory, see `ls`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsEntry {
+    pub name: String,
+
+    pub cid: Cid,
+
+    /// Size of the file data in bytes, 0 for directories.
+    pub size: u64,
+
+    pub is_dir: bool,
+
+    /// POSIX permissions, if stored.
+    pub mode: Option<u32>,
+
+    /// Modification time in seconds since the Unix epoch, if stored.
+    pub mtime: Option<i64>,
+}
+
+impl TryFrom<LsLink> for LsEntry {
+    type Error = cid::Error;
+
+    fn try_from(response: LsLink) -> Result<Self, Self::Error> {
+        Ok(Self {
+            cid: Cid::try_from(response.hash)?,
+            name: response.name,
+            size: response.size,
+            is_dir: LsLink::DIRECTORIES.contains(&response.link_type),
+            mode: Some(response.mode & PERMISSIONS).filter(|mode| *mode != 0),
+            mtime: unix_seconds(&response.mod_time),
+        })
+    }
+}
+
+/// Permission bits of a Go `FileMode`, its type bits are dropped.
+const PERMISSIONS: u32 = 0o777;
+
+/// Seconds since the Unix epoch of an RFC 3339 time, e.g. `2023-11-14T23:13:20.5+01:00`.
+///
+/// `None` if it cannot be parsed or for the zero time of Go, year 1.
+fn unix_seconds(time: &str) -> Option<i64> {
+    let (date, rest) = time.split_once('T')?;
+
+    let mut date = date.splitn(3, '-').map(|number| number.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+
+    if year <= 1 {
+        return None;
+    }
+
+    let zone = rest.find(['Z', 'z', '+', '-'])?;
+    let (time_of_day, zone) = rest.split_at(zone);
+
+    // Fractions of a second are dropped
+    let mut time_of_day = time_of_day.splitn(3, ':').map(|number| {
+        let number = number.split('.').next().unwrap_or_default();
+
+        number.parse::<i64>().ok()
+    });
+    let (hour, minute, second) = (
+        time_of_day.next()??,
+        time_of_day.next()??,
+        time_of_day.next()??,
+    );
+
+    let offset = match zone {
+        "Z" | "z" => 0,
+        zone => {
+            let (hours, minutes) = zone[1..].split_once(':')?;
+            let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+
+            match zone.starts_with('-') {
+                true => -offset,
+                false => offset,
+            }
+        }
+    };
+
+    // Days since the epoch of a proleptic Gregorian date, years starting in March
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    Some(days * 86_400 + hour * 3600 + minute * 60 + second - offset)
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct IdResponse {
     #[serde(rename = "ID")]
     pub id: String,
+
+    #[serde(rename = "PublicKey")]
+    pub public_key: String,
+
+    /// Multiaddresses the peer is listening on.
+    #[serde(rename = "Addresses")]
+    pub addresses: Option<Vec<String>>,
+
+    #[serde(rename = "AgentVersion")]
+    pub agent_version: String,
+
+    #[serde(rename = "Protocols")]
+    pub protocols: Option<Vec<String>>,
 }
 
-impl TryFrom<IdResponse> for Cid {
+impl TryFrom<IdResponse> for PeerId {
     type Error = cid::Error;
 
     fn try_from(response: IdResponse) -> Result<Self, Self::Error> {
-        let decoded = Base::Base58Btc.decode(response.id)?;
-        let multihash = MultihashGeneric::from_bytes(&decoded)?;
-        let cid = Cid::new_v1(0x70, multihash);
+        response.id.parse()
+    }
+}
+
+#[cfg(feature = "diagnostics")]
+#[derive(Debug, Deserialize)]
+pub struct ProvideStatsResponse {
+    #[serde(rename = "TotalProvides", default)]
+    pub total_provides: u64,
+
+    #[serde(rename = "LastReprovideBatchSize", default)]
+    pub last_reprovide_batch_size: u64,
+
+    /// Nanoseconds.
+    #[serde(rename = "AvgProvideDuration", default)]
+    pub avg_provide_duration: u64,
+
+    /// Nanoseconds.
+    #[serde(rename = "LastReprovideDuration", default)]
+    pub last_reprovide_duration: u64,
+}
+
+/// Statistics of the provider system, announcing content to the DHT.
+#[cfg(feature = "diagnostics")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProvideStats {
+    pub total_provides: u64,
+    pub last_reprovide_batch_size: u64,
+    pub avg_provide_duration: Duration,
+    pub last_reprovide_duration: Duration,
+}
+
+#[cfg(feature = "diagnostics")]
+impl From<ProvideStatsResponse> for ProvideStats {
+    fn from(response: ProvideStatsResponse) -> Self {
+        Self {
+            total_provides: response.total_provides,
+            last_reprovide_batch_size: response.last_reprovide_batch_size,
+            avg_provide_duration: Duration::from_nanos(response.avg_provide_duration),
+            last_reprovide_duration: Duration::from_nanos(response.last_reprovide_duration),
+        }
+    }
+}
+
+/// Routing table of one DHT of the node, `wan` or `lan`.
+#[cfg(feature = "diagnostics")]
+#[derive(Debug, Clone, Deserialize)]
+pub struct DhtStats {
+    #[serde(rename = "Name")]
+    pub name: String,
+
+    #[serde(rename = "Buckets", default)]
+    pub buckets: Vec<DhtBucket>,
+}
+
+#[cfg(feature = "diagnostics")]
+#[derive(Debug, Clone, Deserialize)]
+pub struct DhtBucket {
+    /// Time as RFC 3339.
+    #[serde(rename = "LastRefresh")]
+    pub last_refresh: String,
+
+    #[serde(rename = "Peers", default)]
+    pub peers: Vec<DhtPeer>,
+}
+
+#[cfg(feature = "diagnostics")]
+#[derive(Debug, Clone, Deserialize)]
+pub struct DhtPeer {
+    #[serde(rename = "ID")]
+    pub id: String,
+
+    #[serde(rename = "Connected")]
+    pub connected: bool,
+
+    #[serde(rename = "AgentVersion", default)]
+    pub agent_version: String,
+
+    /// Time as RFC 3339.
+    #[serde(rename = "LastUsefulAt", default)]
+    pub last_useful_at: String,
+
+    /// Time as RFC 3339.
+    #[serde(rename = "LastQueriedAt", default)]
+    pub last_queried_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PingResponse {
+    #[serde(rename = "Success")]
+    pub success: bool,
+
+    /// Nanoseconds.
+    #[serde(rename = "Time")]
+    pub time: u64,
+
+    #[serde(rename = "Text", default)]
+    pub text: String,
+}
+
+/// Reply of a ping, or a message such as the average latency when `text` is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PingReply {
+    pub success: bool,
+    pub time: Duration,
+    pub text: String,
+}
+
+impl From<PingResponse> for PingReply {
+    fn from(response: PingResponse) -> Self {
+        Self {
+            success: response.success,
+            time: Duration::from_nanos(response.time),
+            text: response.text,
+        }
+    }
+}
+
+/// Event of a routing query, e.g. `routing/findprovs`.
+#[cfg(feature = "dht")]
+#[derive(Debug, Deserialize)]
+pub struct RoutingQueryResponse {
+    #[serde(rename = "Type")]
+    pub event_type: u8,
+
+    #[serde(rename = "Responses", default)]
+    pub responses: Option<Vec<PeerAddrsResponse>>,
+}
+
+#[cfg(feature = "dht")]
+impl RoutingQueryResponse {
+    /// Type of the events listing providers.
+    pub const PROVIDER: u8 = 4;
+}
+
+#[cfg(feature = "dht")]
+#[derive(Debug, Deserialize)]
+pub struct PeerAddrsResponse {
+    #[serde(rename = "ID")]
+    pub id: String,
+
+    #[serde(rename = "Addrs", default)]
+    pub addrs: Option<Vec<String>>,
+}
 
-        Ok(cid)
+/// Peer providing some content.
+#[cfg(feature = "dht")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provider {
+    pub peer_id: PeerId,
+
+    /// Multiaddresses, empty if unknown.
+    pub addrs: Vec<String>,
+}
+
+#[cfg(feature = "dht")]
+impl TryFrom<PeerAddrsResponse> for Provider {
+    type Error = cid::Error;
+
+    fn try_from(response: PeerAddrsResponse) -> Result<Self, Self::Error> {
+        Ok(Self {
+            peer_id: PeerId::from_str(&response.id)?,
+            addrs: response.addrs.unwrap_or_default(),
+        })
     }
 }
 
+/// Fields are optional, unknown ones are denied so that errors are not mistaken for it.
+#[cfg(feature = "diagnostics")]
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepoGcResponse {
+    #[serde(rename = "Key")]
+    pub key: Option<CidString>,
+
+    #[serde(rename = "Error", default)]
+    pub error: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PinAddResponse {
     #[serde(rename = "Pins")]
@@ -159,6 +791,111 @@ pub struct PinAddResponse {
     pub progress: Option<String>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PinType {
+    Direct,
+    Indirect,
+    Recursive,
+}
+
+impl PinType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Direct => "direct",
+            Self::Indirect => "indirect",
+            Self::Recursive => "recursive",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PinLsResponse {
+    #[serde(rename = "Keys")]
+    pub keys: HashMap<String, PinLsEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PinLsEntry {
+    /// "direct", "recursive" or "indirect through <CID>"
+    #[serde(rename = "Type")]
+    pub pin_type: String,
+
+    /// Only listed if names were requested.
+    #[serde(rename = "Name", default)]
+    pub name: String,
+}
+
+impl PinLsEntry {
+    fn pin_type(&self) -> PinType {
+        match self.pin_type.as_str() {
+            "direct" => PinType::Direct,
+            "recursive" => PinType::Recursive,
+            _ => PinType::Indirect,
+        }
+    }
+}
+
+pub type PinList = HashMap<Cid, PinType>;
+
+impl TryFrom<PinLsResponse> for PinList {
+    type Error = cid::Error;
+
+    fn try_from(response: PinLsResponse) -> Result<Self, Self::Error> {
+        let mut map = HashMap::with_capacity(response.keys.len());
+
+        for (key, entry) in response.keys {
+            let cid = Cid::try_from(key)?;
+
+            map.insert(cid, entry.pin_type());
+        }
+
+        Ok(map)
+    }
+}
+
+/// Pins to list, every pin by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PinFilter {
+    pub pin_type: Option<PinType>,
+
+    /// Only pins with a name containing this.
+    pub name: Option<String>,
+
+    /// Only these CIDs, the node fails if one is not pinned.
+    pub cids: Vec<Cid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinInfo {
+    pub pin_type: PinType,
+
+    /// Name given when pinned, if any.
+    pub name: Option<String>,
+}
+
+pub type NamedPinList = HashMap<Cid, PinInfo>;
+
+impl TryFrom<PinLsResponse> for NamedPinList {
+    type Error = cid::Error;
+
+    fn try_from(response: PinLsResponse) -> Result<Self, Self::Error> {
+        let mut map = HashMap::with_capacity(response.keys.len());
+
+        for (key, entry) in response.keys {
+            let cid = Cid::try_from(key)?;
+
+            let info = PinInfo {
+                pin_type: entry.pin_type(),
+                name: Some(entry.name).filter(|name| !name.is_empty()),
+            };
+
+            map.insert(cid, info);
+        }
+
+        Ok(map)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PinRmResponse {
     #[serde(rename = "Pins")]
@@ -0,0 +1,247 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{backoff::Backoff, clock, config, Error};
+
+/// When and how often idempotent requests are retried.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub max_attempts: u32,
+
+    pub backoff: Backoff,
+
+    /// Randomize delays so that clients don't retry in lockstep.
+    pub jitter: bool,
+
+    /// HTTP status codes considered transient.
+    pub retry_statuses: Vec<u16>,
+
+    /// Also retry requests to endpoints that are not idempotent, see `is_idempotent`.
+    ///
+    /// Otherwise they are only sent again if the connection to the node failed, the request never being sent.
+    pub retry_non_idempotent: bool,
+
+    /// Longest `Retry-After` waited for, longer rate limits are returned as `Error::RateLimited`.
+    #[serde(with = "config::duration")]
+    pub max_retry_after: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Backoff {
+                initial: Duration::from_millis(200),
+                max: Duration::from_secs(5),
+                factor: 2,
+            },
+            jitter: true,
+            retry_statuses: vec![429, 502, 503, 504],
+            retry_non_idempotent: false,
+            max_retry_after: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay to wait after failed attempt number `attempt`, starting at 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.backoff.delay(attempt);
+
+        if !self.jitter {
+            return delay;
+        }
+
+        let half = delay / 2;
+        let random = RandomState::new().build_hasher().finish();
+        let range = half.as_nanos() as u64 + 1;
+
+        half + Duration::from_nanos(random % range)
+    }
+
+    /// Delay to wait after failed attempt number `attempt`, at least the `Retry-After` of a rate limit.
+    pub(crate) fn delay_after(
+        &self,
+        attempt: u32,
+        error: &(dyn std::error::Error + 'static),
+    ) -> Duration {
+        let delay = self.delay(attempt);
+
+        match error.downcast_ref::<Error>().and_then(Error::retry_after) {
+            Some(retry_after) => delay.max(retry_after),
+            None => delay,
+        }
+    }
+
+    pub(crate) fn is_retryable_status(&self, status: u16) -> bool {
+        self.retry_statuses.contains(&status)
+    }
+
+    pub(crate) fn is_retryable_error(&self, error: &(dyn std::error::Error + 'static)) -> bool {
+        match error.downcast_ref::<Error>() {
+            Some(Error::Request {
+                status: Some(status),
+                ..
+            }) => self.is_retryable_status(*status),
+            Some(Error::RateLimited {
+                status,
+                retry_after,
+                ..
+            }) => {
+                self.is_retryable_status(*status)
+                    && retry_after.is_none_or(|delay| delay <= self.max_retry_after)
+            }
+            Some(Error::Request { source, .. }) => {
+                source.downcast_ref::<reqwest::Error>().is_some()
+            }
+            Some(Error::PathNotFound { .. }) => false,
+            None => error.downcast_ref::<reqwest::Error>().is_some(),
+        }
+    }
+}
+
+/// Endpoints whose requests have the same effect however many times they are sent.
+///
+/// Reads, and pinning which leaves the CID pinned once.
+const IDEMPOTENT: [&str; 36] = [
+    "bitswap/stat",
+    "block/get",
+    "block/stat",
+    "cat",
+    "commands",
+    "dag/export",
+    "dag/get",
+    "dag/resolve",
+    "dht/findpeer",
+    "dht/findprovs",
+    "dht/get",
+    "files/ls",
+    "files/read",
+    "files/stat",
+    "id",
+    "key/list",
+    "key/sign",
+    "key/verify",
+    "ls",
+    "name/resolve",
+    "pin/add",
+    "pin/ls",
+    "ping",
+    "refs",
+    "refs/local",
+    "repo/stat",
+    "resolve",
+    "routing/findpeer",
+    "routing/findprovs",
+    "routing/get",
+    "stats/bw",
+    "stats/dht",
+    "stats/provide",
+    "stats/repo",
+    "swarm/peers",
+    "version",
+];
+
+/// Whether sending a request to this endpoint, e.g. `pin/add`, more than once has the effect of sending it once.
+///
+/// Unknown endpoints are not: adding content, publishing records and messages, generating keys or
+/// collecting garbage may happen again with each request.
+pub fn is_idempotent(endpoint: &str) -> bool {
+    IDEMPOTENT.contains(&endpoint)
+}
+
+/// Returns true if the request failed before reaching the node or was refused by it, making it safe to send again.
+///
+/// A node answering 429 did not process the request.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn is_unsent(error: &(dyn std::error::Error + 'static)) -> bool {
+    let error = match error.downcast_ref::<Error>() {
+        Some(Error::RateLimited { status: 429, .. }) => return true,
+        Some(Error::Request {
+            status: None,
+            source,
+            ..
+        }) => source.as_ref(),
+        Some(_) => return false,
+        None => error,
+    };
+
+    matches!(error.downcast_ref::<reqwest::Error>(), Some(error) if error.is_connect())
+}
+
+/// Browsers do not tell connection failures apart, only requests refused with 429 are known to be unsent.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn is_unsent(error: &(dyn std::error::Error + 'static)) -> bool {
+    matches!(
+        error.downcast_ref::<Error>(),
+        Some(Error::RateLimited { status: 429, .. })
+    )
+}
+
+/// Delay of a `Retry-After` header, in seconds or until an HTTP date.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = parse_http_date(value)?;
+
+    Some(date.saturating_sub(clock::since_epoch()))
+}
+
+/// Time since the unix epoch of a date like `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn parse_http_date(value: &str) -> Option<Duration> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let parts: Vec<&str> = value.split_whitespace().collect();
+
+    let (day, month, year, time) = match parts[..] {
+        [_, day, month, year, time, "GMT"] => (day, month, year, time),
+        _ => return None,
+    };
+
+    let day: u64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|name| *name == month)? as u64 + 1;
+    let year: u64 = year.parse().ok()?;
+
+    let time: Vec<u64> = time
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+
+    let (hours, minutes, seconds) = match time[..] {
+        [hours, minutes, seconds] => (hours, minutes, seconds),
+        _ => return None,
+    };
+
+    if year < 1970 || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Days since the epoch of a proleptic gregorian date, years starting in March
+    let (year, month) = if month > 2 {
+        (year, month - 3)
+    } else {
+        (year - 1, month + 9)
+    };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146097 + day_of_era).checked_sub(719468)?;
+
+    let seconds = days * 86400 + hours * 3600 + minutes * 60 + seconds;
+
+    Some(Duration::from_secs(seconds))
+}
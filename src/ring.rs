@@ -0,0 +1,64 @@
+/// Consistent hash ring mapping keys to node indices.
+pub(crate) struct HashRing {
+    /// Points sorted by hash, each owned by a node index.
+    points: Vec<(u64, usize)>,
+    nodes: usize,
+}
+
+impl HashRing {
+    /// Place `virtual_nodes` points on the ring for each node name.
+    pub fn new<'a, I>(names: I, virtual_nodes: usize) -> Self
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut points = Vec::new();
+        let mut nodes = 0;
+
+        for (index, name) in names.into_iter().enumerate() {
+            for replica in 0..virtual_nodes.max(1) {
+                let point = format!("{}#{}", name, replica);
+
+                points.push((fnv1a(point.as_bytes()), index));
+            }
+
+            nodes += 1;
+        }
+
+        points.sort_unstable();
+
+        Self { points, nodes }
+    }
+
+    /// Node indices owning the key, the owner first then the next distinct nodes clockwise.
+    pub fn lookup(&self, key: &[u8]) -> Vec<usize> {
+        let hash = fnv1a(key);
+
+        let start = self.points.partition_point(|(point, _)| *point < hash);
+
+        let mut order = Vec::with_capacity(self.nodes);
+
+        for i in 0..self.points.len() {
+            let (_, index) = self.points[(start + i) % self.points.len()];
+
+            if !order.contains(&index) {
+                order.push(index);
+
+                if order.len() == self.nodes {
+                    break;
+                }
+            }
+        }
+
+        order
+    }
+}
+
+/// 64 bits FNV-1a, stable across platforms and releases unlike the std hasher.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    bytes.iter().fold(OFFSET, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(PRIME)
+    })
+}
@@ -0,0 +1,165 @@
+use std::{
+    cell::Cell,
+    collections::hash_map::RandomState,
+    future::Future,
+    hash::{BuildHasher, Hash, Hasher},
+    time::Duration,
+};
+
+use futures_util::{
+    future::{self, Either},
+    pin_mut, Stream, StreamExt,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{clock, codec::Codec, peer_id::PeerId, pubsub::Subscription, IpfsService, Result};
+
+/// How long to wait for a reply and how many times to publish the request again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RpcOptions {
+    pub timeout: Duration,
+    pub retries: u32,
+}
+
+impl Default for RpcOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            retries: 2,
+        }
+    }
+}
+
+/// Request or reply, correlated by id.
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    id: u64,
+    body: T,
+}
+
+thread_local! {
+    static NEXT_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Id unlikely to be used by another peer at the same time.
+fn request_id() -> u64 {
+    let count = NEXT_ID.with(|next| {
+        let count = next.get();
+        next.set(count.wrapping_add(1));
+        count
+    });
+
+    let mut hasher = RandomState::new().build_hasher();
+    clock::since_epoch().hash(&mut hasher);
+    count.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Topic the replies to this request are published on.
+fn reply_topic(topic: &[u8], id: u64) -> Vec<u8> {
+    let mut reply = topic.to_vec();
+    reply.extend_from_slice(format!("/reply/{:016x}", id).as_bytes());
+    reply
+}
+
+impl IpfsService {
+    /// Publish the request on the topic and wait for the reply of a peer serving it, see `pubsub_serve`.
+    ///
+    /// The request is published again, with the same id, each time no reply came within the timeout.
+    pub async fn pubsub_request<T, Req, Resp>(
+        &self,
+        topic: T,
+        request: &Req,
+        codec: Codec,
+        options: RpcOptions,
+    ) -> Result<Resp>
+    where
+        T: AsRef<[u8]>,
+        Req: ?Sized + Serialize,
+        Resp: DeserializeOwned,
+    {
+        let topic = topic.as_ref();
+        let id = request_id();
+
+        let (replies, _subscription) = self.pubsub_sub(reply_topic(topic, id)).await?;
+
+        pin_mut!(replies);
+
+        let data = codec.encode(&Envelope { id, body: request })?;
+
+        for _ in 0..=options.retries {
+            self.pubsub_pub(topic, data.clone()).await?;
+
+            let reply = async {
+                while let Some(msg) = replies.next().await {
+                    match codec.decode::<Envelope<Resp>>(&msg?.data) {
+                        Ok(reply) if reply.id == id => return Ok(reply.body),
+                        _ => continue,
+                    }
+                }
+
+                Err("Reply subscription ended".into())
+            };
+
+            pin_mut!(reply);
+
+            let delay = futures_timer::Delay::new(options.timeout);
+
+            if let Either::Left((res, _)) = future::select(reply, delay).await {
+                return res;
+            }
+        }
+
+        Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "No reply to the request").into())
+    }
+
+    /// Answer the requests published on the topic with the handler, one at a time, until the subscription is cancelled.
+    ///
+    /// The handler is called with the peer requesting. Retried requests are answered again, handlers should be idempotent.
+    /// Requests that could not be decoded or answered are yielded as errors.
+    pub async fn pubsub_serve<T, Req, Resp, F, Fut>(
+        &self,
+        topic: T,
+        codec: Codec,
+        handler: F,
+    ) -> Result<(impl Stream<Item = Result<()>>, Subscription)>
+    where
+        T: AsRef<[u8]>,
+        Req: DeserializeOwned,
+        Resp: Serialize,
+        F: Fn(PeerId, Req) -> Fut,
+        Fut: Future<Output = Result<Resp>>,
+    {
+        let topic = topic.as_ref().to_vec();
+
+        let (requests, subscription) = self.pubsub_sub(topic.clone()).await?;
+
+        let ipfs = self.clone();
+
+        let stream = requests.then(move |msg| {
+            let request = msg.and_then(|msg| {
+                let request = codec.decode::<Envelope<Req>>(&msg.data)?;
+
+                Ok((msg.from, request))
+            });
+
+            let ipfs = ipfs.clone();
+            let topic = topic.clone();
+            let reply = request.map(|(from, request)| (request.id, handler(from, request.body)));
+
+            async move {
+                let (id, reply) = reply?;
+
+                let data = codec.encode(&Envelope {
+                    id,
+                    body: reply.await?,
+                })?;
+
+                ipfs.pubsub_pub(reply_topic(&topic, id), data).await
+            }
+        });
+
+        Ok((stream, subscription))
+    }
+}
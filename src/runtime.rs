@@ -0,0 +1,35 @@
+/// Run the future or stream in the Tokio context the native HTTP client needs,
+/// whichever executor polls it.
+#[cfg(all(feature = "compat", not(target_arch = "wasm32")))]
+pub(crate) fn compat<T>(inner: T) -> async_compat::Compat<T> {
+    async_compat::Compat::new(inner)
+}
+
+/// Run the future or stream in the Tokio context the native HTTP client needs,
+/// whichever executor polls it.
+#[cfg(not(all(feature = "compat", not(target_arch = "wasm32"))))]
+pub(crate) fn compat<T>(inner: T) -> T {
+    inner
+}
+
+/// Poll each item of the stream with `compat`.
+#[cfg(all(feature = "compat", not(target_arch = "wasm32")))]
+pub(crate) fn compat_stream<S>(stream: S) -> futures_util::stream::LocalBoxStream<'static, S::Item>
+where
+    S: futures_util::Stream + 'static,
+{
+    use futures_util::StreamExt;
+
+    futures_util::stream::unfold(Box::pin(stream), |mut stream| async move {
+        let item = compat(stream.next()).await?;
+
+        Some((item, stream))
+    })
+    .boxed_local()
+}
+
+/// Poll each item of the stream with `compat`.
+#[cfg(not(all(feature = "compat", not(target_arch = "wasm32"))))]
+pub(crate) fn compat_stream<S>(stream: S) -> S {
+    stream
+}
@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use reqwest::{Request, Url};
+
+use tracing::{field, Span};
+
+use crate::{clock, endpoint, redact, Result};
+
+/// Span of a request to the node API, status and duration are recorded on completion.
+pub(crate) fn request_span(base_url: &Url, request: &Request) -> Span {
+    let url = request.url();
+
+    let endpoint = endpoint(base_url, url);
+
+    let arg = url
+        .query_pairs()
+        .find(|(key, _)| key == "arg")
+        .map(|(_, value)| value.into_owned())
+        .unwrap_or_default();
+
+    tracing::debug_span!(
+        "ipfs_request",
+        node = %redact::url(base_url),
+        endpoint,
+        arg,
+        status = field::Empty,
+        duration_ms = field::Empty,
+    )
+}
+
+pub(crate) fn record(span: &Span, result: &Result<(u16, Bytes)>, start: Duration) {
+    span.record(
+        "duration_ms",
+        clock::now().saturating_sub(start).as_millis() as u64,
+    );
+
+    match result {
+        Ok((status, bytes)) => {
+            span.record("status", status);
+
+            tracing::debug!(parent: span, bytes = bytes.len(), "response");
+        }
+        Err(e) => tracing::debug!(parent: span, error = %e, "request failed"),
+    }
+}
@@ -0,0 +1,479 @@
+use std::collections::{BTreeMap, HashSet};
+
+use bytes::Bytes;
+
+use cid::Cid;
+
+use multihash::{Code, MultihashDigest};
+
+use reqwest::multipart::{Form, Part};
+
+use crate::{
+    car::write_car,
+    responses::{DagImportResponse, IPFSError},
+    IpfsService, Result,
+};
+
+const RAW_CODEC: u64 = 0x55;
+const DAG_PB_CODEC: u64 = 0x70;
+
+/// UnixFS node types.
+const DIRECTORY: u64 = 1;
+const FILE: u64 = 2;
+const HAMT_SHARD: u64 = 5;
+
+/// Multicodec of the hash placing entries in HAMT shards.
+const MURMUR3_X64_64: u64 = 0x22;
+
+/// Width of HAMT shards, each level consuming a byte of the hash.
+const HAMT_FANOUT: u64 = 256;
+
+/// Files or directories built locally, see `UnixFsBuilder`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnixFsDag {
+    pub root: Cid,
+
+    /// Every block of the DAG once, children first.
+    pub blocks: Vec<(Cid, Bytes)>,
+}
+
+impl UnixFsDag {
+    /// CARv1 file of the DAG, to upload with `dag_import`.
+    pub fn to_car(&self) -> Bytes {
+        write_car(&[self.root], &self.blocks)
+    }
+}
+
+/// Build UnixFS DAGs offline, laid out like `add` does with CIDv1.
+///
+/// Files are split in fixed size chunks stored as raw blocks, linked by a balanced tree of dag-pb nodes.
+/// Directories over the shard threshold are sharded in a HAMT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnixFsBuilder {
+    chunk_size: usize,
+    max_links: usize,
+    shard_threshold: Option<usize>,
+}
+
+impl Default for UnixFsBuilder {
+    fn default() -> Self {
+        Self {
+            chunk_size: 256 * 1024,
+            max_links: 174,
+            shard_threshold: Some(256 * 1024),
+        }
+    }
+}
+
+/// Entry of a directory being built.
+enum Entry {
+    File(Bytes),
+    Directory(BTreeMap<String, Entry>),
+}
+
+struct Link {
+    name: String,
+    cid: Cid,
+
+    /// Cumulative size of the linked DAG.
+    tsize: u64,
+}
+
+/// Blocks of the DAG being built.
+#[derive(Default)]
+struct Blocks {
+    blocks: Vec<(Cid, Bytes)>,
+    seen: HashSet<Cid>,
+}
+
+impl Blocks {
+    fn push(&mut self, codec: u64, data: Vec<u8>) -> Cid {
+        let cid = Cid::new_v1(codec, Code::Sha2_256.digest(&data));
+
+        if self.seen.insert(cid) {
+            self.blocks.push((cid, data.into()));
+        }
+
+        cid
+    }
+}
+
+impl UnixFsBuilder {
+    /// Bytes per leaf block, 256 KiB by default.
+    pub fn with_chunk_size(&self, chunk_size: usize) -> Self {
+        Self {
+            chunk_size: chunk_size.max(1),
+            ..*self
+        }
+    }
+
+    /// Links per node of file trees, 174 by default.
+    pub fn with_max_links(&self, max_links: usize) -> Self {
+        Self {
+            max_links: max_links.max(2),
+            ..*self
+        }
+    }
+
+    /// Shard directories whose names and CIDs of entries add up to this many bytes, 256 KiB by default.
+    ///
+    /// `None` never shards.
+    pub fn with_shard_threshold(&self, shard_threshold: Option<usize>) -> Self {
+        Self {
+            shard_threshold,
+            ..*self
+        }
+    }
+
+    /// DAG of the file, a raw block if it fits in a chunk.
+    pub fn file(&self, data: Bytes) -> UnixFsDag {
+        let mut blocks = Blocks::default();
+
+        let root = self.build_file(&mut blocks, data);
+
+        UnixFsDag {
+            root: root.cid,
+            blocks: blocks.blocks,
+        }
+    }
+
+    /// DAG of files under a wrapping directory, named by their path in it, e.g. `images/cat.png`.
+    ///
+    /// Same layout as `add_files`, the root being the wrapping directory.
+    pub fn directory<I, N>(&self, files: I) -> Result<UnixFsDag>
+    where
+        I: IntoIterator<Item = (N, Bytes)>,
+        N: Into<String>,
+    {
+        let mut tree = BTreeMap::new();
+
+        for (path, bytes) in files {
+            let path = path.into();
+
+            insert(&mut tree, &path, bytes)?;
+        }
+
+        let mut blocks = Blocks::default();
+
+        let root = self.build_directory(&mut blocks, tree)?;
+
+        Ok(UnixFsDag {
+            root: root.cid,
+            blocks: blocks.blocks,
+        })
+    }
+
+    fn build_file(&self, blocks: &mut Blocks, data: Bytes) -> Link {
+        // Raw leaves with the size of their data
+        let mut level: Vec<(Link, u64)> = match data.is_empty() {
+            true => vec![(raw_leaf(blocks, &[]), 0)],
+            false => data
+                .chunks(self.chunk_size)
+                .map(|chunk| (raw_leaf(blocks, chunk), chunk.len() as u64))
+                .collect(),
+        };
+
+        while level.len() > 1 {
+            level = level
+                .chunks(self.max_links)
+                .map(|children| {
+                    let filesize = children.iter().map(|(_, size)| size).sum();
+
+                    let mut unixfs = Vec::new();
+                    protobuf_varint(&mut unixfs, 1, FILE);
+                    protobuf_varint(&mut unixfs, 3, filesize);
+
+                    for (_, size) in children {
+                        protobuf_varint(&mut unixfs, 4, *size);
+                    }
+
+                    let links = children.iter().map(|(link, _)| link);
+
+                    (dag_pb_node(blocks, String::new(), links, &unixfs), filesize)
+                })
+                .collect();
+        }
+
+        level.remove(0).0
+    }
+
+    fn build_directory(
+        &self,
+        blocks: &mut Blocks,
+        entries: BTreeMap<String, Entry>,
+    ) -> Result<Link> {
+        let mut links = Vec::with_capacity(entries.len());
+
+        for (name, entry) in entries {
+            let link = match entry {
+                Entry::File(bytes) => self.build_file(blocks, bytes),
+                Entry::Directory(entries) => self.build_directory(blocks, entries)?,
+            };
+
+            links.push(Link { name, ..link });
+        }
+
+        let size: usize = links
+            .iter()
+            .map(|link| link.name.len() + link.cid.to_bytes().len())
+            .sum();
+
+        if matches!(self.shard_threshold, Some(threshold) if size >= threshold) {
+            return build_shard(blocks, links, 0);
+        }
+
+        let mut unixfs = Vec::new();
+        protobuf_varint(&mut unixfs, 1, DIRECTORY);
+
+        Ok(dag_pb_node(blocks, String::new(), links.iter(), &unixfs))
+    }
+}
+
+/// Add the file at this path to the tree, creating its directories.
+fn insert(tree: &mut BTreeMap<String, Entry>, path: &str, bytes: Bytes) -> Result<()> {
+    let (name, rest) = match path.split_once('/') {
+        Some((name, rest)) => (name, Some(rest)),
+        None => (path, None),
+    };
+
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(format!("Invalid file path {}", path).into());
+    }
+
+    match (rest, tree.get_mut(name)) {
+        (None, None) => {
+            tree.insert(name.to_owned(), Entry::File(bytes));
+        }
+        (Some(rest), None) => {
+            let mut entries = BTreeMap::new();
+            insert(&mut entries, rest, bytes)?;
+            tree.insert(name.to_owned(), Entry::Directory(entries));
+        }
+        (Some(rest), Some(Entry::Directory(entries))) => insert(entries, rest, bytes)?,
+        _ => return Err(format!("Conflicting file path {}", path).into()),
+    }
+
+    Ok(())
+}
+
+fn raw_leaf(blocks: &mut Blocks, data: &[u8]) -> Link {
+    Link {
+        name: String::new(),
+        cid: blocks.push(RAW_CODEC, data.to_vec()),
+        tsize: data.len() as u64,
+    }
+}
+
+/// HAMT shard of the links at this depth, entries of colliding indexes in child shards.
+fn build_shard(blocks: &mut Blocks, links: Vec<Link>, depth: usize) -> Result<Link> {
+    if depth >= 8 {
+        return Err("HAMT hash exhausted".into());
+    }
+
+    let mut buckets: BTreeMap<u8, Vec<Link>> = BTreeMap::new();
+
+    for link in links {
+        let index = murmur3_x64_64(link.name.as_bytes()).to_be_bytes()[depth];
+
+        buckets.entry(index).or_default().push(link);
+    }
+
+    let mut bitfield = [0u8; (HAMT_FANOUT / 8) as usize];
+    let mut children = Vec::with_capacity(buckets.len());
+
+    for (index, mut bucket) in buckets {
+        bitfield[bitfield.len() - 1 - index as usize / 8] |= 1 << (index % 8);
+
+        let child = match bucket.len() {
+            1 => {
+                let link = bucket.remove(0);
+
+                Link {
+                    name: format!("{:02X}{}", index, link.name),
+                    ..link
+                }
+            }
+            _ => Link {
+                name: format!("{:02X}", index),
+                ..build_shard(blocks, bucket, depth + 1)?
+            },
+        };
+
+        children.push(child);
+    }
+
+    let start = bitfield
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bitfield.len());
+
+    let mut unixfs = Vec::new();
+    protobuf_varint(&mut unixfs, 1, HAMT_SHARD);
+    protobuf_bytes(&mut unixfs, 2, &bitfield[start..]);
+    protobuf_varint(&mut unixfs, 5, MURMUR3_X64_64);
+    protobuf_varint(&mut unixfs, 6, HAMT_FANOUT);
+
+    Ok(dag_pb_node(blocks, String::new(), children.iter(), &unixfs))
+}
+
+/// Encode a dag-pb node, links first as in the canonical form.
+fn dag_pb_node<'a, I>(blocks: &mut Blocks, name: String, links: I, unixfs: &[u8]) -> Link
+where
+    I: Iterator<Item = &'a Link>,
+{
+    let mut node = Vec::new();
+    let mut tsize = 0;
+
+    for link in links {
+        let mut encoded = Vec::new();
+        protobuf_bytes(&mut encoded, 1, &link.cid.to_bytes());
+        protobuf_bytes(&mut encoded, 2, link.name.as_bytes());
+        protobuf_varint(&mut encoded, 3, link.tsize);
+
+        protobuf_bytes(&mut node, 2, &encoded);
+
+        tsize += link.tsize;
+    }
+
+    protobuf_bytes(&mut node, 1, unixfs);
+
+    tsize += node.len() as u64;
+
+    Link {
+        name,
+        cid: blocks.push(DAG_PB_CODEC, node),
+        tsize,
+    }
+}
+
+fn write_uvarint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+
+    out.push(value as u8);
+}
+
+fn protobuf_varint(out: &mut Vec<u8>, field: u64, value: u64) {
+    write_uvarint(out, field << 3);
+    write_uvarint(out, value);
+}
+
+fn protobuf_bytes(out: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    write_uvarint(out, field << 3 | 2);
+    write_uvarint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// First half of MurmurHash3 x64 128 with a zero seed, as hashed by HAMT shards.
+fn murmur3_x64_64(data: &[u8]) -> u64 {
+    const C1: u64 = 0x87c3_7b91_1142_53d5;
+    const C2: u64 = 0x4cf5_ad43_2745_937f;
+
+    let (mut h1, mut h2) = (0u64, 0u64);
+
+    let mut chunks = data.chunks_exact(16);
+
+    for chunk in &mut chunks {
+        let k1 = u64::from_le_bytes(chunk[..8].try_into().unwrap());
+        let k2 = u64::from_le_bytes(chunk[8..].try_into().unwrap());
+
+        h1 ^= k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+        h1 = h1
+            .rotate_left(27)
+            .wrapping_add(h2)
+            .wrapping_mul(5)
+            .wrapping_add(0x52dc_e729);
+
+        h2 ^= k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+        h2 = h2
+            .rotate_left(31)
+            .wrapping_add(h1)
+            .wrapping_mul(5)
+            .wrapping_add(0x3849_5ab5);
+    }
+
+    let tail = chunks.remainder();
+
+    let (mut k1, mut k2) = (0u64, 0u64);
+
+    for (i, byte) in tail.iter().enumerate() {
+        match i < 8 {
+            true => k1 |= (*byte as u64) << (8 * i),
+            false => k2 |= (*byte as u64) << (8 * (i - 8)),
+        }
+    }
+
+    if tail.len() > 8 {
+        h2 ^= k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+    }
+
+    if !tail.is_empty() {
+        h1 ^= k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+    }
+
+    h1 ^= data.len() as u64;
+    h2 ^= data.len() as u64;
+
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+
+    fmix64(h1).wrapping_add(fmix64(h2))
+}
+
+fn fmix64(mut k: u64) -> u64 {
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    k ^ (k >> 33)
+}
+
+impl IpfsService {
+    /// Import the blocks of a CAR file, pinning its roots. Returns the roots.
+    pub async fn dag_import(&self, car: Bytes) -> Result<Vec<Cid>> {
+        let form = Form::new().part("file", Part::stream(car));
+
+        let url = self.base_url.join("dag/import")?;
+
+        let request = self
+            .client
+            .post(url)
+            .query(&[("pin-roots", "true")])
+            .multipart(form);
+
+        let bytes = self.send(request).await?;
+
+        let mut roots = Vec::new();
+
+        for line in bytes.split(|byte| *byte == b'\n') {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+
+            if let Ok(res) = serde_json::from_slice::<DagImportResponse>(line) {
+                if !res.root.pin_error_msg.is_empty() {
+                    return Err(res.root.pin_error_msg.into());
+                }
+
+                roots.push(Cid::try_from(res.root.cid.cid_string)?);
+
+                continue;
+            }
+
+            let error = serde_json::from_slice::<IPFSError>(line)?;
+
+            return Err(error.into());
+        }
+
+        Ok(roots)
+    }
+
+    /// Import a DAG built by `UnixFsBuilder`, pinning its root.
+    pub async fn import_unixfs(&self, dag: &UnixFsDag) -> Result<Cid> {
+        self.dag_import(dag.to_car()).await?;
+
+        Ok(dag.root)
+    }
+}
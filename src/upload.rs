@@ -0,0 +1,88 @@
+use bytes::{Bytes, BytesMut};
+
+use futures_util::{Stream, StreamExt, TryStreamExt};
+
+use js_sys::{Function, Uint8Array};
+
+use wasm_bindgen::{JsCast, JsValue};
+
+use crate::Result;
+
+/// Feature detection of streaming request bodies, https://developer.chrome.com/docs/capabilities/web-apis/fetch-streaming-requests
+const DETECT_REQUEST_STREAMS: &str = "
+    let duplexAccessed = false;
+    const hasContentType = new Request('', {
+        body: new ReadableStream(),
+        method: 'POST',
+        get duplex() {
+            duplexAccessed = true;
+            return 'half';
+        },
+    }).headers.has('Content-Type');
+    return duplexAccessed && !hasContentType;
+";
+
+/// Returns true if fetch can send a stream as request body.
+///
+/// False if the check cannot run, e.g. when the content security policy forbids `Function`.
+pub(crate) fn supports_request_streams() -> bool {
+    let detect = Function::new_no_args(DETECT_REQUEST_STREAMS);
+
+    match detect.call0(&JsValue::NULL) {
+        Ok(supported) => supported.as_bool().unwrap_or(false),
+        Err(_) => false,
+    }
+}
+
+/// Chunks of a browser stream of `Uint8Array`.
+pub(crate) fn readable_stream(
+    stream: web_sys::ReadableStream,
+) -> impl Stream<Item = Result<Bytes>> {
+    wasm_streams::ReadableStream::from_raw(stream)
+        .into_stream()
+        .map(|chunk| {
+            let chunk = chunk.map_err(js_error)?;
+
+            let array = chunk
+                .dyn_into::<Uint8Array>()
+                .map_err(|_| "Stream chunk is not a Uint8Array")?;
+
+            Ok(Bytes::from(array.to_vec()))
+        })
+}
+
+/// Browser stream of `Uint8Array` from the chunks.
+pub(crate) fn into_readable_stream<S>(stream: S) -> web_sys::ReadableStream
+where
+    S: Stream<Item = Result<Bytes>> + 'static,
+{
+    let stream = stream.map(|chunk| match chunk {
+        Ok(bytes) => Ok(Uint8Array::from(&bytes[..]).into()),
+        Err(error) => Err(JsValue::from_str(&error.to_string())),
+    });
+
+    wasm_streams::ReadableStream::from_stream(stream).into_raw()
+}
+
+/// Read the stream to the end.
+pub(crate) async fn collect<S>(stream: S) -> Result<Bytes>
+where
+    S: Stream<Item = Result<Bytes>>,
+{
+    let data = stream
+        .try_fold(BytesMut::new(), |mut data, chunk| async move {
+            data.extend_from_slice(&chunk);
+
+            Ok(data)
+        })
+        .await?;
+
+    Ok(data.freeze())
+}
+
+pub(crate) fn js_error(error: JsValue) -> Box<dyn std::error::Error> {
+    match error.as_string() {
+        Some(message) => message.into(),
+        None => format!("{:?}", error).into(),
+    }
+}
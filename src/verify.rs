@@ -0,0 +1,43 @@
+use core::fmt;
+
+use cid::Cid;
+
+use multihash::{Code, MultihashDigest};
+
+use crate::Result;
+
+/// Returned when data does not hash to the CID it was requested with.
+pub struct IntegrityError {
+    pub cid: Cid,
+}
+
+impl std::error::Error for IntegrityError {}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Data does not match {}", self.cid)
+    }
+}
+
+impl fmt::Debug for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Multihash code of data inlined in the CID.
+const IDENTITY: u64 = 0x00;
+
+/// Hash the data with the CID hash function and compare the digests.
+pub(crate) fn verify(cid: &Cid, data: &[u8]) -> Result<()> {
+    let matches = match cid.hash().code() {
+        IDENTITY => cid.hash().digest() == data,
+        code => Code::try_from(code)?.digest(data) == *cid.hash(),
+    };
+
+    if !matches {
+        return Err(IntegrityError { cid: *cid }.into());
+    }
+
+    Ok(())
+}
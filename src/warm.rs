@@ -0,0 +1,58 @@
+use cid::Cid;
+
+use futures_util::{stream, Stream, StreamExt};
+
+use crate::{IpfsService, Result};
+
+/// Number of blocks requested at once when warming.
+const WARM_CONCURRENCY: usize = 8;
+
+/// A block of the DAG is now local to the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WarmProgress {
+    pub cid: Cid,
+
+    /// Blocks done so far, this one included.
+    pub done: usize,
+
+    /// Blocks in the DAG, the root included.
+    pub total: usize,
+}
+
+impl IpfsService {
+    /// Make the node fetch the DAG into its repo, following links at most `depth` levels deep, `None` to follow all of them.
+    ///
+    /// Blocks are listed with `refs` then fetched with `block_stat`.
+    /// Failed blocks are reported and count as done.
+    pub fn warm(
+        &self,
+        root: Cid,
+        depth: Option<usize>,
+    ) -> impl Stream<Item = Result<WarmProgress>> + '_ {
+        stream::once(self.refs(root, depth))
+            .map(move |res| match res {
+                Ok(refs) => {
+                    let cids: Vec<Cid> = std::iter::once(root)
+                        .chain(refs.into_iter().filter(|cid| *cid != root))
+                        .collect();
+
+                    let total = cids.len();
+
+                    stream::iter(cids)
+                        .map(move |cid| async move { self.block_stat(cid).await.map(|_| cid) })
+                        .buffer_unordered(WARM_CONCURRENCY)
+                        .enumerate()
+                        .map(move |(i, res)| {
+                            res.map(|cid| WarmProgress {
+                                cid,
+                                done: i + 1,
+                                total,
+                            })
+                        })
+                        .left_stream()
+                }
+                Err(e) => stream::iter(Some(Err(e))).right_stream(),
+            })
+            .flatten()
+    }
+}
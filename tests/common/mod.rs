@@ -0,0 +1,42 @@
+//! Setup shared by the desktop and browser tests.
+
+use ipfs_multi_client::{Fixtures, IpfsService, Recorded};
+use reqwest::Url;
+
+/// Address of the node of `replay_service`, unreachable so that unrecorded requests fail.
+const REPLAY_URL: &str = "http://127.0.0.1:1/api/v0/";
+
+/// Successful responses with these bodies, keyed by request.
+pub fn fixtures<I, K, T>(responses: I) -> Fixtures
+where
+    I: IntoIterator<Item = (K, T)>,
+    K: Into<String>,
+    T: Into<String>,
+{
+    let responses = responses
+        .into_iter()
+        .map(|(key, text)| {
+            let recorded = Recorded {
+                status: 200,
+                text: Some(text.into()),
+                binary: None,
+            };
+
+            (key.into(), recorded)
+        })
+        .collect();
+
+    Fixtures { responses }
+}
+
+/// Service replaying successful responses with these bodies, keyed by request, see `fixtures`.
+pub fn replay_service<I, K, T>(responses: I) -> IpfsService
+where
+    I: IntoIterator<Item = (K, T)>,
+    K: Into<String>,
+    T: Into<String>,
+{
+    let url = Url::parse(REPLAY_URL).unwrap();
+
+    IpfsService::new(url).with_replay(fixtures(responses))
+}